@@ -1,4 +1,7 @@
 use ai_functions::ai_function;
+use crate::models::agents::agent_traits::ProjectScope;
+use crate::typed_ai_function;
+use serde::Serialize;
 
 #[ai_function]
 pub fn print_project_scope(_project_description: &str) {
@@ -43,3 +46,19 @@ pub fn print_site_urls(_project_description: &str) {
     /// ["https://api.binance.com/api/v3/exchangeInfo", "https://api.binance.com/api/v3/klines?symbol=BTCUSDT&interval=1d"]
     println!(OUTPUT)
 }
+
+#[derive(Debug, Serialize)]
+pub struct ProjectScopeInput {
+    pub project_description: String,
+    // what was wrong with the scope last time, when it is redone
+    pub feedback_on_previous_scope: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SiteUrlsInput {
+    pub project_description: String,
+    pub do_not_use_urls: Option<Vec<String>>,
+}
+
+typed_ai_function!(pub PrintProjectScope = print_project_scope(ProjectScopeInput) -> ProjectScope);
+typed_ai_function!(pub PrintSiteUrls = print_site_urls(SiteUrlsInput) -> Vec<String>);
@@ -1,35 +1,93 @@
 use ai_functions::ai_function;
+use crate::helpers::workspace::FileMap;
+use crate::models::agents::agent_traits::RouteObject;
+use crate::typed_ai_function;
+use serde::Serialize;
 
 #[ai_function]
 pub fn print_backend_webserver_code(_project_description_and_template: &str) {
-    /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, a CODE_TEMPLATE and the CURRENT_FILE_TREE for a website backend build
     /// IMPORTANT: The backend code is ONLY an example. If the Project Description requires it, make as many changes as you like.
     /// IMPORTANT: You do not need to follow the backend code exactly. Write functions that make sense for the users request if required.
     /// FUNCTION: Takes an existing set of code marked as CODE_TEMPLATE and updates or re-writes it to work for the purpose in the PROJECT_DESCRIPTION
+    /// IMPORTANT: Split the code into modules where it helps, for example src/main.rs, src/models.rs, src/routes.rs, src/db.rs, config/ and tests/
+    /// IMPORTANT: src/main.rs must exist and declare every module it uses. Files may only be written inside src/, tests/ and config/
+    /// IMPORTANT: If a DATA_LAYER is given, src/repository.rs is already written and must not be changed. Declare `pub mod repository;` in src/lib.rs,
+    ///   call `repository::connect()` once at startup, share the Repository through web::Data and use it for all persistence
+    /// IMPORTANT: If an AUTH_MODULE is given, src/auth.rs is already written and must not be changed. Do not write your own login code.
+    ///   Declare `pub mod auth;` in src/lib.rs, call `cfg.configure(auth::configure)` inside configure, register
+    ///   `web::Data::new(auth::AuthState::load()?)` as app data once at startup and add an `auth::AuthUser` argument to every handler that needs a logged in user
+    /// IMPORTANT: The server binds to the BIND_ADDRESS environment variable, defaulting to 127.0.0.1:6678, and serves GET /health returning 200
+    /// IMPORTANT: src/lib.rs must exist and expose `pub fn configure(cfg: &mut web::ServiceConfig)` registering every route, so integration tests can build the app
     /// IMPORTANT: The following libraries are already installed
     ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
     /// No other external libraries should be used. Write functions that fit with the description from the PROJECT_DESCRIPTION
-    /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
+    /// OUTPUT: Print ONLY a JSON object mapping each relative file path to the full contents of that file, nothing else.
+    /// EXAMPLE OUTPUT:
+    /// {
+    ///   "src/main.rs": "mod routes;\n...",
+    ///   "src/routes.rs": "use actix_web::...\n..."
+    /// }
     println!(OUTPUT)
 }
 
 #[ai_function]
 pub fn print_improved_webserver_code(_project_description_and_template: &str) {
-    /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, the CURRENT_FILE_TREE and the CODE_FILES for a website backend build
+    /// INPUT: May also take IMPROVEMENT_HINTS from cargo check, clippy, cargo test and rustfmt. Address every hint given.
     /// FUNCTION: Performs the following tasks:
     ///   1. Removes any bugs in the code and adds minor additional functionality
     ///   2. Makes sure everything requested in the spec from a backend standpoint was followed. If not, add the feature. No code should be implemented later. Everything should be written now.
     ///   3. ONLY writes the code. No commentary.
     /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
     ///   reqwest, serde, serde_json, tokio, actix-web, async-trait
+    /// OUTPUT: Print ONLY a JSON object mapping each relative file path that was created or changed to the full new contents of that file.
+    /// Files that are unchanged are left out. Files may only be written inside src/, tests/ and config/
     println!(OUTPUT)
 }
 
 #[ai_function]
 pub fn print_fixed_code(_broken_code_with_bugs: &str) {
-    /// INPUT: Takes in Rust BROKEN_CODE and the ERROR_BUGS found
+    /// INPUT: Takes in the CURRENT_FILE_TREE, the Rust BROKEN_CODE files and the ERROR_BUGS found
     /// FUNCTION: Removes bugs from code
     /// IMPORTANT: Only prints out the new and improved code. No commentary or anything else
+    /// OUTPUT: Print ONLY a JSON object mapping each relative file path that was changed to the full new contents of that file.
+    /// Files that are unchanged are left out.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_improved_webserver_patch(_project_description_and_code: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, the CURRENT_FILE_TREE and the CODE_FILES for a website backend build
+    /// INPUT: May also take IMPROVEMENT_HINTS from cargo check, clippy, cargo test and rustfmt. Address every hint given.
+    /// FUNCTION: Performs the same improvements as a full rewrite but only prints the edits needed:
+    ///   1. Removes any bugs in the code and adds minor additional functionality
+    ///   2. Makes sure everything requested in the spec from a backend standpoint was followed. If not, add the feature.
+    /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait
+    /// OUTPUT: Print ONLY edit blocks in the following format, one block per change, nothing else:
+    /// FILE: src/main.rs
+    /// <<<<<<< SEARCH
+    /// exact lines currently in the file, with enough surrounding lines to be unique
+    /// =======
+    /// the lines that replace them
+    /// >>>>>>> REPLACE
+    /// IMPORTANT: To create a new file leave the SEARCH section empty. Never repeat unchanged code outside the SEARCH lines.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_code_patch(_broken_code_with_bugs: &str) {
+    /// INPUT: Takes in the CURRENT_FILE_TREE, the Rust BROKEN_CODE files and the ERROR_BUGS found
+    /// FUNCTION: Removes bugs from code by printing only the edits needed
+    /// OUTPUT: Print ONLY edit blocks in the following format, one block per change, nothing else:
+    /// FILE: src/main.rs
+    /// <<<<<<< SEARCH
+    /// exact lines currently in the file, with enough surrounding lines to be unique
+    /// =======
+    /// the lines that replace them
+    /// >>>>>>> REPLACE
+    /// IMPORTANT: A unified diff (--- a/path, +++ b/path, @@ hunks) is also accepted. No commentary or anything else
     println!(OUTPUT)
 }
 
@@ -110,3 +168,29 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     /// ]
     println!(OUTPUT)
 }
+
+// file trees and contents are rendered for the prompt rather than sent as json
+#[derive(Debug, Serialize)]
+pub struct BackendCodeInput {
+    pub code_template: String,
+    pub current_file_tree: String,
+    pub project_description: String,
+    pub data_layer: String,
+    pub auth_module: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FixedCodeInput {
+    pub current_file_tree: String,
+    pub broken_code: String,
+    pub error_bugs: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestApiEndpointsInput {
+    pub code_input: String,
+}
+
+typed_ai_function!(pub PrintBackendWebserverCode = print_backend_webserver_code(BackendCodeInput) -> FileMap);
+typed_ai_function!(pub PrintFixedCode = print_fixed_code(FixedCodeInput) -> FileMap);
+typed_ai_function!(pub PrintRestApiEndpoints = print_rest_api_endpoints(RestApiEndpointsInput) -> Vec<RouteObject>);
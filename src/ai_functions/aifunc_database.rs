@@ -0,0 +1,33 @@
+use crate::models::agents::agent_traits::{Entity, ProjectScope};
+use crate::typed_ai_function;
+use ai_functions::ai_function;
+use serde::Serialize;
+
+#[ai_function]
+pub fn print_data_entities(_project_description_and_scope: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION and the PROJECT_SCOPE of a website backend
+    /// FUNCTION: Derives the records the backend has to store, for example users, contacts or orders
+    /// IMPORTANT: Entity and field names are lowercase snake_case and singular. Do not add an id field, every entity gets one.
+    /// IMPORTANT: field_type is one of string, integer, float or boolean. Dates and times are strings.
+    /// IMPORTANT: If is_user_login_and_logout is true, accounts and passwords are already handled. Do not add a user entity, link records with a user_id field instead.
+    /// IMPORTANT: Link entities by adding an integer field such as user_id. Print an empty list if nothing needs to be stored.
+    /// OUTPUT: Print ONLY a JSON list of entities in the following format, nothing else:
+    ///   [
+    ///     {
+    ///       "name": "contact",
+    ///       "fields": [
+    ///         { "name": "full_name", "field_type": "string", "optional": false },
+    ///         { "name": "phone_number", "field_type": "string", "optional": true }
+    ///       ]
+    ///     }
+    ///   ]
+    println!(OUTPUT)
+}
+
+#[derive(Debug, Serialize)]
+pub struct DataEntitiesInput {
+    pub project_description: String,
+    pub project_scope: Option<ProjectScope>,
+}
+
+typed_ai_function!(pub PrintDataEntities = print_data_entities(DataEntitiesInput) -> Vec<Entity>);
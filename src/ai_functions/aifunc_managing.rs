@@ -1,4 +1,5 @@
 use ai_functions::ai_function;
+use crate::typed_ai_function;
 
 #[ai_function]
 pub fn convert_user_input_to_goal(_user_request: &str) {
@@ -13,3 +14,5 @@ pub fn convert_user_input_to_goal(_user_request: &str) {
     ///   OUTPUT = "build a website that fetches and stores crypto price data within a supabase setup including a frontend UI to fetch the data."
     println!(OUTPUT)
 }
+
+typed_ai_function!(pub ConvertUserInputToGoal = convert_user_input_to_goal(String) -> String);
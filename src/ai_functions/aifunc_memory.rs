@@ -0,0 +1,10 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_history_summary(_earlier_exchanges: &str) {
+    /// INPUT: Takes in the EARLIER_EXCHANGES of an agent with a language model, each a PROMPT followed by its RESPONSE
+    /// FUNCTION: Summarises what was asked, what was tried and what went wrong so a follow up call does not repeat it.
+    /// IMPORTANT: Keep file names, error messages, route paths and decisions exactly as written. Leave out code that can be read again.
+    /// OUTPUT: Print ONLY the summary as short plain text sentences, nothing else.
+    println!(OUTPUT)
+}
@@ -0,0 +1,39 @@
+use crate::models::agents::agent_traits::{CodeReview, ProjectScope, RouteObject};
+use crate::typed_ai_function;
+use ai_functions::ai_function;
+use serde::Serialize;
+
+#[ai_function]
+pub fn print_code_review(_scope_routes_and_code: &str) {
+    /// INPUT: Takes in the PROJECT_DESCRIPTION, the PROJECT_SCOPE, the API_ENDPOINTS and the CODE_FILES of a website backend
+    /// FUNCTION: Reviews the code as an independent senior engineer who did not write it. Looks for:
+    ///   1. missing_requirement: anything the PROJECT_SCOPE or PROJECT_DESCRIPTION asks for that is not implemented,
+    ///      for example login and logout routes when is_user_login_and_logout is true or create, read, update and delete when is_crud_required is true
+    ///   2. security: for example passwords stored or compared in plaintext, secrets in code, unchecked user input used in file paths
+    ///   3. error_handling: for example unwrap on user input or file access, errors returned as 200 responses, missing 404s
+    /// IMPORTANT: Only report real problems in the code given. Do not report style preferences.
+    /// IMPORTANT: severity is one of low, medium or high. category is one of missing_requirement, security, error_handling or other
+    /// OUTPUT: Print ONLY a JSON object in the following format, nothing else:
+    ///   {
+    ///     "approved": bool, // true if there are no medium or high severity issues
+    ///     "issues": [
+    ///       {
+    ///         "category": "security",
+    ///         "severity": "high",
+    ///         "file": "src/routes.rs", // or null if the issue is not in one file
+    ///         "description": "passwords are saved to the json database in plaintext"
+    ///       }
+    ///     ]
+    ///   }
+    println!(OUTPUT)
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeReviewInput {
+    pub project_description: String,
+    pub project_scope: ProjectScope,
+    pub api_endpoints: Vec<RouteObject>,
+    pub code_files: String,
+}
+
+typed_ai_function!(pub PrintCodeReview = print_code_review(CodeReviewInput) -> CodeReview);
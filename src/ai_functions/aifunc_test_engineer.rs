@@ -0,0 +1,35 @@
+use crate::helpers::workspace::FileMap;
+use crate::models::agents::agent_traits::RouteObject;
+use crate::typed_ai_function;
+use ai_functions::ai_function;
+use serde::Serialize;
+
+#[ai_function]
+pub fn print_integration_tests(_api_endpoints_and_code: &str) {
+    /// INPUT: Takes in the API_ENDPOINTS schema, the CODE_FILES of an actix-web backend and the CRATE_NAME to import it by
+    /// INPUT: May also take TEST_ERRORS from a previous set of tests that did not compile. Fix every error given.
+    /// FUNCTION: Writes Rust integration tests that exercise every endpoint in API_ENDPOINTS
+    /// IMPORTANT: Build the app with actix_web::test::init_service(App::new().configure(CRATE_NAME::configure))
+    ///   and send requests with actix_web::test::TestRequest. Each test is an #[actix_web::test] async fn.
+    /// IMPORTANT: Check status codes and the shape of the json response against the schema. Do not call external urls.
+    /// IMPORTANT: The following libraries are already installed
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
+    /// OUTPUT: Print ONLY a JSON object mapping each test file path to its full contents. Every path must be inside tests/
+    /// EXAMPLE OUTPUT:
+    /// {
+    ///   "tests/api.rs": "use actix_web::{test, App};\n..."
+    /// }
+    println!(OUTPUT)
+}
+
+#[derive(Debug, Serialize)]
+pub struct IntegrationTestsInput {
+    pub api_endpoints: Vec<RouteObject>,
+    pub code_files: String,
+    pub crate_name: String,
+    // the previous tests and why they failed, when they are rewritten
+    pub current_tests: Option<String>,
+    pub test_errors: Option<String>,
+}
+
+typed_ai_function!(pub PrintIntegrationTests = print_integration_tests(IntegrationTestsInput) -> FileMap);
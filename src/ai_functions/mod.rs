@@ -1,3 +1,9 @@
 pub mod aifunc_architect;
 pub mod aifunc_backend;
+pub mod aifunc_database;
 pub mod aifunc_managing;
+pub mod aifunc_memory;
+pub mod aifunc_reviewer;
+pub mod aifunc_test_engineer;
+pub mod prompt_registry;
+pub mod typed_function;
@@ -0,0 +1,416 @@
+use crate::apis::model_routing::Route;
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::load_env;
+use crate::models::general::errors::AgentError;
+use crate::models::general::llm::Message;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// directory of prompts replacing the built in ones, one markdown file per ai function
+// named after it, eg print_project_scope.md:
+// ---
+// version: 3
+// model: gpt-4o-mini
+// ---
+// <the function text>
+// ## Example input
+// build a todo app
+// ## Example output
+// {"is_crud_required": true, ...}
+//
+// the front matter may also be written as toml between +++ lines. only one `key: value` or
+// `key = value` per line is read: values are json literals such as 3, 0.4, "text" or ["END"],
+// single quoted or bare text. tables, multi line values and comments after a value are not
+pub const PROMPTS_DIR_ENV: &str = "AUTO_GIPPITY_PROMPTS_DIR";
+
+const EXAMPLE_INPUT_HEADING: &str = "## Example input";
+const EXAMPLE_OUTPUT_HEADING: &str = "## Example output";
+
+// names a prompt file can be given, a file named otherwise is never used
+const AI_FUNCTION_NAMES: [&str; 13] = [
+    "convert_user_input_to_goal",
+    "print_backend_webserver_code",
+    "print_code_review",
+    "print_data_entities",
+    "print_fixed_code",
+    "print_fixed_code_patch",
+    "print_history_summary",
+    "print_improved_webserver_code",
+    "print_improved_webserver_patch",
+    "print_integration_tests",
+    "print_project_scope",
+    "print_rest_api_endpoints",
+    "print_site_urls",
+];
+
+static PROMPT_REGISTRY: OnceLock<PromptRegistry> = OnceLock::new();
+
+// a question and the answer the function should give, sent before the real input
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptExample {
+    pub input: String,
+    pub output: String,
+}
+
+// the text an ai function is called with and the version recorded with every call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt {
+    pub version: String,
+    pub text: String,
+    pub examples: Vec<PromptExample>,
+}
+
+impl Prompt {
+    // the text written with #[ai_function], versioned with the crate
+    pub fn builtin(text: &str) -> Self {
+        Self {
+            version: format!("builtin@{}", env!("CARGO_PKG_VERSION")),
+            text: text.to_string(),
+            examples: vec![],
+        }
+    }
+
+    // the examples as the exchanges the llm would have had
+    pub fn example_messages(&self) -> Vec<Message> {
+        self.examples
+            .iter()
+            .flat_map(|example| {
+                [
+                    Message::user(example.input.clone()),
+                    Message::assistant(example.output.clone()),
+                ]
+            })
+            .collect()
+    }
+}
+
+// settings a prompt file may set in its front matter
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FrontMatter {
+    version: Option<Value>,
+    model: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    top_p: Option<f32>,
+    stop: Option<Vec<String>>,
+}
+
+// a prompt read from the prompts directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptOverride {
+    pub prompt: Prompt,
+    // model settings the prompt was written for, routes still take precedence
+    pub hints: Route,
+}
+
+impl std::str::FromStr for PromptOverride {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (front_matter, body) = split_front_matter(value)?;
+        let front_matter: FrontMatter = serde_json::from_value(Value::Object(front_matter))
+            .map_err(|e| AgentError::Config(format!("invalid prompt front matter: {}", e)))?;
+
+        let version: String = match front_matter.version {
+            Some(Value::String(version)) => version,
+            Some(version) => version.to_string(),
+            None => {
+                return Err(AgentError::Config(
+                    "prompt front matter has no version".to_string(),
+                ))
+            }
+        };
+        let (text, examples) = split_examples(body)?;
+
+        Ok(Self {
+            prompt: Prompt {
+                version,
+                text,
+                examples,
+            },
+            hints: Route {
+                model: front_matter.model,
+                temperature: front_matter.temperature,
+                max_tokens: front_matter.max_tokens,
+                top_p: front_matter.top_p,
+                stop: front_matter.stop,
+                ..Route::default()
+            },
+        })
+    }
+}
+
+// the front matter as json values and the rest of the file, values that are not json
+// such as unquoted yaml strings or single quoted toml ones are kept as text
+fn split_front_matter(contents: &str) -> Result<(Map<String, Value>, &str), AgentError> {
+    let contents: &str = contents.trim_start();
+    let (delimiter, separator) = if contents.starts_with("---") {
+        ("---", ':')
+    } else if contents.starts_with("+++") {
+        ("+++", '=')
+    } else {
+        return Err(AgentError::Config(
+            "prompt has no front matter between --- or +++ lines".to_string(),
+        ));
+    };
+
+    let rest: &str = &contents[delimiter.len()..];
+    let end: usize = rest.find(&format!("\n{}", delimiter)).ok_or_else(|| {
+        AgentError::Config(format!("prompt front matter has no closing {}", delimiter))
+    })?;
+    let body: &str = rest[end + 1 + delimiter.len()..].trim_start_matches(['\r', '\n']);
+
+    let mut fields: Map<String, Value> = Map::new();
+    for line in rest[..end].lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once(separator).ok_or_else(|| {
+            AgentError::Config(format!(
+                "prompt front matter line '{}' has no {}",
+                line, separator
+            ))
+        })?;
+        let value: &str = value.trim();
+        if value.is_empty() {
+            return Err(AgentError::Config(format!(
+                "prompt front matter line '{}' has no value, values spanning lines are not supported",
+                line
+            )));
+        }
+        let value: Value = serde_json::from_str(value).unwrap_or_else(|_| {
+            let text: &str = value
+                .strip_prefix('\'')
+                .and_then(|text| text.strip_suffix('\''))
+                .unwrap_or(value);
+            Value::String(text.to_string())
+        });
+        fields.insert(key.trim().to_string(), value);
+    }
+
+    Ok((fields, body))
+}
+
+// the function text before the first example, and the examples in pairs of headings
+fn split_examples(body: &str) -> Result<(String, Vec<PromptExample>), AgentError> {
+    let mut sections: Vec<(&str, String)> = vec![("", String::new())];
+    for line in body.lines() {
+        let heading: &str = line.trim_end();
+        if heading == EXAMPLE_INPUT_HEADING || heading == EXAMPLE_OUTPUT_HEADING {
+            sections.push((heading, String::new()));
+        } else if let Some((_, text)) = sections.last_mut() {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+
+    let mut sections = sections.into_iter();
+    let text: String = sections.next().map(|(_, text)| text).unwrap_or_default();
+    let mut examples: Vec<PromptExample> = vec![];
+    while let Some((heading, input)) = sections.next() {
+        match (heading, sections.next()) {
+            (EXAMPLE_INPUT_HEADING, Some((EXAMPLE_OUTPUT_HEADING, output))) => {
+                examples.push(PromptExample {
+                    input: input.trim().to_string(),
+                    output: output.trim().to_string(),
+                })
+            }
+            _ => {
+                return Err(AgentError::Config(format!(
+                    "every '{}' in a prompt needs an '{}' after it",
+                    EXAMPLE_INPUT_HEADING, EXAMPLE_OUTPUT_HEADING
+                )))
+            }
+        }
+    }
+
+    let text: String = text.trim().to_string();
+    if text.is_empty() {
+        return Err(AgentError::Config(
+            "prompt has no function text".to_string(),
+        ));
+    }
+    Ok((text, examples))
+}
+
+// the prompts read from the prompts directory, by ai function name
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PromptRegistry {
+    overrides: BTreeMap<String, PromptOverride>,
+}
+
+impl PromptRegistry {
+    pub fn from_dir(dir: &Path) -> Result<Self, AgentError> {
+        let entries = fs::read_dir(dir).map_err(|e| {
+            AgentError::io(
+                &format!("Failed to read {} {}", PROMPTS_DIR_ENV, dir.display()),
+                e,
+            )
+        })?;
+
+        let mut overrides: BTreeMap<String, PromptOverride> = BTreeMap::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| AgentError::io("Failed to list prompts", e))?
+                .path();
+            if path.extension().is_none_or(|extension| extension != "md") {
+                continue;
+            }
+            let Some(name) = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+            else {
+                continue;
+            };
+
+            let contents: String = fs::read_to_string(&path).map_err(|e| {
+                AgentError::io(&format!("Failed to read prompt {}", path.display()), e)
+            })?;
+            let prompt: PromptOverride = contents.parse().map_err(|e| match e {
+                AgentError::Config(msg) => {
+                    AgentError::Config(format!("{}: {}", path.display(), msg))
+                }
+                other => other,
+            })?;
+            overrides.insert(name, prompt);
+        }
+
+        Ok(Self { overrides })
+    }
+
+    // empty when no prompts directory is configured
+    pub fn from_env() -> Result<Self, AgentError> {
+        load_env();
+        match env::var(PROMPTS_DIR_ENV) {
+            Ok(dir) if !dir.trim().is_empty() => Self::from_dir(Path::new(&dir)),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn get(&self, operation: &str) -> Option<&PromptOverride> {
+        self.overrides.get(operation)
+    }
+
+    // prompt files whose name matches no ai function, usually a typo
+    pub fn unknown_functions(&self) -> Vec<&str> {
+        self.overrides
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !AI_FUNCTION_NAMES.contains(name))
+            .collect()
+    }
+}
+
+// the prompt read for an ai function, the prompts directory is read once per run
+pub fn prompt_override(operation: &str) -> Result<Option<&'static PromptOverride>, AgentError> {
+    let registry: &'static PromptRegistry = match PROMPT_REGISTRY.get() {
+        Some(registry) => registry,
+        None => {
+            let registry: PromptRegistry = PromptRegistry::from_env()?;
+            for name in registry.unknown_functions() {
+                PrintCommand::Issue.print_agent_message(
+                    "Prompt Registry",
+                    &format!(
+                        "{}.md in {} matches no ai function and is ignored",
+                        name, PROMPTS_DIR_ENV
+                    ),
+                );
+            }
+            PROMPT_REGISTRY.get_or_init(|| registry)
+        }
+    };
+    Ok(registry.get(operation))
+}
+
+// the prompt an ai function is called with, the built in text when none is registered
+pub fn prompt_for(
+    operation: &str,
+    ai_func: fn(&str) -> &'static str,
+    func_input: &str,
+) -> Result<Prompt, AgentError> {
+    Ok(match prompt_override(operation)? {
+        Some(registered) => registered.prompt.clone(),
+        None => Prompt::builtin(ai_func(func_input)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_parses_prompt_files() {
+        let markdown: PromptOverride = "---\nversion: 1.2.0\nmodel: gpt-4o-mini\nstop: [\"END\"]\n---\nfn print_goal(request: &str) {}\n\n## Example input\nlogin page\n## Example output\nbuild a website with a login page\n"
+            .parse()
+            .expect("markdown prompt should parse");
+        assert_eq!(markdown.prompt.version, "1.2.0");
+        assert_eq!(markdown.prompt.text, "fn print_goal(request: &str) {}");
+        assert_eq!(markdown.hints.model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(markdown.hints.stop, Some(vec!["END".to_string()]));
+        assert_eq!(
+            markdown.prompt.example_messages(),
+            vec![
+                Message::user("login page"),
+                Message::assistant("build a website with a login page"),
+            ]
+        );
+
+        let toml: PromptOverride = "+++\nversion = 3\ntemperature = 0.4\n+++\nfn print_goal() {}\n"
+            .parse()
+            .expect("toml front matter should parse");
+        assert_eq!(toml.prompt.version, "3");
+        assert_eq!(toml.hints.temperature, Some(0.4));
+        assert!(toml.prompt.examples.is_empty());
+
+        let unversioned = "---\nmodel: gpt-4o\n---\nfn print_goal() {}\n".parse::<PromptOverride>();
+        assert!(matches!(unversioned, Err(AgentError::Config(_))));
+        let unanswered = "---\nversion: 1\n---\nfn print_goal() {}\n## Example input\nlogin page\n"
+            .parse::<PromptOverride>();
+        assert!(matches!(unanswered, Err(AgentError::Config(_))));
+        assert!(Prompt::builtin("fn print_goal() {}")
+            .version
+            .starts_with("builtin@"));
+    }
+
+    #[test]
+    fn tests_reads_supported_front_matter_subset() {
+        let toml: PromptOverride =
+            "+++\n# tuned for local models\nversion = '2.1'\nmodel = 'llama3'\nstop = [\"END\"]\n+++\nfn print_goal() {}\n"
+                .parse()
+                .expect("single quoted toml should parse");
+        assert_eq!(toml.prompt.version, "2.1");
+        assert_eq!(toml.hints.model.as_deref(), Some("llama3"));
+        assert_eq!(toml.hints.stop, Some(vec!["END".to_string()]));
+
+        let table = "+++\nversion = 1\n[model]\nname = 'llama3'\n+++\nfn print_goal() {}\n"
+            .parse::<PromptOverride>();
+        assert!(matches!(table, Err(AgentError::Config(_))));
+        let multi_line = "+++\nversion = 1\nstop = [\n  \"END\",\n]\n+++\nfn print_goal() {}\n"
+            .parse::<PromptOverride>();
+        assert!(matches!(multi_line, Err(AgentError::Config(_))));
+        let trailing_comment =
+            "+++\nversion = 1\ntemperature = 0.4 # warm\n+++\nfn print_goal() {}\n"
+                .parse::<PromptOverride>();
+        assert!(matches!(trailing_comment, Err(AgentError::Config(_))));
+    }
+
+    #[test]
+    fn tests_lists_prompts_for_unknown_functions() {
+        let prompt: PromptOverride = "---\nversion: 1\n---\nfn print_goal() {}\n"
+            .parse()
+            .unwrap();
+        let registry: PromptRegistry = PromptRegistry {
+            overrides: BTreeMap::from([
+                ("print_project_scope".to_string(), prompt.clone()),
+                ("print_projct_scope".to_string(), prompt),
+            ]),
+        };
+        assert_eq!(registry.unknown_functions(), vec!["print_projct_scope"]);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::helpers::general::strip_markdown;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// a value an ai function prints, with the shape the llm is told to print it in
+pub trait AiOutput: DeserializeOwned {
+    // json shape of the value, eg {"approved": bool, "issues": [...]}
+    fn schema() -> String;
+
+    // added to the instructions of every ai function returning the value
+    fn output_instruction() -> String {
+        format!("Print only JSON matching {}", Self::schema())
+    }
+
+    fn decode(llm_response: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(strip_markdown(llm_response).trim())
+    }
+}
+
+impl AiOutput for String {
+    fn schema() -> String {
+        "string".to_string()
+    }
+
+    // printed as it is, not as a json string
+    fn output_instruction() -> String {
+        "Print plain text only".to_string()
+    }
+
+    fn decode(llm_response: &str) -> Result<Self, serde_json::Error> {
+        Ok(strip_markdown(llm_response))
+    }
+}
+
+macro_rules! ai_output_primitive {
+    ($schema:literal: $($ty:ty),*) => {
+        $(impl AiOutput for $ty {
+            fn schema() -> String {
+                $schema.to_string()
+            }
+        })*
+    };
+}
+
+ai_output_primitive!("bool": bool);
+ai_output_primitive!("integer": u8, u16, u32, u64, usize, i32, i64);
+ai_output_primitive!("number": f32, f64);
+ai_output_primitive!("any json value": Value);
+
+impl<T: AiOutput> AiOutput for Option<T> {
+    fn schema() -> String {
+        format!("{} or null", T::schema())
+    }
+}
+
+impl<T: AiOutput> AiOutput for Vec<T> {
+    fn schema() -> String {
+        format!("[{}, ...]", T::schema())
+    }
+}
+
+impl<T: AiOutput> AiOutput for BTreeMap<String, T> {
+    fn schema() -> String {
+        format!("{{\"<key>\": {}, ...}}", T::schema())
+    }
+}
+
+// implements AiOutput for a struct from its fields, eg
+// ai_output_struct!(CodeReview { approved: bool, issues: Vec<ReviewIssue> });
+#[macro_export]
+macro_rules! ai_output_struct {
+    ($name:ident { $($field:ident: $field_ty:ty),* $(,)? }) => {
+        impl $crate::ai_functions::typed_function::AiOutput for $name {
+            fn schema() -> String {
+                let fields: Vec<String> = vec![$(format!(
+                    "\"{}\": {}",
+                    stringify!($field),
+                    <$field_ty as $crate::ai_functions::typed_function::AiOutput>::schema()
+                )),*];
+                format!("{{{}}}", fields.join(", "))
+            }
+        }
+
+        // stops compiling when a field is added, removed or changes type
+        const _: fn(&$name) = |value: &$name| {
+            let $name { $($field),* } = value;
+            $(let _: &$field_ty = $field;)*
+        };
+    };
+}
+
+// implements AiOutput for an enum of unit variants, named as serde writes them
+#[macro_export]
+macro_rules! ai_output_enum {
+    ($name:ident { $($variant:ident),* $(,)? }) => {
+        impl $crate::ai_functions::typed_function::AiOutput for $name {
+            fn schema() -> String {
+                let variants: Vec<String> =
+                    vec![$(::serde_json::to_string(&$name::$variant).unwrap_or_default()),*];
+                format!("one of {}", variants.join(" | "))
+            }
+        }
+
+        // stops compiling when a variant is added or removed
+        const _: fn(&$name) = |value: &$name| match value {
+            $($name::$variant => {}),*
+        };
+    };
+}
+
+// an ai function with a typed input and output, for ai_function_request
+pub trait AiFunction {
+    type Input: Serialize;
+    type Output: AiOutput;
+
+    // name of the untyped function, used for routing and agent memory
+    const NAME: &'static str;
+
+    // the instructions written with #[ai_function]
+    fn instructions(input: &str) -> &'static str;
+}
+
+// declares a typed ai function around one written with #[ai_function], eg
+// typed_ai_function!(pub PrintProjectScope = print_project_scope(ProjectScopeInput) -> ProjectScope);
+#[macro_export]
+macro_rules! typed_ai_function {
+    ($vis:vis $name:ident = $func:ident($input:ty) -> $output:ty) => {
+        #[derive(Debug, Clone, Copy)]
+        $vis struct $name;
+
+        impl $crate::ai_functions::typed_function::AiFunction for $name {
+            type Input = $input;
+            type Output = $output;
+
+            const NAME: &'static str = stringify!($func);
+
+            fn instructions(input: &str) -> &'static str {
+                $func(input)
+            }
+        }
+    };
+}
+
+// the input as the labelled sections the prompts use, eg "PROJECT_DESCRIPTION: ...",
+// in the order the fields are declared and leaving out fields that are null
+pub fn render_input<T: Serialize>(input: &T) -> String {
+    match serde_json::to_value(input).unwrap_or_default() {
+        Value::Object(fields) => fields
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| format!("{}: {} \n", key.to_uppercase(), render_value(value)))
+            .collect(),
+        value => render_value(&value),
+    }
+}
+
+fn render_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Level {
+        Low,
+        VeryHigh,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Report {
+        level: Level,
+        notes: Vec<String>,
+        file: Option<String>,
+    }
+
+    ai_output_enum!(Level { Low, VeryHigh });
+    ai_output_struct!(Report {
+        level: Level,
+        notes: Vec<String>,
+        file: Option<String>,
+    });
+
+    #[test]
+    fn tests_describes_and_decodes_typed_output() {
+        assert_eq!(
+            Report::schema(),
+            r#"{"level": one of "low" | "very_high", "notes": [string, ...], "file": string or null}"#
+        );
+
+        let report: Report = Report::decode(
+            "```json\n{\"level\": \"very_high\", \"notes\": [], \"file\": null}\n```",
+        )
+        .unwrap();
+        assert_eq!(report.level, Level::VeryHigh);
+        assert_eq!(String::decode("```\nplain\n```").unwrap(), "plain\n");
+
+        let input =
+            serde_json::json!({"project_description": "todo app", "feedback": null, "urls": ["a"]});
+        assert_eq!(
+            render_input(&input),
+            "PROJECT_DESCRIPTION: todo app \nURLS: [\"a\"] \n"
+        );
+        assert_eq!(render_input(&"todo app"), "todo app");
+    }
+
+    #[test]
+    fn tests_renders_input_in_declared_order() {
+        #[derive(Serialize)]
+        struct ScopeInput {
+            project_description: &'static str,
+            feedback: Option<&'static str>,
+            code_files: &'static str,
+            api_endpoints: Vec<&'static str>,
+        }
+
+        let input = ScopeInput {
+            project_description: "todo app",
+            feedback: None,
+            code_files: "fn main() {}",
+            api_endpoints: vec!["/todos"],
+        };
+        assert_eq!(
+            render_input(&input),
+            "PROJECT_DESCRIPTION: todo app \nCODE_FILES: fn main() {} \nAPI_ENDPOINTS: [\"/todos\"] \n"
+        );
+    }
+}
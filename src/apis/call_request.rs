@@ -1,23 +1,218 @@
-use crate::models::general::llm::{APIResponse, ChatCompletion, Message};
-use dotenv::dotenv;
+use crate::apis::stream::{SseDecoder, SSE_DONE};
+use crate::helpers::command_line::StreamPrinter;
+use crate::helpers::context_window::{ContextWindow, TokenCounter};
+use crate::models::general::errors::AgentError;
+use crate::models::general::llm::{
+    APIChoice, APIMessage, APIResponse, ChatCompletion, FinishReason, Message, Role, StreamChunk,
+    ToolDefinition,
+};
+use crate::apis::http_client::shared_client;
+use crate::apis::model_routing::ModelRoute;
+use crate::helpers::general::load_env;
 use reqwest::Client;
 use std::env;
 
 use reqwest::header::{HeaderMap, HeaderValue};
 
-pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::error::Error + Send>> {
-    dotenv().ok();
+pub const STREAM_ENV: &str = "AUTO_GIPPITY_STREAM";
 
-    // Extract api information
-    let api_key: String =
-        env::var("OPEN_AI_KEY").expect("OPEN_AI_KEY not found in environment variables");
-    let api_org: String =
-        env::var("OPEN_AI_ORG").expect("OPEN_AI_ORG not found in environment variables");
-    let api_model: String =
-        env::var("OPEN_AI_MODEL").expect("OPEN_AI_MODEL not found in environment variables");
+const OPEN_AI_URL: &str = "https://api.openai.com/v1/chat/completions";
+
+// times a completion cut off at the token limit is asked to carry on
+const MAX_CONTINUATIONS: u8 = 2;
+
+pub const CONTINUE_PROMPT: &str =
+    "You were cut off. Continue exactly where you stopped, without repeating anything.";
+
+// the first choice, continued when the llm ran out of tokens
+pub async fn call_gpt(mut messages: Vec<Message>, route: &ModelRoute) -> Result<String, AgentError> {
+    load_env();
+    let streaming: bool = streaming_enabled()?;
+    let response_tokens: usize = ContextWindow::for_route(route)?.response_tokens;
+
+    let mut content: String = String::new();
+    for _ in 0..=MAX_CONTINUATIONS {
+        let choice: APIChoice = if streaming {
+            let mut printer: StreamPrinter = StreamPrinter::new();
+            let choice = call_gpt_streamed(messages.clone(), route, response_tokens, &mut |delta| {
+                printer.print_delta(delta)
+            })
+            .await;
+            printer.finish();
+            choice?
+        } else {
+            call_gpt_choices(messages.clone(), route, 1)
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| AgentError::LlmResponse("no choices returned".to_string()))?
+        };
+
+        let truncated: bool = choice.is_truncated();
+        let chunk: String = choice.into_message().content;
+        content.push_str(&chunk);
+        if !truncated {
+            return Ok(content);
+        }
 
-    // confirm endpoint
-    let url: &str = "https://api.openai.com/v1/chat/completions";
+        // the earlier chunks are already in the messages, only the new one is added
+        messages.push(Message::assistant(chunk));
+        messages.push(Message::user(CONTINUE_PROMPT));
+    }
+
+    Err(AgentError::LlmResponse(format!(
+        "completion still cut off at the token limit after {} continuations",
+        MAX_CONTINUATIONS
+    )))
+}
+
+// n choices for the same messages, with why each of them ended
+pub async fn call_gpt_choices(
+    messages: Vec<Message>,
+    route: &ModelRoute,
+    n: u8,
+) -> Result<Vec<APIChoice>, AgentError> {
+    let chat_completion: ChatCompletion = ChatCompletion {
+        n: (n > 1).then_some(n),
+        ..chat_completion(messages, route)
+    };
+    request_choices(&chat_completion).await
+}
+
+// one choice that may call the tools instead of answering, unless calls are switched off
+pub async fn call_gpt_tools(
+    messages: Vec<Message>,
+    route: &ModelRoute,
+    tools: Vec<ToolDefinition>,
+    allow_calls: bool,
+) -> Result<APIChoice, AgentError> {
+    let chat_completion: ChatCompletion = ChatCompletion {
+        tools,
+        tool_choice: (!allow_calls).then(|| "none".to_string()),
+        ..chat_completion(messages, route)
+    };
+    request_choices(&chat_completion)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| AgentError::LlmResponse("no choices returned".to_string()))
+}
+
+// a request with the model and generation settings of the route
+fn chat_completion(messages: Vec<Message>, route: &ModelRoute) -> ChatCompletion {
+    ChatCompletion {
+        model: route.model.clone(),
+        messages,
+        temperature: route.temperature,
+        max_tokens: route.max_tokens,
+        top_p: route.top_p,
+        stop: route.stop.clone(),
+        n: None,
+        tools: vec![],
+        tool_choice: None,
+        stream: false,
+    }
+}
+
+async fn request_choices(chat_completion: &ChatCompletion) -> Result<Vec<APIChoice>, AgentError> {
+    let (client, headers) = openai_client()?;
+
+    // extract api response
+    let res: APIResponse = client
+        .post(OPEN_AI_URL)
+        .headers(headers)
+        .json(chat_completion)
+        .send()
+        .await
+        .map_err(AgentError::LlmRequest)?
+        .json()
+        .await
+        .map_err(AgentError::LlmRequest)?;
+
+    if res.choices.is_empty() {
+        return Err(AgentError::LlmResponse("no choices returned".to_string()));
+    }
+    Ok(res.choices)
+}
+
+// the first choice read as it is written, cut off once it runs past the token budget
+pub async fn call_gpt_streamed(
+    messages: Vec<Message>,
+    route: &ModelRoute,
+    max_tokens: usize,
+    on_delta: &mut (dyn FnMut(&str) + Send),
+) -> Result<APIChoice, AgentError> {
+    let (client, headers) = openai_client()?;
+    let counter: TokenCounter = TokenCounter::for_model(&route.model);
+
+    let chat_completion: ChatCompletion = ChatCompletion {
+        stream: true,
+        ..chat_completion(messages, route)
+    };
+
+    let mut response: reqwest::Response = client
+        .post(OPEN_AI_URL)
+        .headers(headers)
+        .json(&chat_completion)
+        .send()
+        .await
+        .map_err(AgentError::LlmRequest)?;
+    if !response.status().is_success() {
+        let status: u16 = response.status().as_u16();
+        let body: String = response.text().await.unwrap_or_default();
+        return Err(AgentError::LlmResponse(format!("status {}: {}", status, body)));
+    }
+
+    let mut decoder: SseDecoder = SseDecoder::default();
+    let mut content: String = String::new();
+    let mut finish_reason: Option<FinishReason> = None;
+    let mut tokens: usize = 0;
+    'stream: while let Some(bytes) = response.chunk().await.map_err(AgentError::LlmRequest)? {
+        for data in decoder.push(&bytes) {
+            if data == SSE_DONE {
+                break 'stream;
+            }
+            let chunk: StreamChunk =
+                serde_json::from_str(&data).map_err(|source| AgentError::Decode {
+                    operation: "completion stream".to_string(),
+                    source,
+                })?;
+
+            for choice in chunk.choices.into_iter().filter(|choice| choice.index == 0) {
+                if let Some(delta) = choice.delta.content {
+                    tokens += counter.count(&delta);
+                    on_delta(&delta);
+                    content.push_str(&delta);
+                }
+                finish_reason = choice.finish_reason.or(finish_reason);
+            }
+
+            // dropping the response closes the connection, so the llm stops writing too.
+            // what was written is kept as cut off, for the caller to continue
+            if tokens > max_tokens {
+                finish_reason = Some(FinishReason::Length);
+                break 'stream;
+            }
+        }
+    }
+
+    Ok(APIChoice {
+        message: APIMessage {
+            role: Role::Assistant,
+            content: Some(content),
+            tool_calls: vec![],
+        },
+        finish_reason,
+    })
+}
+
+// the shared client and headers with the api key and organisation
+fn openai_client() -> Result<(&'static Client, HeaderMap), AgentError> {
+    load_env();
+
+    // Extract api information
+    let api_key: String = env_setting("OPEN_AI_KEY")?;
+    let api_org: String = env_setting("OPEN_AI_ORG")?;
 
     // create headers
     let mut headers: HeaderMap = HeaderMap::new();
@@ -25,40 +220,36 @@ pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::err
     headers.insert(
         "authorization",
         HeaderValue::from_str(&format!("Bearer {}", api_key))
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
+            .map_err(|e| AgentError::Config(format!("invalid OPEN_AI_KEY: {}", e)))?,
     );
     // create api org header
     headers.insert(
         "OpenAI-Organization",
         HeaderValue::from_str(api_org.as_str())
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
+            .map_err(|e| AgentError::Config(format!("invalid OPEN_AI_ORG: {}", e)))?,
     );
 
-    // create client
-    let client = Client::builder()
-        .default_headers(headers)
-        .build()
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
-
-    let chat_completion: ChatCompletion = ChatCompletion {
-        model: api_model.to_string(),
-        messages,
-        temperature: 0.1,
-    };
+    Ok((shared_client()?, headers))
+}
 
-    // extract api response
-    let res: APIResponse = client
-        .post(url)
-        .json(&chat_completion)
-        .send()
-        .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?
-        .json()
-        .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
+// whether completions are shown while they stream, on unless switched off
+fn streaming_enabled() -> Result<bool, AgentError> {
+    match env::var(STREAM_ENV) {
+        Ok(value) => match value.trim().to_lowercase().as_str() {
+            "" | "1" | "true" | "on" => Ok(true),
+            "0" | "false" | "off" => Ok(false),
+            other => Err(AgentError::Config(format!(
+                "{} must be on or off, got '{}'",
+                STREAM_ENV, other
+            ))),
+        },
+        Err(_) => Ok(true),
+    }
+}
 
-    // send response
-    Ok(res.choices[0].message.content.clone())
+fn env_setting(key: &str) -> Result<String, AgentError> {
+    env::var(key)
+        .map_err(|_| AgentError::Config(format!("{} not found in environment variables", key)))
 }
 #[cfg(test)]
 mod tests {
@@ -66,12 +257,10 @@ mod tests {
     use super::*;
     #[tokio::test]
     async fn tests_call_to_openai() {
-        let message = Message {
-            role: "user".to_string(),
-            content: "Hi there, this is a test. Give me a short response.".to_string(),
-        };
+        let message = Message::user("Hi there, this is a test. Give me a short response.");
         let messages: Vec<Message> = vec![message];
-        let res: Result<String, Box<dyn std::error::Error + Send>> = call_gpt(messages).await;
+        let route: ModelRoute = ModelRoute::from_env().expect("OPEN_AI_MODEL should be set");
+        let res: Result<String, AgentError> = call_gpt(messages, &route).await;
         if let Ok(res_str) = res {
             dbg!(res_str);
             assert!(true);
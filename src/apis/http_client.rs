@@ -0,0 +1,154 @@
+use crate::helpers::general::load_env;
+use crate::models::general::errors::AgentError;
+use reqwest::{Certificate, Client, NoProxy, Proxy};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub const HTTP_TIMEOUT_ENV: &str = "AUTO_GIPPITY_HTTP_TIMEOUT_SECS";
+pub const CONNECT_TIMEOUT_ENV: &str = "AUTO_GIPPITY_CONNECT_TIMEOUT_SECS";
+pub const PROXY_ENV: &str = "AUTO_GIPPITY_PROXY";
+pub const CA_CERT_ENV: &str = "AUTO_GIPPITY_CA_CERT";
+pub const USER_AGENT_ENV: &str = "AUTO_GIPPITY_USER_AGENT";
+
+// long enough for a large code generation to stream in full
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+// the generated backend is always reached directly, never through the proxy
+const LOCAL_HOSTS: &str = "localhost,127.0.0.1,::1";
+
+static SHARED_CLIENT: OnceLock<Client> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkSettings {
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub proxy: Option<String>,
+    // pem file trusted on top of the system roots, eg for a corporate proxy
+    pub ca_cert: Option<PathBuf>,
+    pub user_agent: String,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            proxy: None,
+            ca_cert: None,
+            user_agent: format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+impl NetworkSettings {
+    pub fn from_env() -> Result<Self, AgentError> {
+        load_env();
+        Self::from_vars(|key| env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, AgentError> {
+        let mut settings: Self = Self::default();
+        if let Some(timeout) = read_seconds(HTTP_TIMEOUT_ENV, var(HTTP_TIMEOUT_ENV))? {
+            settings.timeout = timeout;
+        }
+        if let Some(timeout) = read_seconds(CONNECT_TIMEOUT_ENV, var(CONNECT_TIMEOUT_ENV))? {
+            settings.connect_timeout = timeout;
+        }
+        settings.proxy = var(PROXY_ENV).filter(|proxy| !proxy.trim().is_empty());
+        settings.ca_cert = var(CA_CERT_ENV)
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from);
+        if let Some(user_agent) = var(USER_AGENT_ENV).filter(|agent| !agent.trim().is_empty()) {
+            settings.user_agent = user_agent;
+        }
+        Ok(settings)
+    }
+
+    pub fn build_client(&self) -> Result<Client, AgentError> {
+        let mut builder = Client::builder()
+            .user_agent(self.user_agent.as_str())
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT);
+
+        if let Some(proxy) = &self.proxy {
+            let proxy: Proxy = Proxy::all(proxy.as_str())
+                .map_err(|e| AgentError::Config(format!("invalid {}: {}", PROXY_ENV, e)))?
+                .no_proxy(NoProxy::from_string(LOCAL_HOSTS));
+            builder = builder.proxy(proxy);
+        }
+
+        if let Some(ca_cert) = &self.ca_cert {
+            let pem: Vec<u8> = fs::read(ca_cert)
+                .map_err(|e| AgentError::io(&format!("Failed to read {}", CA_CERT_ENV), e))?;
+            let certificate: Certificate = Certificate::from_pem(&pem)
+                .map_err(|e| AgentError::Config(format!("invalid {}: {}", CA_CERT_ENV, e)))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        builder.build().map_err(AgentError::Http)
+    }
+}
+
+fn read_seconds(key: &str, value: Option<String>) -> Result<Option<Duration>, AgentError> {
+    match value {
+        Some(value) => value
+            .trim()
+            .parse::<u64>()
+            .map(|seconds| Some(Duration::from_secs(seconds)))
+            .map_err(|_| {
+                AgentError::Config(format!(
+                    "{} must be a number of seconds, got '{}'",
+                    key, value
+                ))
+            }),
+        None => Ok(None),
+    }
+}
+
+// one pooled client for the whole run, built from the network settings on first use
+pub fn shared_client() -> Result<&'static Client, AgentError> {
+    if let Some(client) = SHARED_CLIENT.get() {
+        return Ok(client);
+    }
+    let client: Client = NetworkSettings::from_env()?.build_client()?;
+    Ok(SHARED_CLIENT.get_or_init(|| client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_reads_network_settings() {
+        let settings: NetworkSettings = NetworkSettings::from_vars(|key| match key {
+            HTTP_TIMEOUT_ENV => Some("120".to_string()),
+            PROXY_ENV => Some("http://proxy.corp.example:3128".to_string()),
+            _ => None,
+        })
+        .expect("settings should parse");
+        assert_eq!(settings.timeout, Duration::from_secs(120));
+        assert_eq!(settings.connect_timeout, DEFAULT_CONNECT_TIMEOUT);
+        assert!(settings.user_agent.starts_with("auto_gippity/"));
+        assert!(settings.build_client().is_ok());
+
+        let invalid = NetworkSettings::from_vars(|key| {
+            (key == CONNECT_TIMEOUT_ENV).then(|| "soon".to_string())
+        });
+        assert!(matches!(invalid, Err(AgentError::Config(_))));
+
+        let missing_ca = NetworkSettings {
+            ca_cert: Some(PathBuf::from("/nonexistent/corp-ca.pem")),
+            ..NetworkSettings::default()
+        };
+        assert!(matches!(
+            missing_ca.build_client(),
+            Err(AgentError::Io { .. })
+        ));
+    }
+}
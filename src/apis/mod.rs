@@ -1 +1,4 @@
 pub mod call_request;
+pub mod http_client;
+pub mod model_routing;
+pub mod stream;
@@ -0,0 +1,202 @@
+use crate::ai_functions::prompt_registry::prompt_override;
+use crate::helpers::general::load_env;
+use crate::models::general::errors::AgentError;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+// path of a json file with the routes, eg
+// [{"operation": "print_project_scope", "model": "gpt-4o-mini"},
+//  {"position": "Backend Developer", "model": "gpt-4o", "max_tokens": 8192}]
+pub const ROUTES_ENV: &str = "AUTO_GIPPITY_ROUTES";
+
+const DEFAULT_TEMPERATURE: f32 = 0.1;
+
+static ROUTING_TABLE: OnceLock<RoutingTable> = OnceLock::new();
+
+// a route matches an ai function name, an agent position or both, and overrides
+// the generation settings it names
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    pub operation: Option<String>,
+    pub position: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f32>,
+    pub stop: Option<Vec<String>>,
+}
+
+impl Route {
+    fn matches(&self, operation: &str, position: &str) -> bool {
+        self.operation
+            .as_deref()
+            .is_none_or(|name| name == operation)
+            && self.position.as_deref().is_none_or(|name| name == position)
+    }
+
+    // routes naming the operation beat those naming the position, both beat either
+    fn specificity(&self) -> u8 {
+        u8::from(self.position.is_some()) + 2 * u8::from(self.operation.is_some())
+    }
+
+    fn apply(&self, route: &mut ModelRoute) {
+        if let Some(model) = &self.model {
+            route.model = model.clone();
+        }
+        if let Some(temperature) = self.temperature {
+            route.temperature = temperature;
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            route.max_tokens = Some(max_tokens);
+        }
+        if let Some(top_p) = self.top_p {
+            route.top_p = Some(top_p);
+        }
+        if let Some(stop) = &self.stop {
+            route.stop = stop.clone();
+        }
+    }
+}
+
+// the settings a call is made with
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelRoute {
+    pub model: String,
+    pub temperature: f32,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f32>,
+    pub stop: Vec<String>,
+}
+
+impl ModelRoute {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            temperature: DEFAULT_TEMPERATURE,
+            max_tokens: None,
+            top_p: None,
+            stop: vec![],
+        }
+    }
+
+    // OPEN_AI_MODEL with the default settings, used where no route applies
+    pub fn from_env() -> Result<Self, AgentError> {
+        load_env();
+        let model: String = env::var("OPEN_AI_MODEL").map_err(|_| {
+            AgentError::Config("OPEN_AI_MODEL not found in environment variables".to_string())
+        })?;
+        Ok(Self::new(&model))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RoutingTable {
+    routes: Vec<Route>,
+}
+
+impl RoutingTable {
+    pub fn new(routes: Vec<Route>) -> Self {
+        Self { routes }
+    }
+
+    // empty when no routes file is configured
+    pub fn from_env() -> Result<Self, AgentError> {
+        load_env();
+        let path: String = match env::var(ROUTES_ENV) {
+            Ok(path) if !path.trim().is_empty() => path,
+            _ => return Ok(Self::default()),
+        };
+        let contents: String = fs::read_to_string(&path).map_err(|e| {
+            AgentError::io(&format!("Failed to read {} file {}", ROUTES_ENV, path), e)
+        })?;
+        contents.parse()
+    }
+
+    // every matching route applied from the least to the most specific
+    pub fn resolve(&self, operation: &str, position: &str, default: ModelRoute) -> ModelRoute {
+        let mut matching: Vec<&Route> = self
+            .routes
+            .iter()
+            .filter(|route| route.matches(operation, position))
+            .collect();
+        matching.sort_by_key(|route| route.specificity());
+
+        let mut resolved: ModelRoute = default;
+        for route in matching {
+            route.apply(&mut resolved);
+        }
+        resolved
+    }
+}
+
+impl std::str::FromStr for RoutingTable {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str::<Vec<Route>>(value)
+            .map(Self::new)
+            .map_err(|e| AgentError::Config(format!("invalid routes in {}: {}", ROUTES_ENV, e)))
+    }
+}
+
+// settings for one ai function called by one agent, the routes file is read once per run.
+// the settings a registered prompt was written for come before the routes
+pub fn route_for(operation: &str, position: &str) -> Result<ModelRoute, AgentError> {
+    let table: &RoutingTable = match ROUTING_TABLE.get() {
+        Some(table) => table,
+        None => {
+            let table: RoutingTable = RoutingTable::from_env()?;
+            ROUTING_TABLE.get_or_init(|| table)
+        }
+    };
+    let mut default: ModelRoute = ModelRoute::from_env()?;
+    if let Some(registered) = prompt_override(operation)? {
+        registered.hints.apply(&mut default);
+    }
+    Ok(table.resolve(operation, position, default))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_resolves_most_specific_route_last() {
+        let table: RoutingTable = r#"[
+            {"operation": "print_backend_webserver_code", "position": "Backend Developer", "stop": ["END"]},
+            {"position": "Backend Developer", "model": "gpt-4o", "max_tokens": 8192, "temperature": 0.2},
+            {"operation": "print_backend_webserver_code", "temperature": 0.0},
+            {"operation": "print_project_scope", "model": "gpt-4o-mini"},
+            {"top_p": 0.9}
+        ]"#
+        .parse()
+        .expect("routes should parse");
+        let default: ModelRoute = ModelRoute::new("gpt-4");
+
+        let code: ModelRoute = table.resolve(
+            "print_backend_webserver_code",
+            "Backend Developer",
+            default.clone(),
+        );
+        assert_eq!(code.model, "gpt-4o");
+        assert_eq!(code.temperature, 0.0);
+        assert_eq!(code.max_tokens, Some(8192));
+        assert_eq!(code.top_p, Some(0.9));
+        assert_eq!(code.stop, vec!["END".to_string()]);
+
+        let scope: ModelRoute = table.resolve(
+            "print_project_scope",
+            "Solutions Architect",
+            default.clone(),
+        );
+        assert_eq!(scope.model, "gpt-4o-mini");
+        assert_eq!(scope.temperature, DEFAULT_TEMPERATURE);
+
+        let unknown = "[{\"operation\": \"print_project_scope\", \"modle\": \"gpt-4o\"}]"
+            .parse::<RoutingTable>();
+        assert!(matches!(unknown, Err(AgentError::Config(_))));
+    }
+}
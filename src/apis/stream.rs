@@ -0,0 +1,55 @@
+// data of the event that ends an openai completion stream
+pub const SSE_DONE: &str = "[DONE]";
+
+// splits a server sent event stream into the data of each event
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    // bytes are kept until an event is complete, so characters split across chunks survive
+    buffer: Vec<u8>,
+}
+
+impl SseDecoder {
+    // the data of every event completed by this chunk
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer
+            .extend(chunk.iter().filter(|byte| **byte != b'\r'));
+
+        let mut events: Vec<String> = vec![];
+        while let Some(end) = self.buffer.windows(2).position(|pair| pair == b"\n\n") {
+            let event: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            let text: String = String::from_utf8_lossy(&event).to_string();
+
+            // comments and fields other than data, such as keep alives, are skipped
+            let data: Vec<&str> = text
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect();
+            if !data.is_empty() {
+                events.push(data.join("\n"));
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_decodes_events_split_across_chunks() {
+        let mut decoder: SseDecoder = SseDecoder::default();
+        let stream: &[u8] =
+            "data: {\"a\":\"é\"}\r\n\r\n: keep alive\n\ndata: [DONE]\n\n".as_bytes();
+
+        // split inside the two bytes of é
+        let split: usize = stream.iter().position(|byte| *byte == 0xc3).unwrap() + 1;
+        assert!(decoder.push(&stream[..split]).is_empty());
+        assert_eq!(
+            decoder.push(&stream[split..]),
+            vec!["{\"a\":\"é\"}", SSE_DONE]
+        );
+        assert!(decoder.push(b"data: partial").is_empty());
+    }
+}
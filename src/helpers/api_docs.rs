@@ -0,0 +1,268 @@
+use crate::models::agents::agent_traits::{ProjectScope, RouteObject};
+use serde_json::{json, Map, Value};
+
+pub const API_DOCS_FILE: &str = "docs/API.md";
+
+// everything the api reference is written from
+#[derive(Debug, Clone)]
+pub struct ApiDocSpec {
+    pub project_description: String,
+    pub project_scope: Option<ProjectScope>,
+    pub external_urls: Vec<String>,
+    pub routes: Vec<RouteObject>,
+    pub base_url: String,
+}
+
+// what the running server answered for a documented endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointCheck {
+    pub method: String,
+    pub route: String,
+    pub status: Option<u16>,
+    pub matches_schema: bool,
+}
+
+impl EndpointCheck {
+    // a documented example only counts when it succeeded with the documented body
+    pub fn is_verified(&self) -> bool {
+        self.status
+            .is_some_and(|status| (200..300).contains(&status))
+            && self.matches_schema
+    }
+}
+
+// turn a schema of type names such as {"id": "number"} into an example value
+pub fn example_value(schema: &Value) -> Option<Value> {
+    match schema {
+        Value::Null => None,
+        Value::String(type_name) => match type_name.to_lowercase().as_str() {
+            "" | "none" | "null" => None,
+            "number" | "integer" | "int" | "i32" | "i64" | "u32" | "u64" => Some(json!(1)),
+            "float" | "f32" | "f64" => Some(json!(1.5)),
+            "bool" | "boolean" => Some(json!(true)),
+            "string" | "str" => Some(json!("example")),
+            _ => Some(schema.clone()),
+        },
+        Value::Array(items) => Some(Value::Array(
+            items.first().and_then(example_value).into_iter().collect(),
+        )),
+        Value::Object(fields) => Some(Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| (name.clone(), example_value(field).unwrap_or(Value::Null)))
+                .collect::<Map<String, Value>>(),
+        )),
+        other => Some(other.clone()),
+    }
+}
+
+// a response matches when every documented field is present with the documented type
+pub fn matches_schema(schema: &Value, actual: &Value) -> bool {
+    match (schema, actual) {
+        (Value::Object(fields), Value::Object(actual_fields)) => {
+            fields.iter().all(|(name, field)| {
+                actual_fields
+                    .get(name)
+                    .is_some_and(|actual_field| matches_schema(field, actual_field))
+            })
+        }
+        (Value::Object(_), _) => false,
+        (Value::Array(items), Value::Array(actual_items)) => {
+            match (items.first(), actual_items.first()) {
+                (Some(item), Some(actual_item)) => matches_schema(item, actual_item),
+                _ => true,
+            }
+        }
+        (Value::Array(_), _) => false,
+        (Value::String(type_name), _) => match type_name.to_lowercase().as_str() {
+            "" | "none" | "null" => true,
+            "number" | "integer" | "int" | "i32" | "i64" | "u32" | "u64" | "float" | "f32"
+            | "f64" => actual.is_number(),
+            "bool" | "boolean" => actual.is_boolean(),
+            "string" | "str" => actual.is_string(),
+            // a type name such as Contact, something must have been returned
+            _ => !actual.is_null(),
+        },
+        (Value::Number(_), _) => actual.is_number(),
+        (Value::Bool(_), _) => actual.is_boolean(),
+        (Value::Null, _) => true,
+    }
+}
+
+// fill dynamic segments such as {id} so the example can be pasted as is
+pub fn example_path(route: &str) -> String {
+    route
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                "1"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<&str>>()
+        .join("/")
+}
+
+pub fn render_api_reference(spec: &ApiDocSpec, checks: Option<&[EndpointCheck]>) -> String {
+    let mut doc: String = format!("# API reference\n\n{}\n", spec.project_description.trim());
+
+    if let Some(scope) = &spec.project_scope {
+        doc.push_str(&format!(
+            "\n## Scope\n\n- CRUD: {}\n- User login and logout: {}\n- Uses external data: {}\n",
+            yes_no(scope.is_crud_required),
+            yes_no(scope.is_user_login_and_logout),
+            yes_no(scope.is_external_urls_required)
+        ));
+        if scope.is_user_login_and_logout {
+            doc.push_str("\nRoutes that need a logged in user expect `Authorization: Bearer <token>` from `POST /auth/login`.\n");
+        }
+    }
+
+    if !spec.external_urls.is_empty() {
+        doc.push_str("\n## External services\n\n");
+        for url in &spec.external_urls {
+            doc.push_str(&format!("- {}\n", url));
+        }
+    }
+
+    doc.push_str(&format!(
+        "\n## Endpoints\n\nBase URL: `{}`\n",
+        spec.base_url
+    ));
+    match checks {
+        Some(_) => doc.push_str("\nGET examples were checked against the running server when these docs were written.\n"),
+        None => doc.push_str("\nThese examples were not checked against a running server.\n"),
+    }
+
+    for route in &spec.routes {
+        doc.push_str(&render_endpoint(spec, route, checks));
+    }
+
+    doc
+}
+
+fn render_endpoint(
+    spec: &ApiDocSpec,
+    route: &RouteObject,
+    checks: Option<&[EndpointCheck]>,
+) -> String {
+    let method: String = route.method.to_uppercase();
+    let url: String = format!("{}{}", spec.base_url, example_path(&route.route));
+    let mut section: String = format!("\n### {} {}\n", method, route.route);
+
+    let mut curl: String = format!("curl -X {} {}", method, url);
+    if let Some(body) = example_value(&route.request_body) {
+        let body_json: String = serde_json::to_string_pretty(&body).unwrap_or_default();
+        section.push_str(&format!("\nRequest body:\n\n```json\n{}\n```\n", body_json));
+        curl.push_str(&format!(
+            " \\\n  -H 'Content-Type: application/json' \\\n  -d '{}'",
+            serde_json::to_string(&body).unwrap_or_default()
+        ));
+    }
+    if let Some(response) = example_value(&route.response) {
+        let response_json: String = serde_json::to_string_pretty(&response).unwrap_or_default();
+        section.push_str(&format!("\nResponse:\n\n```json\n{}\n```\n", response_json));
+    }
+    section.push_str(&format!("\nExample:\n\n```sh\n{}\n```\n", curl));
+
+    let check: Option<&EndpointCheck> = checks.and_then(|checks| {
+        checks
+            .iter()
+            .find(|check| check.method == route.method && check.route == route.route)
+    });
+    if let Some(check) = check {
+        let result: String = match check.status {
+            Some(status) if check.is_verified() => format!("verified, returned {}", status),
+            Some(status) if !(200..300).contains(&status) => format!("failed, returned {}", status),
+            Some(status) => format!(
+                "returned {} but the body did not match the documented response",
+                status
+            ),
+            None => "no response from the server".to_string(),
+        };
+        section.push_str(&format!("\nChecked: {}\n", result));
+    }
+
+    section
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(method: &str, path: &str, request_body: Value, response: Value) -> RouteObject {
+        RouteObject {
+            is_route_dynamic: path.contains('{').to_string(),
+            method: method.to_string(),
+            request_body,
+            response,
+            route: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn tests_examples_from_schema() {
+        let schema: Value = json!([{ "id": "number", "name": "string", "done": "bool" }]);
+        assert_eq!(
+            example_value(&schema),
+            Some(json!([{ "id": 1, "name": "example", "done": true }]))
+        );
+        assert_eq!(example_value(&json!("None")), None);
+        assert_eq!(example_path("/contact/{id}/notes"), "/contact/1/notes");
+
+        assert!(matches_schema(
+            &schema,
+            &json!([{ "id": 4, "name": "a", "done": false, "extra": 1 }])
+        ));
+        assert!(matches_schema(&schema, &json!([])));
+        assert!(!matches_schema(&schema, &json!([{ "id": 4 }])));
+        assert!(!matches_schema(&json!({ "id": "number" }), &json!("text")));
+        assert!(!matches_schema(
+            &json!({ "id": "number" }),
+            &json!({ "id": "4" })
+        ));
+        assert!(!matches_schema(&json!("string"), &json!(4)));
+        assert!(matches_schema(&json!("None"), &Value::Null));
+
+        let check = |status: u16| EndpointCheck {
+            method: "get".to_string(),
+            route: "/contact".to_string(),
+            status: Some(status),
+            matches_schema: true,
+        };
+        assert!(check(200).is_verified());
+        assert!(!check(404).is_verified());
+    }
+
+    #[test]
+    fn tests_renders_endpoint_with_curl_example() {
+        let spec = ApiDocSpec {
+            project_description: "Tracks contacts".to_string(),
+            project_scope: None,
+            external_urls: vec![],
+            routes: vec![route(
+                "post",
+                "/contact",
+                json!({ "name": "string" }),
+                json!("None"),
+            )],
+            base_url: "http://localhost:6678".to_string(),
+        };
+
+        let doc: String = render_api_reference(&spec, None);
+        assert!(doc.contains("### POST /contact"));
+        assert!(doc.contains(
+            "curl -X POST http://localhost:6678/contact \\\n  -H 'Content-Type: application/json' \\\n  -d '{\"name\":\"example\"}'"
+        ));
+        assert!(doc.contains("not checked against a running server"));
+    }
+}
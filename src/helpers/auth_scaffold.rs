@@ -0,0 +1,295 @@
+use crate::helpers::workspace::FileMap;
+
+// file of the generated auth module inside the backend workspace
+pub const AUTH_FILE: &str = "src/auth.rs";
+
+// the backend manifest needs argon2 = { version = "0.5", features = ["std"] }
+pub const AUTH_REQUIRED_CRATES: [&str; 1] = ["argon2"];
+
+// the auth module written into the workspace when users must log in and out
+pub fn render_auth_module() -> FileMap {
+    FileMap::from([(AUTH_FILE.to_string(), AUTH_MODULE.to_string())])
+}
+
+const AUTH_MODULE: &str = r#"// generated auth module, do not edit: accounts, password hashing, sessions and the AuthUser guard
+use actix_web::{
+    dev::Payload, error::ErrorUnauthorized, http::header, http::StatusCode, web, FromRequest,
+    HttpRequest, HttpResponse, ResponseError,
+};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{ready, Ready};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const USERS_PATH: &str = "data/users.json";
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_USERNAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredUser {
+    id: u64,
+    username: String,
+    password_hash: String,
+}
+
+// the logged in user, add it as a handler argument to require a valid session
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuthUser {
+    pub id: u64,
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    InvalidInput(&'static str),
+    UsernameTaken,
+    InvalidCredentials,
+    Internal,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInput(reason) => write!(f, "{}", reason),
+            Self::UsernameTaken => write!(f, "username is already taken"),
+            Self::InvalidCredentials => write!(f, "invalid username or password"),
+            Self::Internal => write!(f, "internal error"),
+        }
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            Self::UsernameTaken => StatusCode::CONFLICT,
+            Self::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(serde_json::json!({ "error": self.to_string() }))
+    }
+}
+
+// accounts are kept in USERS_PATH, sessions only live as long as the server
+pub struct AuthState {
+    path: Option<PathBuf>,
+    users: Mutex<Vec<StoredUser>>,
+    sessions: Mutex<HashMap<String, u64>>,
+}
+
+impl AuthState {
+    pub fn load() -> std::io::Result<Self> {
+        let path: &Path = Path::new(USERS_PATH);
+        let users: Vec<StoredUser> = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            users: Mutex::new(users),
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            users: Mutex::new(vec![]),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn register(&self, credentials: &Credentials) -> Result<AuthUser, AuthError> {
+        let username: &str = credentials.username.trim();
+        if username.is_empty() || username.len() > MAX_USERNAME_LEN {
+            return Err(AuthError::InvalidInput("username must be 1 to 64 characters"));
+        }
+        if credentials.password.len() < MIN_PASSWORD_LEN {
+            return Err(AuthError::InvalidInput("password must be at least 8 characters"));
+        }
+
+        let password_hash: String = hash_password(&credentials.password)?;
+        let mut users = self.users.lock().map_err(|_| AuthError::Internal)?;
+        if users.iter().any(|user| user.username.eq_ignore_ascii_case(username)) {
+            return Err(AuthError::UsernameTaken);
+        }
+
+        let user = StoredUser {
+            id: users.iter().map(|user| user.id).max().unwrap_or(0) + 1,
+            username: username.to_string(),
+            password_hash,
+        };
+        users.push(user.clone());
+        self.persist(&users)?;
+
+        Ok(AuthUser {
+            id: user.id,
+            username: user.username,
+        })
+    }
+
+    // returns a new session token
+    pub fn login(&self, credentials: &Credentials) -> Result<String, AuthError> {
+        let user: Option<StoredUser> = self
+            .users
+            .lock()
+            .map_err(|_| AuthError::Internal)?
+            .iter()
+            .find(|user| user.username.eq_ignore_ascii_case(credentials.username.trim()))
+            .cloned();
+
+        let user: StoredUser = match user {
+            Some(user) if verify_password(&credentials.password, &user.password_hash) => user,
+            Some(_) => return Err(AuthError::InvalidCredentials),
+            None => {
+                // spend the same time as a real check so unknown usernames are not revealed
+                let _ = hash_password(&credentials.password);
+                return Err(AuthError::InvalidCredentials);
+            }
+        };
+
+        let token: String = new_token();
+        self.sessions
+            .lock()
+            .map_err(|_| AuthError::Internal)?
+            .insert(token.clone(), user.id);
+        Ok(token)
+    }
+
+    pub fn logout(&self, token: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(token);
+        }
+    }
+
+    pub fn user_for_token(&self, token: &str) -> Option<AuthUser> {
+        let user_id: u64 = *self.sessions.lock().ok()?.get(token)?;
+        self.users
+            .lock()
+            .ok()?
+            .iter()
+            .find(|user| user.id == user_id)
+            .map(|user| AuthUser {
+                id: user.id,
+                username: user.username.clone(),
+            })
+    }
+
+    fn persist(&self, users: &[StoredUser]) -> Result<(), AuthError> {
+        let path: &PathBuf = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|_| AuthError::Internal)?;
+        }
+        let contents: String = serde_json::to_string_pretty(users).map_err(|_| AuthError::Internal)?;
+        std::fs::write(path, contents).map_err(|_| AuthError::Internal)
+    }
+}
+
+fn hash_password(password: &str) -> Result<String, AuthError> {
+    let salt: SaltString = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|_| AuthError::Internal)
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    let value: &str = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(|token| token.trim().to_string())
+}
+
+impl FromRequest for AuthUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let user: Option<AuthUser> = req
+            .app_data::<web::Data<AuthState>>()
+            .zip(bearer_token(req))
+            .and_then(|(state, token)| state.user_for_token(&token));
+        ready(user.ok_or_else(|| ErrorUnauthorized("login required")))
+    }
+}
+
+// register, login, logout and me routes under /auth
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/auth")
+            .route("/register", web::post().to(register))
+            .route("/login", web::post().to(login))
+            .route("/logout", web::post().to(logout))
+            .route("/me", web::get().to(me)),
+    );
+}
+
+async fn register(
+    state: web::Data<AuthState>,
+    credentials: web::Json<Credentials>,
+) -> Result<HttpResponse, AuthError> {
+    let user: AuthUser = state.register(&credentials)?;
+    Ok(HttpResponse::Created().json(user))
+}
+
+async fn login(
+    state: web::Data<AuthState>,
+    credentials: web::Json<Credentials>,
+) -> Result<HttpResponse, AuthError> {
+    let token: String = state.login(&credentials)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "token": token })))
+}
+
+async fn logout(state: web::Data<AuthState>, req: HttpRequest, _user: AuthUser) -> HttpResponse {
+    if let Some(token) = bearer_token(&req) {
+        state.logout(&token);
+    }
+    HttpResponse::NoContent().finish()
+}
+
+async fn me(user: AuthUser) -> HttpResponse {
+    HttpResponse::Ok().json(user)
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::workspace::validate_file_map;
+
+    #[test]
+    fn tests_auth_module_is_a_valid_workspace_file() {
+        let files: FileMap = render_auth_module();
+        assert!(validate_file_map(&files).is_ok());
+        assert!(files[AUTH_FILE].contains("pub fn configure(cfg: &mut web::ServiceConfig)"));
+        assert!(files[AUTH_FILE].contains("impl FromRequest for AuthUser"));
+    }
+}
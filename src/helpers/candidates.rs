@@ -0,0 +1,357 @@
+use crate::apis::http_client::shared_client;
+use crate::helpers::general::{check_status_code, start_web_server};
+use crate::helpers::quality_gates::{Gate, GatePipeline, GatePolicy, GateReport};
+use crate::helpers::sandbox::{CodeRunner, ExecutionMode, RunningServer};
+use crate::helpers::workspace::{write_file_map, FileMap};
+use crate::models::agents::agent_traits::RouteObject;
+use crate::models::general::errors::AgentError;
+use std::cmp::Reverse;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// how many versions of the backend code to generate and compare, one turns it off.
+// pair it with a route temperature above zero so the candidates differ
+pub const CANDIDATES_ENV: &str = "AUTO_GIPPITY_CANDIDATES";
+
+const MAX_CANDIDATES: u8 = 8;
+
+// directory inside the workspace holding one copy of it per candidate
+const CANDIDATES_DIR: &str = ".candidates";
+
+// left out of the candidate copies, the build cache is shared instead
+const SKIPPED_ENTRIES: [&str; 4] = ["target", ".git", ".sandbox", CANDIDATES_DIR];
+
+pub fn candidate_count_from_env() -> Result<u8, AgentError> {
+    let value: String = match env::var(CANDIDATES_ENV) {
+        Ok(value) if !value.trim().is_empty() => value,
+        _ => return Ok(1),
+    };
+    match value.trim().parse::<u8>() {
+        Ok(count) if (1..=MAX_CANDIDATES).contains(&count) => Ok(count),
+        _ => Err(AgentError::Config(format!(
+            "{} must be a number from 1 to {}, got '{}'",
+            CANDIDATES_ENV, MAX_CANDIDATES, value
+        ))),
+    }
+}
+
+// how a candidate did when it was built, linted and served
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CandidateScore {
+    pub builds: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    pub clippy_findings: usize,
+    pub endpoints_passed: usize,
+    // zero when the endpoints are not known yet
+    pub endpoints_checked: usize,
+}
+
+impl CandidateScore {
+    fn from_report(report: &GateReport) -> Self {
+        let mut score: Self = Self::default();
+        for result in &report.results {
+            match result.gate {
+                Gate::Check => {
+                    score.builds = result.passed;
+                    for finding in &result.findings {
+                        match finding.level.as_str() {
+                            "error" => score.errors += 1,
+                            _ => score.warnings += 1,
+                        }
+                    }
+                }
+                // clippy repeats the compiler warnings, only its own lints count here
+                Gate::Clippy => {
+                    score.clippy_findings = result
+                        .findings
+                        .iter()
+                        .filter(|finding| {
+                            finding
+                                .code
+                                .as_deref()
+                                .is_some_and(|code| code.starts_with("clippy::"))
+                        })
+                        .count();
+                }
+                _ => {}
+            }
+        }
+        score
+    }
+
+    // building comes first, then working endpoints, then the fewest problems
+    fn rank(&self) -> (bool, usize, Reverse<usize>, Reverse<usize>, Reverse<usize>) {
+        (
+            self.builds,
+            self.endpoints_passed,
+            Reverse(self.errors),
+            Reverse(self.warnings),
+            Reverse(self.clippy_findings),
+        )
+    }
+}
+
+impl fmt::Display for CandidateScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.builds {
+            return write!(f, "does not build, {} error(s)", self.errors);
+        }
+        write!(
+            f,
+            "builds, {} warning(s), {} clippy finding(s)",
+            self.warnings, self.clippy_findings
+        )?;
+        if self.endpoints_checked > 0 {
+            write!(
+                f,
+                ", {}/{} endpoints",
+                self.endpoints_passed, self.endpoints_checked
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub index: usize,
+    pub files: FileMap,
+    pub score: CandidateScore,
+}
+
+// the best scoring candidate, the earliest one on a tie
+pub fn best_candidate(candidates: &[Candidate]) -> Option<&Candidate> {
+    candidates.iter().max_by(|a, b| {
+        a.score
+            .rank()
+            .cmp(&b.score.rank())
+            .then(b.index.cmp(&a.index))
+    })
+}
+
+// builds every candidate in its own copy of the workspace, sharing one target directory
+#[derive(Debug, Clone)]
+pub struct CandidateJudge {
+    root: PathBuf,
+    mode: ExecutionMode,
+    external_urls: Vec<String>,
+    endpoints: Vec<RouteObject>,
+    port: u16,
+}
+
+impl CandidateJudge {
+    pub fn new(root: &str, mode: ExecutionMode, external_urls: &[String], port: u16) -> Self {
+        Self {
+            root: PathBuf::from(root),
+            mode,
+            external_urls: external_urls.to_vec(),
+            endpoints: vec![],
+            port,
+        }
+    }
+
+    // get routes to call on every candidate that builds
+    pub fn with_endpoints(mut self, endpoints: Vec<RouteObject>) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    // score the candidates one at a time, cargo locks the shared target directory anyway
+    // and only one server can listen on the port
+    pub async fn judge(&self, candidates: Vec<FileMap>) -> Result<Vec<Candidate>, AgentError> {
+        let mut scores: Vec<CandidateScore> = vec![];
+        for (index, files) in candidates.iter().enumerate() {
+            let workspace: PathBuf = prepare_candidate_workspace(&self.root, index, files)?;
+            let runner: CodeRunner =
+                CodeRunner::new(self.mode, &workspace.to_string_lossy(), &self.external_urls)?
+                    .with_target_dir(&self.root.join("target"));
+
+            let report: GateReport = scoring_pipeline().run(&runner).await?;
+            let mut score: CandidateScore = CandidateScore::from_report(&report);
+            if score.builds && !self.endpoints.is_empty() {
+                score.endpoints_checked = self.endpoints.len();
+                score.endpoints_passed = self.passing_endpoints(&runner).await?;
+            }
+            scores.push(score);
+        }
+
+        Ok(candidates
+            .into_iter()
+            .zip(scores)
+            .enumerate()
+            .map(|(index, (files, score))| Candidate {
+                index,
+                files,
+                score,
+            })
+            .collect())
+    }
+
+    // a candidate whose server does not come up passes none of them
+    async fn passing_endpoints(&self, runner: &CodeRunner) -> Result<usize, AgentError> {
+        let mut server: RunningServer =
+            match start_web_server(shared_client()?, runner, self.port).await {
+                Ok(server) => server,
+                Err(AgentError::CommandFailed { .. }) => return Ok(0),
+                Err(e) => return Err(e),
+            };
+
+        let mut passed: usize = 0;
+        for endpoint in &self.endpoints {
+            let url: String = format!("http://localhost:{}{}", self.port, endpoint.route);
+            if let Ok(200) = check_status_code(shared_client()?, &url).await {
+                passed += 1;
+            }
+        }
+
+        server
+            .kill()
+            .await
+            .map_err(|e| AgentError::io("Failed to kill candidate web server", e))?;
+        Ok(passed)
+    }
+
+    pub fn clean_up(&self) -> Result<(), AgentError> {
+        match fs::remove_dir_all(self.root.join(CANDIDATES_DIR)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(AgentError::io("Failed to remove candidate workspaces", e)),
+        }
+    }
+}
+
+// the compiler decides, clippy only runs on code that builds
+fn scoring_pipeline() -> GatePipeline {
+    GatePipeline::new(vec![
+        (Gate::Check, GatePolicy::Blocking),
+        (Gate::Clippy, GatePolicy::Advisory),
+    ])
+}
+
+// a fresh copy of the workspace with the candidate files written over it
+fn prepare_candidate_workspace(
+    root: &Path,
+    index: usize,
+    files: &FileMap,
+) -> Result<PathBuf, AgentError> {
+    let workspace: PathBuf = root.join(CANDIDATES_DIR).join(index.to_string());
+    if workspace.exists() {
+        fs::remove_dir_all(&workspace)
+            .map_err(|e| AgentError::io("Failed to clear candidate workspace", e))?;
+    }
+    copy_dir(root, &workspace, &SKIPPED_ENTRIES)?;
+    write_file_map(&workspace, files)?;
+    Ok(workspace)
+}
+
+fn copy_dir(from: &Path, to: &Path, skipped: &[&str]) -> Result<(), AgentError> {
+    fs::create_dir_all(to)
+        .map_err(|e| AgentError::io("Failed to create candidate workspace", e))?;
+
+    let entries = fs::read_dir(from).map_err(|e| AgentError::io("Failed to list workspace", e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| AgentError::io("Failed to list workspace", e))?;
+        if skipped.iter().any(|name| entry.file_name() == *name) {
+            continue;
+        }
+        let file_type = entry
+            .file_type()
+            .map_err(|e| AgentError::io("Failed to list workspace", e))?;
+        let target: PathBuf = to.join(entry.file_name());
+
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target, &[])?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &target).map_err(|e| {
+                AgentError::io(&format!("Failed to copy {}", entry.path().display()), e)
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(index: usize, score: CandidateScore) -> Candidate {
+        Candidate {
+            index,
+            files: FileMap::new(),
+            score,
+        }
+    }
+
+    #[test]
+    fn tests_picks_building_candidate_with_fewest_problems() {
+        let broken = CandidateScore {
+            errors: 2,
+            ..CandidateScore::default()
+        };
+        let noisy = CandidateScore {
+            builds: true,
+            warnings: 3,
+            endpoints_passed: 2,
+            endpoints_checked: 2,
+            ..CandidateScore::default()
+        };
+        let clean = CandidateScore {
+            warnings: 1,
+            ..noisy
+        };
+        let unserved = CandidateScore {
+            builds: true,
+            endpoints_passed: 1,
+            endpoints_checked: 2,
+            ..CandidateScore::default()
+        };
+
+        let candidates: Vec<Candidate> = vec![
+            candidate(0, broken),
+            candidate(1, noisy),
+            candidate(2, unserved),
+            candidate(3, clean),
+            candidate(4, clean),
+        ];
+        assert_eq!(best_candidate(&candidates).map(|c| c.index), Some(3));
+        assert_eq!(broken.to_string(), "does not build, 2 error(s)");
+        assert_eq!(
+            clean.to_string(),
+            "builds, 1 warning(s), 0 clippy finding(s), 2/2 endpoints"
+        );
+    }
+
+    #[test]
+    fn tests_copies_workspace_without_build_output() {
+        let root: PathBuf =
+            std::env::temp_dir().join(format!("autogippity-candidates-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("target/debug/app"), "").unwrap();
+
+        let files: FileMap = FileMap::from([(
+            "src/main.rs".to_string(),
+            "fn main() { todo!() }\n".to_string(),
+        )]);
+        let workspace: PathBuf = prepare_candidate_workspace(&root, 1, &files).unwrap();
+
+        assert!(workspace.join("Cargo.toml").exists());
+        assert!(!workspace.join("target").exists());
+        assert_eq!(
+            fs::read_to_string(workspace.join("src/main.rs")).unwrap(),
+            "fn main() { todo!() }\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
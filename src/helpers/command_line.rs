@@ -1,8 +1,20 @@
+use crate::models::general::errors::AgentError;
 use crossterm::{
     style::{Color, ResetColor, SetForegroundColor},
     ExecutableCommand,
 };
-use std::io::{stdin, stdout};
+use std::io::{stdin, stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// agents running in parallel ask for confirmation one at a time
+static CONFIRM_LOCK: Mutex<()> = Mutex::new(());
+
+// only one completion streams to the terminal at a time, the others stay quiet
+static LIVE_STREAM: AtomicBool = AtomicBool::new(false);
+
+// lines of a streamed completion shown before it collapses into a counter
+const MAX_STREAM_PREVIEW_LINES: usize = 8;
 
 #[derive(PartialEq, Debug)]
 pub enum PrintCommand {
@@ -32,9 +44,85 @@ impl PrintCommand {
         stdout.execute(ResetColor).unwrap();
     }
 }
-// get user response that code is safe to execute
-pub fn confirm_safe_code() -> bool {
-    
+
+// shows a completion under the agent banner while it streams
+#[derive(Debug)]
+pub struct StreamPrinter {
+    live: bool,
+    lines: usize,
+    chars: usize,
+}
+
+impl StreamPrinter {
+    pub fn new() -> Self {
+        let live: bool = LIVE_STREAM
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+        Self {
+            live,
+            lines: 0,
+            chars: 0,
+        }
+    }
+
+    // long output such as code collapses into a running count after the first lines
+    pub fn print_delta(&mut self, delta: &str) {
+        self.chars += delta.chars().count();
+        if !self.live {
+            return;
+        }
+
+        let mut stdout: std::io::Stdout = stdout();
+        stdout.execute(SetForegroundColor(Color::DarkGrey)).unwrap();
+        for piece in delta.split_inclusive('\n') {
+            if self.lines < MAX_STREAM_PREVIEW_LINES {
+                print!("{}", piece);
+            }
+            if piece.ends_with('\n') {
+                self.lines += 1;
+            }
+        }
+        if self.lines >= MAX_STREAM_PREVIEW_LINES {
+            print!("\r  ... {} lines, {} characters", self.lines, self.chars);
+        }
+        stdout.execute(ResetColor).unwrap();
+        stdout.flush().ok();
+    }
+
+    pub fn finish(self) {
+        if self.live && self.chars > 0 {
+            println!();
+        }
+    }
+}
+
+impl Drop for StreamPrinter {
+    fn drop(&mut self) {
+        if self.live {
+            LIVE_STREAM.store(false, Ordering::Release);
+        }
+    }
+}
+
+// print an error followed by each of its causes
+pub fn print_agent_error(agent_pos: &str, error: &AgentError) {
+    let chain: Vec<String> = error.chain();
+    PrintCommand::Issue.print_agent_message(agent_pos, &format!("Error: {}", chain[0]));
+    for cause in chain.iter().skip(1) {
+        PrintCommand::Issue.print_agent_message(agent_pos, &format!("  caused by: {}", cause));
+    }
+}
+
+// get user response that code is safe to execute, stdin is read off the async workers so
+// agents in the same wave keep running while one waits for an answer
+pub async fn confirm_safe_code() -> bool {
+    tokio::task::spawn_blocking(prompt_safe_code)
+        .await
+        .unwrap_or(false)
+}
+
+fn prompt_safe_code() -> bool {
+    let _guard = CONFIRM_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
     let mut stdout: std::io::Stdout = stdout();
     loop {
         stdout.execute( SetForegroundColor(Color::DarkYellow) ).unwrap();
@@ -73,6 +161,36 @@ pub fn confirm_safe_code() -> bool {
     }
 }
 
+// ask the user whether an agent may run a tool
+pub async fn confirm_tool_call(agent_pos: &str, tool_call: &str) -> bool {
+    let (agent_pos, tool_call) = (agent_pos.to_string(), tool_call.to_string());
+    tokio::task::spawn_blocking(move || prompt_tool_call(&agent_pos, &tool_call))
+        .await
+        .unwrap_or(false)
+}
+
+fn prompt_tool_call(agent_pos: &str, tool_call: &str) -> bool {
+    let _guard = CONFIRM_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut stdout: std::io::Stdout = stdout();
+    loop {
+        stdout.execute(SetForegroundColor(Color::DarkYellow)).unwrap();
+        println!("{} wants to run the tool {}", agent_pos, tool_call);
+        stdout.execute(ResetColor).unwrap();
+        println!("Allow it? [y/n]");
+
+        let mut human_response: String = String::new();
+        stdin()
+            .read_line(&mut human_response)
+            .expect("Failed to read response");
+
+        match human_response.trim().to_lowercase().as_str() {
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => println!("Invalid input, please answer y or n"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
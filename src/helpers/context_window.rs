@@ -0,0 +1,347 @@
+use crate::apis::model_routing::ModelRoute;
+use crate::helpers::general::load_env;
+use crate::models::general::errors::AgentError;
+use crate::models::general::llm::Message;
+use std::env;
+use std::sync::OnceLock;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+pub const CONTEXT_TOKENS_ENV: &str = "AUTO_GIPPITY_CONTEXT_TOKENS";
+pub const RESPONSE_TOKENS_ENV: &str = "AUTO_GIPPITY_RESPONSE_TOKENS";
+
+// room left for the answer when the window is not configured
+const DEFAULT_RESPONSE_TOKENS: usize = 4096;
+
+// most of the window an answer may take, the rest is kept for the prompt
+const RESPONSE_SHARE_PERCENT: usize = 25;
+
+// window assumed for models tiktoken does not know, such as local ones, unless
+// AUTO_GIPPITY_CONTEXT_TOKENS says otherwise
+const UNKNOWN_MODEL_CONTEXT_TOKENS: usize = 32_768;
+
+// share of the prompt budget earlier exchanges may take before they are summarised
+const HISTORY_SHARE_PERCENT: usize = 25;
+
+// per message framing added by the chat format, and the tokens priming the reply
+const TOKENS_PER_MESSAGE: usize = 4;
+const TOKENS_PER_REPLY: usize = 3;
+
+const TRUNCATION_MARKER: &str = " [truncated]";
+
+static CL100K: OnceLock<CoreBPE> = OnceLock::new();
+static O200K: OnceLock<CoreBPE> = OnceLock::new();
+
+// counts tokens locally with the tokenizer of the configured model
+#[derive(Clone, Copy)]
+pub struct TokenCounter {
+    bpe: &'static CoreBPE,
+}
+
+impl std::fmt::Debug for TokenCounter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenCounter")
+    }
+}
+
+impl TokenCounter {
+    // models without a known tokenizer, such as local ones, are counted as cl100k
+    pub fn for_model(model: &str) -> Self {
+        let bpe: &'static CoreBPE = match get_tokenizer(model) {
+            Some(Tokenizer::O200kBase) => O200K
+                .get_or_init(|| tiktoken_rs::o200k_base().expect("o200k vocabulary is bundled")),
+            _ => CL100K
+                .get_or_init(|| tiktoken_rs::cl100k_base().expect("cl100k vocabulary is bundled")),
+        };
+        Self { bpe }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+
+    pub fn count_messages(&self, messages: &[Message]) -> usize {
+        let framed: usize = messages
+            .iter()
+            .map(|message| {
+                let name: usize = message.name.as_deref().map_or(0, |name| self.count(name));
+                let tool_calls: usize = message
+                    .tool_calls
+                    .iter()
+                    .map(|call| {
+                        self.count(&call.function.name) + self.count(&call.function.arguments)
+                    })
+                    .sum();
+                TOKENS_PER_MESSAGE
+                    + self.count(&message.role.to_string())
+                    + self.count(&message.content)
+                    + name
+                    + tool_calls
+            })
+            .sum();
+        if messages.is_empty() {
+            framed
+        } else {
+            framed + TOKENS_PER_REPLY
+        }
+    }
+
+    // the longest prefix of the text within the token limit
+    pub fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        let tokens = self.bpe.encode_with_special_tokens(text);
+        if tokens.len() <= max_tokens {
+            return text.to_string();
+        }
+        self.bpe
+            .decode(tokens[..max_tokens].to_vec())
+            .unwrap_or_else(|_| text.chars().take(max_tokens * 3).collect())
+    }
+}
+
+// the token window of the model used for a call
+#[derive(Debug, Clone, Copy)]
+pub struct ContextWindow {
+    pub counter: TokenCounter,
+    pub max_tokens: usize,
+    pub response_tokens: usize,
+}
+
+impl ContextWindow {
+    pub fn for_model(model: &str) -> Self {
+        // tiktoken falls back to a 4096 token window for models it does not know
+        let max_tokens: usize = match get_tokenizer(model) {
+            Some(_) => tiktoken_rs::model::get_context_size(model),
+            None => UNKNOWN_MODEL_CONTEXT_TOKENS,
+        };
+        Self {
+            counter: TokenCounter::for_model(model),
+            max_tokens,
+            response_tokens: DEFAULT_RESPONSE_TOKENS,
+        }
+        .with_capped_response()
+    }
+
+    // window of the routed model, with the sizes overridable from the environment and
+    // room left for the answer length the route asks for, up to a share of the window
+    pub fn for_route(route: &ModelRoute) -> Result<Self, AgentError> {
+        load_env();
+        let mut window: Self = Self::for_model(&route.model);
+
+        if let Some(max_tokens) = read_token_setting(CONTEXT_TOKENS_ENV)? {
+            window.max_tokens = max_tokens;
+        }
+        if let Some(response_tokens) = read_token_setting(RESPONSE_TOKENS_ENV)? {
+            window.response_tokens = response_tokens;
+        }
+        if let Some(max_tokens) = route.max_tokens {
+            window.response_tokens = max_tokens as usize;
+        }
+
+        Ok(window.with_capped_response())
+    }
+
+    fn with_capped_response(mut self) -> Self {
+        self.response_tokens = self
+            .response_tokens
+            .min(self.max_tokens * RESPONSE_SHARE_PERCENT / 100);
+        self
+    }
+
+    // tokens all messages of a call may use together
+    pub fn prompt_budget(&self) -> usize {
+        self.max_tokens - self.response_tokens
+    }
+
+    pub fn history_budget(&self) -> usize {
+        self.prompt_budget() * HISTORY_SHARE_PERCENT / 100
+    }
+}
+
+fn read_token_setting(key: &str) -> Result<Option<usize>, AgentError> {
+    match env::var(key) {
+        Ok(value) => value.trim().parse::<usize>().map(Some).map_err(|_| {
+            AgentError::Config(format!(
+                "{} must be a number of tokens, got '{}'",
+                key, value
+            ))
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+// how hard the assembler tries to keep a section of the prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SectionPriority {
+    Low,
+    High,
+    // never dropped, only cut short when nothing else is left to drop
+    Required,
+}
+
+#[derive(Debug, Clone)]
+struct PromptSection {
+    label: &'static str,
+    content: String,
+    priority: SectionPriority,
+}
+
+// a prompt context of labelled sections, fitted into a token budget
+#[derive(Debug, Clone, Default)]
+pub struct ContextAssembler {
+    sections: Vec<PromptSection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembledContext {
+    pub text: String,
+    pub tokens: usize,
+    pub dropped: Vec<&'static str>,
+    pub truncated: Vec<&'static str>,
+}
+
+impl ContextAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_section(
+        mut self,
+        label: &'static str,
+        content: impl Into<String>,
+        priority: SectionPriority,
+    ) -> Self {
+        self.sections.push(PromptSection {
+            label,
+            content: content.into(),
+            priority,
+        });
+        self
+    }
+
+    // drop the least important sections, latest first, then cut the largest required one
+    pub fn assemble(&self, counter: &TokenCounter, budget: usize) -> AssembledContext {
+        let mut sections: Vec<PromptSection> = self.sections.clone();
+        let mut dropped: Vec<&'static str> = vec![];
+        let mut truncated: Vec<&'static str> = vec![];
+
+        let mut tokens: usize = count_sections(counter, &sections);
+        while tokens > budget {
+            let droppable: Option<usize> = sections
+                .iter()
+                .enumerate()
+                .filter(|(_, section)| section.priority != SectionPriority::Required)
+                .min_by_key(|(index, section)| (section.priority, std::cmp::Reverse(*index)))
+                .map(|(index, _)| index);
+            let Some(index) = droppable else {
+                break;
+            };
+            dropped.push(sections.remove(index).label);
+            tokens = count_sections(counter, &sections);
+        }
+
+        // the text kept shrinks every pass, so this ends even when the marker does not fit
+        while tokens > budget {
+            let Some(largest) = sections
+                .iter_mut()
+                .max_by_key(|section| counter.count(&section.content))
+            else {
+                break;
+            };
+            let content: &str = largest
+                .content
+                .strip_suffix(TRUNCATION_MARKER)
+                .unwrap_or(&largest.content);
+            let keep: usize = counter.count(content).saturating_sub(tokens - budget);
+            largest.content = format!("{}{}", counter.truncate(content, keep), TRUNCATION_MARKER);
+            if !truncated.contains(&largest.label) {
+                truncated.push(largest.label);
+            }
+            tokens = count_sections(counter, &sections);
+            if keep == 0 {
+                break;
+            }
+        }
+
+        AssembledContext {
+            text: render_sections(&sections),
+            tokens,
+            dropped,
+            truncated,
+        }
+    }
+}
+
+fn render_sections(sections: &[PromptSection]) -> String {
+    sections
+        .iter()
+        .map(|section| format!("{}: {} \n", section.label, section.content))
+        .collect()
+}
+
+fn count_sections(counter: &TokenCounter, sections: &[PromptSection]) -> usize {
+    counter.count(&render_sections(sections))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_counts_tokens_per_model() {
+        let counter: TokenCounter = TokenCounter::for_model("gpt-4");
+        assert_eq!(counter.count("hello world"), 2);
+        assert_eq!(counter.truncate("hello world", 1), "hello");
+
+        let message = Message::user("hello world");
+        assert_eq!(counter.count_messages(&[message]), 4 + 1 + 2 + 3);
+        assert_eq!(
+            ContextWindow::for_model("gpt-4").prompt_budget(),
+            8192 - 2048
+        );
+        assert_eq!(ContextWindow::for_model("gpt-4o").response_tokens, 4096);
+    }
+
+    #[test]
+    fn tests_sizes_unknown_models_and_long_answers() {
+        let local: ContextWindow = ContextWindow::for_model("llama3-local");
+        assert_eq!(local.max_tokens, UNKNOWN_MODEL_CONTEXT_TOKENS);
+        assert_eq!(local.response_tokens, DEFAULT_RESPONSE_TOKENS);
+
+        let route: ModelRoute = ModelRoute {
+            max_tokens: Some(8192),
+            ..ModelRoute::new("gpt-4")
+        };
+        let window: ContextWindow = ContextWindow::for_route(&route).unwrap();
+        assert_eq!(window.response_tokens, 2048);
+        assert_eq!(window.prompt_budget(), 6144);
+    }
+
+    #[test]
+    fn tests_assembles_within_budget() {
+        let counter: TokenCounter = TokenCounter::for_model("gpt-4o");
+        let assembler: ContextAssembler = ContextAssembler::new()
+            .with_section(
+                "CODE_FILES",
+                "fn main() {} ".repeat(40),
+                SectionPriority::Required,
+            )
+            .with_section(
+                "API_ENDPOINTS",
+                "get /users ".repeat(40),
+                SectionPriority::Low,
+            )
+            .with_section("PROJECT_SCOPE", "crud only", SectionPriority::High);
+
+        let full: AssembledContext = assembler.assemble(&counter, 10_000);
+        assert!(full.dropped.is_empty() && full.truncated.is_empty());
+
+        let fitted: AssembledContext = assembler.assemble(&counter, full.tokens - 20);
+        assert_eq!(fitted.dropped, vec!["API_ENDPOINTS"]);
+        assert!(fitted.text.contains("PROJECT_SCOPE: crud only"));
+
+        let cut: AssembledContext = assembler.assemble(&counter, 60);
+        assert_eq!(cut.dropped, vec!["API_ENDPOINTS", "PROJECT_SCOPE"]);
+        assert_eq!(cut.truncated, vec!["CODE_FILES"]);
+        assert!(cut.tokens <= 60);
+    }
+}
@@ -1,123 +1,572 @@
-use crate::apis::call_request::call_gpt;
-use crate::helpers::command_line::PrintCommand;
-use crate::models::general::llm::Message;
+use crate::ai_functions::aifunc_memory::print_history_summary;
+use crate::ai_functions::prompt_registry::{prompt_for, Prompt};
+use crate::ai_functions::typed_function::{render_input, AiFunction, AiOutput};
+use crate::apis::call_request::{call_gpt, call_gpt_choices, call_gpt_tools, CONTINUE_PROMPT};
+use crate::apis::model_routing::{route_for, ModelRoute};
+use crate::helpers::command_line::{print_agent_error, PrintCommand};
+use crate::helpers::context_window::ContextWindow;
+use crate::helpers::git_history::CodeHistory;
+use crate::helpers::sandbox::{CodeRunner, RunningServer};
+use crate::helpers::tools::{JsonToolCall, ToolBox, ToolProtocol};
+use crate::helpers::transcript::TranscriptEntry;
+use crate::helpers::workspace::{
+    read_workspace_files, write_file_map, write_packaging_files, FileMap,
+};
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::general::errors::AgentError;
+use crate::models::general::llm::{APIChoice, Message, Role};
+use dotenv::dotenv;
 use reqwest::Client;
-use serde::de::DeserializeOwned;
+use serde_json::Value;
 use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, Once};
+use std::process::Output;
+use std::time::{Duration, Instant};
+use tokio::time;
 use regex::Regex;
 
 const CODE_TEMPLATE_PATH: &str = "/home/ubuntu/rust_autogpt/web_template/src/code_template.rs";
 pub const WEB_SERVER_PROJECT_PATH: &str = "/home/ubuntu/rust_autogpt/web_template/";
-const EXEC_MAIN_PATH: &str = "/home/ubuntu/rust_autogpt/web_template/src/main.rs";
+pub const WEB_SERVER_PORT: u16 = 6678;
 const API_SCHEMA_PATH: &str = "/home/ubuntu/rust_autogpt/auto_gippity/schemas/api_schema.json";
 
+// a url that takes longer than this to answer counts as broken
+pub const URL_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// a built server that does not answer within this long counts as not starting
+const SERVER_READY_TIMEOUT: Duration = Duration::from_secs(30);
+const SERVER_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// agents running in parallel share one git repository in the workspace
+static WORKSPACE_COMMIT_LOCK: Mutex<()> = Mutex::new(());
+
+static LOAD_ENV: Once = Once::new();
+
+// read .env once per run, variables already set take precedence
+pub fn load_env() {
+    LOAD_ENV.call_once(|| {
+        dotenv().ok();
+    });
+}
+
 
 // Extend ai function to encourage specific output
-pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -> Message {
+// the instruction goes in a system message and the input to the function in a user message
+pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -> (Message, Message) {
     // run the ai function to get the return string
-    let ai_function_str = ai_func(func_input);
+    extend_prompt(ai_func(func_input), func_input)
+}
 
+fn extend_prompt(ai_function_str: &str, func_input: &str) -> (Message, Message) {
     // extend the string to encourage only printing the output
     let msg: String = format!(
         "FUNCTION {}
     INSTRUCTION You are a function printer. You ONLY print the results of functions.
-    Nothing else. No commentry. Here is the input to the function: {}.
+    Nothing else. No commentry. The user message is the input to the function.
     Print out what the function will return.",
-        ai_function_str, func_input
+        ai_function_str
     );
 
-    Message {
-        role: "system".to_string(),
-        content: msg,
-    }
+    (Message::system(msg), Message::user(func_input))
 }
 
-// perform call to LLM GPT - decoded
-pub async fn ai_task_request_decoded<T: DeserializeOwned>(
-    msg_context: String,
-    agent_position: &str,
+// the prompt registered for an ai function, or its built in text, extended the same way
+fn extend_registered_ai_function(
     agent_operation: &str,
-    function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> T {
-    let llm_response: String =
-        ai_task_request(msg_context, agent_position, agent_operation, function_pass).await;
+    ai_func: fn(&str) -> &'static str,
+    func_input: &str,
+) -> Result<(Prompt, Message, Message), AgentError> {
+    let prompt: Prompt = prompt_for(agent_operation, ai_func, func_input)?;
+    let (instruction, input) = extend_prompt(&prompt.text, func_input);
+    Ok((prompt, instruction, input))
+}
 
-    let decoded_response: T = serde_json::from_str(llm_response.as_str())
-        .expect("failed to decode ai response from serde json");
+// perform call to LLM GPT for a typed ai function, telling it the output format
+pub async fn ai_function_request<F: AiFunction>(
+    input: &F::Input,
+    agent: &mut BasicAgent,
+) -> Result<F::Output, AgentError> {
+    let (prompt, instruction, input) = extend_typed_ai_function::<F>(input)?;
 
-    return decoded_response;
+    PrintCommand::AICall.print_agent_message(&agent.position, F::NAME);
+
+    let llm_response: String = complete_ai_task(agent, F::NAME, &prompt, instruction, input).await?;
+    F::Output::decode(&llm_response).map_err(|source| AgentError::Decode {
+        operation: F::NAME.to_string(),
+        source,
+    })
+}
+
+// the instructions of a typed ai function with its output format, and its rendered input
+fn extend_typed_ai_function<F: AiFunction>(
+    input: &F::Input,
+) -> Result<(Prompt, Message, Message), AgentError> {
+    let (prompt, mut instruction, input) =
+        extend_registered_ai_function(F::NAME, F::instructions, &render_input(input))?;
+    instruction.content.push_str(&format!(
+        "\n    OUTPUT_FORMAT {}",
+        <F::Output as AiOutput>::output_instruction()
+    ));
+    Ok((prompt, instruction, input))
 }
 
 // get ai response without markdown code wrapper
 pub async fn ai_task_request_without_markdown(
     msg_context: String,
-    agent_position: &str,
+    agent: &mut BasicAgent,
     agent_operation: &str,
     function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> String {
+) -> Result<String, AgentError> {
     let llm_response: String =
-        ai_task_request(msg_context, agent_position, agent_operation, function_pass).await;
-    
+        ai_task_request(msg_context, agent, agent_operation, function_pass).await?;
+    Ok(strip_markdown(&llm_response))
+}
+
+// remove the code block the llm wraps its output in
+pub fn strip_markdown(llm_response: &str) -> String {
     let markdown_regex: Regex = Regex::new(r"(^```.*(\r\n|\r|\n)|```\s*$)").unwrap();
-    let llm_result = markdown_regex.replace_all( llm_response.as_ref(),"");
-    return llm_result.to_string();
+    let llm_result = markdown_regex.replace_all( llm_response,"");
+    llm_result.to_string()
 }
 
 // perform call to LLM GPT
 pub async fn ai_task_request(
     msg_context: String,
-    agent_position: &str,
+    agent: &mut BasicAgent,
     agent_operation: &str,
     function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> String {
-    let extended_msg: Message = extend_ai_function(function_pass, &msg_context);
+) -> Result<String, AgentError> {
+    let (prompt, instruction, input) =
+        extend_registered_ai_function(agent_operation, function_pass, &msg_context)?;
+
+    PrintCommand::AICall.print_agent_message(&agent.position, agent_operation);
 
-    PrintCommand::AICall.print_agent_message(agent_position, agent_operation);
+    complete_ai_task(agent, agent_operation, &prompt, instruction, input).await
+}
+
+// route, fit and send the call, then record and remember the exchange
+async fn complete_ai_task(
+    agent: &mut BasicAgent,
+    agent_operation: &str,
+    prompt: &Prompt,
+    instruction: Message,
+    input: Message,
+) -> Result<String, AgentError> {
+    let route: ModelRoute = route_for(agent_operation, &agent.position)?;
+    let window: ContextWindow = ContextWindow::for_route(&route)?;
+    let messages: Vec<Message> =
+        prepare_messages(agent, agent_operation, prompt, instruction, input.clone(), &window).await?;
 
     // get LLM response
-    let llm_result: Result<String, Box<dyn std::error::Error + Send>> =
-        call_gpt(vec![extended_msg.clone()]).await;
+    let llm_result: Result<String, AgentError> = call_gpt(messages.clone(), &route).await;
 
     // return success or try again
-    match llm_result {
-        Ok(llm_response) => {
-            // remove markdown from response
-            llm_response
-        },
-        Err(_) => call_gpt(vec![extended_msg.clone()])
-            .await
-            .expect("Failed to call LLM"),
+    let llm_response: String = match llm_result {
+        Ok(llm_response) => llm_response,
+        Err(e) if e.is_transient() => call_gpt(messages.clone(), &route).await?,
+        Err(e) => return Err(e),
+    };
+
+    TranscriptEntry::new(
+        &agent.position,
+        agent_operation,
+        prompt,
+        &route,
+        &messages,
+        std::slice::from_ref(&llm_response),
+    )
+    .record();
+    agent.remember(agent_operation, input, llm_response.clone());
+    Ok(llm_response)
+}
+
+// several answers of a typed ai function, without markdown, for the caller to choose from.
+// nothing is remembered until the caller knows which answer it keeps
+pub async fn ai_function_candidates<F: AiFunction>(
+    input: &F::Input,
+    agent: &mut BasicAgent,
+    count: u8,
+) -> Result<Vec<String>, AgentError> {
+    let agent_operation: &str = F::NAME;
+    let (prompt, instruction, input) = extend_typed_ai_function::<F>(input)?;
+
+    PrintCommand::AICall.print_agent_message(
+        &agent.position,
+        &format!("{} ({} candidates)", agent_operation, count),
+    );
+
+    let route: ModelRoute = route_for(agent_operation, &agent.position)?;
+    let window: ContextWindow = ContextWindow::for_route(&route)?;
+    let messages: Vec<Message> =
+        prepare_messages(agent, agent_operation, &prompt, instruction, input, &window).await?;
+
+    let choices = match call_gpt_choices(messages.clone(), &route, count).await {
+        Ok(choices) => choices,
+        Err(e) if e.is_transient() => call_gpt_choices(messages.clone(), &route, count).await?,
+        Err(e) => return Err(e),
+    };
+
+    let llm_responses: Vec<String> = choices
+        .into_iter()
+        .map(|choice| choice.into_message().content)
+        .collect();
+    TranscriptEntry::new(&agent.position, agent_operation, &prompt, &route, &messages, &llm_responses)
+        .record();
+
+    Ok(llm_responses
+        .iter()
+        .map(|llm_response| strip_markdown(llm_response))
+        .collect())
+}
+
+// perform call to LLM GPT, letting it inspect the workspace with tools before it answers
+pub async fn ai_task_request_with_tools(
+    msg_context: String,
+    agent: &mut BasicAgent,
+    agent_operation: &str,
+    function_pass: for<'a> fn(&'a str) -> &'static str,
+    tools: &ToolBox,
+) -> Result<String, AgentError> {
+    let (prompt, mut instruction, input) =
+        extend_registered_ai_function(agent_operation, function_pass, &msg_context)?;
+    instruction
+        .content
+        .push_str("\n    You may call the tools to inspect the project before printing the result.");
+    if tools.protocol() == ToolProtocol::Json {
+        instruction.content.push_str(&format!("\n    {}", tools.json_protocol_prompt()));
+    }
+
+    PrintCommand::AICall.print_agent_message(&agent.position, agent_operation);
+
+    let route: ModelRoute = route_for(agent_operation, &agent.position)?;
+    let window: ContextWindow = ContextWindow::for_route(&route)?;
+    let mut messages: Vec<Message> =
+        prepare_messages(agent, agent_operation, &prompt, instruction, input.clone(), &window).await?;
+
+    let mut llm_response: Option<String> = None;
+    for _ in 0..tools.max_iterations() {
+        match tools.protocol() {
+            ToolProtocol::Native => {
+                let reply: Message = call_with_tools(&messages, &route, tools, true).await?;
+                if reply.tool_calls.is_empty() {
+                    llm_response = Some(reply.content);
+                    break;
+                }
+
+                messages.push(reply.clone());
+                for call in reply.tool_calls {
+                    let arguments: Value =
+                        serde_json::from_str(&call.function.arguments).unwrap_or(Value::Null);
+                    let output: String =
+                        run_tool(agent, tools, &window, &messages, &call.function.name, &arguments).await;
+                    messages.push(Message::tool_result(&call.id, output));
+                }
+            }
+            ToolProtocol::Json => {
+                let reply: String = call_gpt(messages.clone(), &route).await?;
+                let Some(call) = JsonToolCall::parse(&reply) else {
+                    llm_response = Some(reply);
+                    break;
+                };
+
+                messages.push(Message::assistant(reply));
+                let output: String =
+                    run_tool(agent, tools, &window, &messages, &call.tool, &call.arguments).await;
+                messages.push(Message::user(format!("TOOL_RESULT {}: {}", call.tool, output)));
+            }
+        }
     }
+
+    // out of tool calls, so it answers with what it found
+    let llm_response: String = match llm_response {
+        Some(llm_response) => llm_response,
+        None => {
+            messages.push(Message::user(
+                "No more tool calls are available. Print what the function returns now.",
+            ));
+            match tools.protocol() {
+                ToolProtocol::Native => call_with_tools(&messages, &route, tools, false).await?.content,
+                ToolProtocol::Json => call_gpt(messages.clone(), &route).await?,
+            }
+        }
+    };
+
+    TranscriptEntry::new(
+        &agent.position,
+        agent_operation,
+        &prompt,
+        &route,
+        &messages,
+        std::slice::from_ref(&llm_response),
+    )
+    .record();
+    agent.remember(agent_operation, input, llm_response.clone());
+    Ok(llm_response)
+}
+
+// one reply offering the tools, tried again when the call failed on the way and continued
+// when the answer was cut off at the token limit
+async fn call_with_tools(
+    messages: &[Message],
+    route: &ModelRoute,
+    tools: &ToolBox,
+    allow_calls: bool,
+) -> Result<Message, AgentError> {
+    let choice: APIChoice = match call_gpt_tools(messages.to_vec(), route, tools.definitions(), allow_calls).await {
+        Ok(choice) => choice,
+        Err(e) if e.is_transient() => {
+            call_gpt_tools(messages.to_vec(), route, tools.definitions(), allow_calls).await?
+        }
+        Err(e) => return Err(e),
+    };
+
+    let truncated: bool = choice.is_truncated();
+    let mut reply: Message = choice.into_message();
+    if truncated && reply.tool_calls.is_empty() {
+        let mut continued: Vec<Message> = messages.to_vec();
+        continued.push(Message::assistant(reply.content.clone()));
+        continued.push(Message::user(CONTINUE_PROMPT));
+        reply.content.push_str(&call_gpt(continued, route).await?);
+    }
+    Ok(reply)
+}
+
+// a tool result that would not fit the context window is replaced by a note saying so
+async fn run_tool(
+    agent: &BasicAgent,
+    tools: &ToolBox,
+    window: &ContextWindow,
+    messages: &[Message],
+    name: &str,
+    arguments: &Value,
+) -> String {
+    PrintCommand::AICall.print_agent_message(&agent.position, &format!("Using tool {} {}", name, arguments));
+    let output: String = tools.run(&agent.position, name, arguments).await;
+
+    let tokens: usize = window.counter.count_messages(messages) + window.counter.count(&output);
+    if tokens > window.prompt_budget() {
+        return "error: no room left in the context window, answer with what you have".to_string();
+    }
+    output
+}
+
+// the instruction, the examples of the prompt, the related history that fits and the input
+async fn prepare_messages(
+    agent: &mut BasicAgent,
+    agent_operation: &str,
+    prompt: &Prompt,
+    instruction: Message,
+    input: Message,
+    window: &ContextWindow,
+) -> Result<Vec<Message>, AgentError> {
+    // earlier calls of the same function go first so a follow up knows what was tried
+    let mut messages: Vec<Message> = vec![instruction.clone()];
+    messages.extend(prompt.example_messages());
+    messages.extend(fit_history(agent, agent_operation, window).await?);
+    messages.push(input.clone());
+
+    // the examples and history are left out rather than the prompt when all do not fit
+    if window.counter.count_messages(&messages) > window.prompt_budget() {
+        messages = vec![instruction, input];
+        let tokens: usize = window.counter.count_messages(&messages);
+        if tokens > window.prompt_budget() {
+            return Err(AgentError::ContextOverflow {
+                operation: agent_operation.to_string(),
+                tokens,
+                budget: window.prompt_budget(),
+            });
+        }
+    }
+
+    Ok(messages)
+}
+
+// summarise the related exchanges once they outgrow the history budget
+async fn fit_history(
+    agent: &mut BasicAgent,
+    agent_operation: &str,
+    window: &ContextWindow,
+) -> Result<Vec<Message>, AgentError> {
+    let history: Vec<Message> = agent.relevant_history(agent_operation);
+    if window.counter.count_messages(&history) <= window.history_budget() {
+        return Ok(history);
+    }
+
+    PrintCommand::AICall.print_agent_message(&agent.position, "Summarising earlier exchanges");
+
+    // every message gets an equal share so the latest exchange is not cut off
+    let share: usize = window.prompt_budget() / (history.len() + 1);
+    let exchanges: String = history
+        .iter()
+        .map(|message| {
+            let label: &str = if message.role == Role::Assistant { "RESPONSE" } else { "PROMPT" };
+            format!("{}: {} \n", label, window.counter.truncate(&message.content, share))
+        })
+        .collect();
+
+    let summary_operation: &str = get_function_string!(print_history_summary);
+    let (prompt, instruction, input) =
+        extend_registered_ai_function(summary_operation, print_history_summary, &exchanges)?;
+    let route: ModelRoute = route_for(summary_operation, &agent.position)?;
+    let mut messages: Vec<Message> = vec![instruction];
+    messages.extend(prompt.example_messages());
+    messages.push(input);
+
+    let summary: String = call_gpt(messages.clone(), &route).await?;
+    TranscriptEntry::new(
+        &agent.position,
+        summary_operation,
+        &prompt,
+        &route,
+        &messages,
+        std::slice::from_ref(&summary),
+    )
+    .record();
+    agent.summarise_history(agent_operation, summary);
+    Ok(agent.relevant_history(agent_operation))
+}
+
+// tokens left for the context of an ai function once its instructions and history are counted
+pub fn context_budget(
+    agent_position: &str,
+    agent_operation: &str,
+    function_pass: for<'a> fn(&'a str) -> &'static str,
+) -> Result<(ContextWindow, usize), AgentError> {
+    let window: ContextWindow = ContextWindow::for_route(&route_for(agent_operation, agent_position)?)?;
+    let (prompt, instruction, input) =
+        extend_registered_ai_function(agent_operation, function_pass, "")?;
+    let mut messages: Vec<Message> = vec![instruction];
+    messages.extend(prompt.example_messages());
+    messages.push(input);
+    let instructions: usize = window.counter.count_messages(&messages);
+    let budget: usize = window
+        .prompt_budget()
+        .saturating_sub(window.history_budget() + instructions);
+    Ok((window, budget))
+}
+
+// status and json body of a GET, the body is null when it is not json
+pub async fn fetch_json(client: &Client, url: &str) -> Result<(u16, Value), reqwest::Error> {
+    let response: reqwest::Response = client.get(url).timeout(URL_CHECK_TIMEOUT).send().await?;
+    let status: u16 = response.status().as_u16();
+    Ok((status, response.json().await.unwrap_or(Value::Null)))
 }
 
 // check whether url is valid
 pub async fn check_status_code(client: &Client, url: &str) -> Result<u16, reqwest::Error> {
-    let response: reqwest::Response = client.get(url).send().await?;
+    let response: reqwest::Response = client.get(url).timeout(URL_CHECK_TIMEOUT).send().await?;
     Ok(response.status().as_u16())
 }
 
+// build the server, start it and wait until it answers, so the time a first compile
+// takes is not mistaken for failing endpoints
+pub async fn start_web_server(
+    client: &Client,
+    runner: &CodeRunner,
+    port: u16,
+) -> Result<RunningServer, AgentError> {
+    let build: Output = runner.cargo_output(&["build"]).await?;
+    if !build.status.success() {
+        return Err(AgentError::CommandFailed {
+            command: "cargo build".to_string(),
+            stderr: String::from_utf8_lossy(&build.stderr).trim().to_string(),
+        });
+    }
+
+    let mut server: RunningServer = runner.spawn_cargo(&["run"], port).await?;
+    let url: String = format!("http://localhost:{}/", port);
+    let deadline: Instant = Instant::now() + SERVER_READY_TIMEOUT;
+
+    // any http answer counts, the sandbox forwarder accepts connections before the server does
+    loop {
+        if client.get(&url).timeout(SERVER_POLL_INTERVAL).send().await.is_ok() {
+            return Ok(server);
+        }
+        if server.has_exited() || Instant::now() >= deadline {
+            let _ = server.kill().await;
+            return Err(AgentError::CommandFailed {
+                command: "cargo run".to_string(),
+                stderr: format!(
+                    "web server did not answer on port {} within {}s",
+                    port,
+                    SERVER_READY_TIMEOUT.as_secs()
+                ),
+            });
+        }
+        time::sleep(SERVER_POLL_INTERVAL).await;
+    }
+}
+
 // get code template
-pub fn read_code_template_contents() -> String {
+pub fn read_code_template_contents() -> Result<String, AgentError> {
     let path: String = String::from(CODE_TEMPLATE_PATH);
-    fs::read_to_string(path).expect("Failed to read code template")
+    fs::read_to_string(path).map_err(|e| AgentError::io("Failed to read code template", e))
+}
+
+// get the backend project files
+pub fn read_backend_files() -> Result<FileMap, AgentError> {
+    read_workspace_files(Path::new(WEB_SERVER_PROJECT_PATH))
 }
 
-// get executable code
-pub fn read_exec_main_contents() -> String {
-    let path: String = String::from(EXEC_MAIN_PATH);
-    fs::read_to_string(path).expect("Failed to read code template")
+// save new or changed backend project files
+pub fn save_backend_files(files: &FileMap) -> Result<(), AgentError> {
+    write_file_map(Path::new(WEB_SERVER_PROJECT_PATH), files)
 }
 
-// save new backend code
-pub fn save_backend_code(contents: &String) {
-    let path: String = String::from(EXEC_MAIN_PATH);
-    fs::write(path, contents).expect("Failed to write main.rs")
+// save the container and run instructions at the top of the backend project
+pub fn save_packaging_files(files: &FileMap) -> Result<(), AgentError> {
+    write_packaging_files(Path::new(WEB_SERVER_PROJECT_PATH), files)
+}
+
+// get the backend Cargo.toml
+pub fn read_backend_manifest() -> Result<String, AgentError> {
+    let manifest_path = Path::new(WEB_SERVER_PROJECT_PATH).join("Cargo.toml");
+    fs::read_to_string(manifest_path).map_err(|e| AgentError::io("Failed to read backend Cargo.toml", e))
+}
+
+// whether the backend Cargo.toml declares a dependency on the crate
+pub fn manifest_has_dependency(manifest: &str, crate_name: &str) -> bool {
+    manifest.lines().any(|line| {
+        line.trim_start()
+            .strip_prefix(crate_name)
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    })
+}
+
+// name integration tests use to import the backend crate
+pub fn read_backend_crate_name() -> Result<String, AgentError> {
+    Ok(read_backend_package_name()?.replace('-', "_"))
+}
+
+// package name from the backend Cargo.toml, also the name of its binary
+pub fn read_backend_package_name() -> Result<String, AgentError> {
+    let manifest: String = read_backend_manifest()?;
+
+    let package_name = Regex::new(r#"(?m)^\s*name\s*=\s*"([^"]+)""#).unwrap();
+    let package_section: &str = manifest.split("[package]").nth(1).unwrap_or_default();
+    package_name
+        .captures(package_section)
+        .map(|captures| captures[1].to_string())
+        .ok_or_else(|| AgentError::Config("backend Cargo.toml has no package name".to_string()))
+}
+
+// commit the backend workspace after a step by an agent other than the backend developer,
+// history is a debugging aid so failures are only reported
+pub fn commit_backend_step(agent_position: &str, operation: &str) {
+    let _guard = WORKSPACE_COMMIT_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let committed = CodeHistory::init(Path::new(WEB_SERVER_PROJECT_PATH))
+        .and_then(|history| history.commit_step(agent_position, operation, 0));
+    if let Err(e) = committed {
+        print_agent_error(agent_position, &e);
+    }
 }
 
 // save the json api endpoint schema
-pub fn save_api_endpoints(contents: &String) {
+pub fn save_api_endpoints(contents: &String) -> Result<(), AgentError> {
     let path: String = String::from(API_SCHEMA_PATH);
-    fs::write(path, contents).expect("Failed to write api endpoints file")
+    fs::write(path, contents).map_err(|e| AgentError::io("Failed to write api endpoints file", e))
 }
 
 #[cfg(test)]
@@ -127,24 +576,38 @@ mod tests {
 
     #[test]
     fn tests_extending_ai_function() {
-        let extended_message: Message =
-            extend_ai_function(convert_user_input_to_goal, "dummy variable");
-        assert_eq!(extended_message.role, "system".to_string());
+        let (instruction, input) = extend_ai_function(convert_user_input_to_goal, "dummy variable");
+        assert_eq!(instruction.role, Role::System);
+        assert_eq!(input.role, Role::User);
+        assert_eq!(input.content, "dummy variable");
+    }
+
+    #[test]
+    fn tests_manifest_has_dependency() {
+        let manifest: &str = "[dependencies]\nargon2 = { version = \"0.5\", features = [\"std\"] }\nserde_json = \"1.0\"\n";
+        assert!(manifest_has_dependency(manifest, "argon2"));
+        assert!(manifest_has_dependency(manifest, "serde_json"));
+        assert!(!manifest_has_dependency(manifest, "serde"));
+        assert!(!manifest_has_dependency(manifest, "rusqlite"));
     }
 
     #[tokio::test]
     async fn tests_ai_task_request() {
         let ai_func_param = "Build me a webserver that makes pigs fly".to_string();
+        let mut agent: BasicAgent =
+            BasicTraits::new("Manages the project".to_string(), "Managing Agent".to_string());
 
         let res: String = ai_task_request(
             ai_func_param,
-            "Managing Agent",
+            &mut agent,
             "Defining user requirements",
             convert_user_input_to_goal,
         )
-        .await;
+        .await
+        .expect("Failed to call LLM");
 
         assert!(res.len() > 20);
+        assert_eq!(agent.get_memory().len(), 2);
         dbg!(res);
     }
 }
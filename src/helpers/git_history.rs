@@ -0,0 +1,256 @@
+use crate::models::general::errors::AgentError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// tags name the iteration they point at, eg green-4 built and tested-4 also passed its endpoints
+const GREEN_TAG_PREFIX: &str = "green-";
+const TESTED_TAG_PREFIX: &str = "tested-";
+const WORKSPACE_IGNORES: [&str; 3] = ["target/", ".sandbox/", ".candidates/"];
+
+// a tagged version of the code that worked
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenTag {
+    pub name: String,
+    pub iteration: u32,
+}
+
+// records every agent step in the web server workspace as a git commit
+#[derive(Debug, Clone)]
+pub struct CodeHistory {
+    root: PathBuf,
+}
+
+impl CodeHistory {
+    // open the workspace repository, creating it with an initial commit if needed
+    pub fn init(root: &Path) -> Result<Self, AgentError> {
+        let history = Self {
+            root: root.to_path_buf(),
+        };
+
+        if !root.join(".git").exists() {
+            history.git(&["init", "--quiet"])?;
+        }
+        history.ensure_ignores()?;
+
+        if history
+            .git(&["rev-parse", "--verify", "--quiet", "HEAD"])
+            .is_err()
+        {
+            history.git(&["add", "-A"])?;
+            history.git(&[
+                "commit",
+                "--quiet",
+                "--allow-empty",
+                "-m",
+                "Initial workspace",
+            ])?;
+        }
+
+        Ok(history)
+    }
+
+    // commit the workspace if anything changed, returning the new commit hash
+    pub fn commit_step(
+        &self,
+        agent_position: &str,
+        operation: &str,
+        bug_count: u8,
+    ) -> Result<Option<String>, AgentError> {
+        self.git(&["add", "-A"])?;
+        if self.git(&["diff", "--cached", "--quiet"]).is_ok() {
+            return Ok(None);
+        }
+
+        let message: String = format!("[{}] {} (bugs: {})", agent_position, operation, bug_count);
+        self.git(&["commit", "--quiet", "-m", &message])?;
+        self.head().map(Some)
+    }
+
+    // tag the current commit as the iteration that builds, moving the tag when the
+    // iteration changed again, eg after an auto-fix
+    pub fn tag_green(&self, iteration: u32) -> Result<String, AgentError> {
+        self.tag(GREEN_TAG_PREFIX, iteration)
+    }
+
+    // tag the current commit as the iteration whose endpoints passed
+    pub fn tag_tested(&self, iteration: u32) -> Result<String, AgentError> {
+        self.tag(TESTED_TAG_PREFIX, iteration)
+    }
+
+    // the last version that passed its endpoint tests, otherwise the last that built
+    pub fn last_green(&self) -> Result<Option<GreenTag>, AgentError> {
+        for prefix in [TESTED_TAG_PREFIX, GREEN_TAG_PREFIX] {
+            if let Some(tag) = self.tags(prefix)?.into_iter().next() {
+                return Ok(Some(tag));
+            }
+        }
+        Ok(None)
+    }
+
+    // restore the paths to the last green version as a new commit, keeping the failed
+    // attempts in history. files added since are removed, other paths are left alone
+    pub fn rollback_to_last_green(
+        &self,
+        agent_position: &str,
+        paths: &[String],
+    ) -> Result<Option<GreenTag>, AgentError> {
+        let tag: GreenTag = match self.last_green()? {
+            Some(tag) => tag,
+            None => return Ok(None),
+        };
+
+        let mut args: Vec<&str> = vec![
+            "restore",
+            "--source",
+            &tag.name,
+            "--staged",
+            "--worktree",
+            "--",
+        ];
+        if paths.is_empty() {
+            args.push(".");
+        }
+        args.extend(paths.iter().map(String::as_str));
+        self.git(&args)?;
+
+        let message: String = format!("[{}] roll back to {}", agent_position, tag.name);
+        self.git(&["commit", "--quiet", "--allow-empty", "-m", &message])?;
+        Ok(Some(tag))
+    }
+
+    pub fn head(&self) -> Result<String, AgentError> {
+        self.git_stdout(&["rev-parse", "HEAD"])
+    }
+
+    fn tag(&self, prefix: &str, iteration: u32) -> Result<String, AgentError> {
+        let tag: String = format!("{}{}", prefix, iteration);
+        self.git(&["tag", "--force", &tag])?;
+        Ok(tag)
+    }
+
+    // newest first
+    fn tags(&self, prefix: &str) -> Result<Vec<GreenTag>, AgentError> {
+        let pattern: String = format!("{}*", prefix);
+        let tags: String = self.git_stdout(&["tag", "--list", &pattern, "--sort=-v:refname"])?;
+        Ok(tags
+            .lines()
+            .filter_map(|name| {
+                let iteration: u32 = name.strip_prefix(prefix)?.parse().ok()?;
+                Some(GreenTag {
+                    name: name.to_string(),
+                    iteration,
+                })
+            })
+            .collect())
+    }
+
+    fn ensure_ignores(&self) -> Result<(), AgentError> {
+        let path: PathBuf = self.root.join(".gitignore");
+        let mut contents: String = fs::read_to_string(&path).unwrap_or_default();
+        let missing: Vec<&str> = WORKSPACE_IGNORES
+            .iter()
+            .filter(|ignore| !contents.lines().any(|line| line.trim() == **ignore))
+            .copied()
+            .collect();
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        for ignore in missing {
+            contents.push_str(ignore);
+            contents.push('\n');
+        }
+        fs::write(&path, contents)
+            .map_err(|e| AgentError::io("Failed to write workspace .gitignore", e))
+    }
+
+    fn git_stdout(&self, args: &[&str]) -> Result<String, AgentError> {
+        let output: Output = self.git(args)?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn git(&self, args: &[&str]) -> Result<Output, AgentError> {
+        let output: Output = Command::new("git")
+            .arg("-C")
+            .arg(&self.root)
+            .args([
+                "-c",
+                "user.name=auto_gippity",
+                "-c",
+                "user.email=auto_gippity@localhost",
+            ])
+            .args(args)
+            .output()
+            .map_err(|e| AgentError::io("Failed to run git", e))?;
+
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(AgentError::CommandFailed {
+                command: format!("git {}", args.join(" ")),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_commits_tags_and_rolls_back() {
+        let root: PathBuf =
+            std::env::temp_dir().join(format!("autogippity-history-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+
+        let history: CodeHistory = CodeHistory::init(&root).unwrap();
+        assert_eq!(history.tag_green(1).unwrap(), "green-1");
+        assert_eq!(history.tag_tested(1).unwrap(), "tested-1");
+
+        // a later build that never passed its endpoints is not preferred
+        fs::write(root.join("src/main.rs"), "fn main() { untested() }\n").unwrap();
+        history
+            .commit_step("Backend Developer", "print_improved_webserver_code", 0)
+            .unwrap();
+        assert_eq!(history.tag_green(2).unwrap(), "green-2");
+        assert_eq!(history.tag_green(2).unwrap(), "green-2");
+
+        fs::write(root.join("src/main.rs"), "fn main() { broken }\n").unwrap();
+        fs::write(root.join("src/extra.rs"), "broken\n").unwrap();
+        fs::create_dir_all(root.join("tests")).unwrap();
+        fs::write(root.join("tests/api.rs"), "#[test]\nfn works() {}\n").unwrap();
+        let commit = history
+            .commit_step("Backend Developer", "print_fixed_code", 1)
+            .unwrap();
+        assert!(commit.is_some());
+        assert!(history
+            .commit_step("Backend Developer", "print_fixed_code", 1)
+            .unwrap()
+            .is_none());
+
+        let restored = history
+            .rollback_to_last_green("Backend Developer", &["src".to_string()])
+            .unwrap();
+        assert_eq!(
+            restored,
+            Some(GreenTag {
+                name: "tested-1".to_string(),
+                iteration: 1,
+            })
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        assert!(!root.join("src/extra.rs").exists());
+        assert!(root.join("tests/api.rs").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,2 +1,15 @@
+pub mod api_docs;
+pub mod auth_scaffold;
+pub mod candidates;
 pub mod command_line;
+pub mod context_window;
 pub mod general;
+pub mod git_history;
+pub mod packaging;
+pub mod patch;
+pub mod persistence;
+pub mod quality_gates;
+pub mod sandbox;
+pub mod tools;
+pub mod transcript;
+pub mod workspace;
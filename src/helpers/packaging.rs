@@ -0,0 +1,322 @@
+use crate::helpers::persistence::StorageBackend;
+use crate::helpers::workspace::FileMap;
+use crate::models::agents::agent_traits::RouteObject;
+
+pub const DOCKERFILE: &str = "Dockerfile";
+pub const DOCKERIGNORE: &str = ".dockerignore";
+pub const COMPOSE_FILE: &str = "docker-compose.yml";
+pub const README_FILE: &str = "README.md";
+
+pub const HEALTH_ROUTE: &str = "/health";
+
+// base images are pinned to a major version so builds stay reproducible
+const BUILDER_IMAGE: &str = "rust:1-slim-bookworm";
+const RUNTIME_IMAGE: &str = "debian:bookworm-slim";
+
+const DOCKERFILE_INSTRUCTIONS: [&str; 18] = [
+    "FROM",
+    "RUN",
+    "CMD",
+    "LABEL",
+    "EXPOSE",
+    "ENV",
+    "ADD",
+    "COPY",
+    "ENTRYPOINT",
+    "VOLUME",
+    "USER",
+    "WORKDIR",
+    "ARG",
+    "ONBUILD",
+    "STOPSIGNAL",
+    "HEALTHCHECK",
+    "SHELL",
+    "MAINTAINER",
+];
+
+// what the packaged service looks like, gathered from the factsheet
+#[derive(Debug, Clone)]
+pub struct PackageSpec {
+    pub package_name: String,
+    pub project_description: String,
+    pub port: u16,
+    pub storage: Option<StorageBackend>,
+    pub has_auth: bool,
+    // config/ holds migrations and settings the binary reads at runtime
+    pub has_config: bool,
+    pub routes: Vec<RouteObject>,
+}
+
+impl PackageSpec {
+    // json and sqlite databases and auth accounts are files under data/
+    fn has_persistent_data(&self) -> bool {
+        self.has_auth
+            || matches!(
+                self.storage,
+                Some(StorageBackend::JsonFile) | Some(StorageBackend::Sqlite)
+            )
+    }
+}
+
+pub fn has_health_route(routes: &[RouteObject]) -> bool {
+    routes
+        .iter()
+        .any(|route| route.method.eq_ignore_ascii_case("get") && route.route == HEALTH_ROUTE)
+}
+
+pub fn render_packaging(spec: &PackageSpec) -> FileMap {
+    FileMap::from([
+        (DOCKERFILE.to_string(), render_dockerfile(spec)),
+        (
+            DOCKERIGNORE.to_string(),
+            "target/\n.git/\n.sandbox/\ndata/\n*.autogippity-staged\n".to_string(),
+        ),
+        (COMPOSE_FILE.to_string(), render_compose(spec)),
+        (README_FILE.to_string(), render_readme(spec)),
+    ])
+}
+
+fn render_dockerfile(spec: &PackageSpec) -> String {
+    // COPY fails the build when the source directory does not exist
+    let (copy_config, copy_built_config) = if spec.has_config {
+        (
+            "COPY config ./config\n",
+            "COPY --from=builder /app/config ./config\n",
+        )
+    } else {
+        ("", "")
+    };
+
+    format!(
+        "# build stage: dependencies are compiled in their own layer so code changes rebuild quickly
+FROM {builder} AS builder
+WORKDIR /app
+COPY Cargo.toml Cargo.lock* ./
+RUN mkdir src && echo 'fn main() {{}}' > src/main.rs && touch src/lib.rs \\
+    && cargo build --release && rm -rf src
+COPY src ./src
+{copy_config}RUN touch src/main.rs src/lib.rs && cargo build --release
+
+# runtime stage
+FROM {runtime}
+RUN apt-get update && apt-get install -y --no-install-recommends ca-certificates curl \\
+    && rm -rf /var/lib/apt/lists/* && useradd --create-home app
+WORKDIR /app
+COPY --from=builder /app/target/release/{bin} /usr/local/bin/{bin}
+{copy_built_config}RUN mkdir -p data && chown app data
+USER app
+ENV BIND_ADDRESS=0.0.0.0:{port}
+EXPOSE {port}
+HEALTHCHECK --interval=30s --timeout=3s --retries=3 CMD curl -fsS http://localhost:{port}{health} || exit 1
+CMD [\"{bin}\"]
+",
+        builder = BUILDER_IMAGE,
+        runtime = RUNTIME_IMAGE,
+        copy_config = copy_config,
+        copy_built_config = copy_built_config,
+        bin = spec.package_name,
+        port = spec.port,
+        health = HEALTH_ROUTE
+    )
+}
+
+fn render_compose(spec: &PackageSpec) -> String {
+    let mut compose: String = format!(
+        "services:
+  backend:
+    build: .
+    ports:
+      - \"{port}:{port}\"
+    environment:
+      BIND_ADDRESS: \"0.0.0.0:{port}\"
+    restart: unless-stopped
+",
+        port = spec.port
+    );
+
+    if spec.has_persistent_data() {
+        compose.push_str(
+            "    volumes:
+      - backend-data:/app/data
+
+volumes:
+  backend-data:
+",
+        );
+    }
+
+    compose
+}
+
+fn render_readme(spec: &PackageSpec) -> String {
+    let mut readme: String = format!(
+        "# {name}
+
+{description}
+
+## Running locally
+
+```sh
+cargo run
+```
+
+The server listens on `127.0.0.1:{port}`. Set `BIND_ADDRESS` to change it.
+
+## Running with Docker
+
+```sh
+docker compose up --build
+```
+
+The container listens on port {port} and reports its health from `GET {health}`.
+",
+        name = spec.package_name,
+        description = spec.project_description.trim(),
+        port = spec.port,
+        health = HEALTH_ROUTE
+    );
+
+    match spec.storage {
+        Some(StorageBackend::JsonFile) => readme.push_str(
+            "\n## Data\n\nRecords are stored in `data/database.json`, which compose keeps in the `backend-data` volume.\n",
+        ),
+        Some(StorageBackend::Sqlite) => readme.push_str(
+            "\n## Data\n\nRecords are stored in `data/database.sqlite`, which compose keeps in the `backend-data` volume. Migrations in `config/migrations` run at startup.\n",
+        ),
+        Some(StorageBackend::InMemory) => {
+            readme.push_str("\n## Data\n\nRecords are kept in memory and are lost when the server stops.\n")
+        }
+        None => {}
+    }
+
+    if spec.has_auth {
+        readme.push_str(
+            "\n## Authentication\n\nRegister with `POST /auth/register` and log in with `POST /auth/login` using `{\"username\": \"...\", \"password\": \"...\"}`. \
+Send the returned token as `Authorization: Bearer <token>` to routes that need a logged in user.\n",
+        );
+    }
+
+    if !spec.routes.is_empty() {
+        readme.push_str("\n## Endpoints\n\n| Method | Route |\n| --- | --- |\n");
+        for route in &spec.routes {
+            readme.push_str(&format!(
+                "| {} | `{}` |\n",
+                route.method.to_uppercase(),
+                route.route
+            ));
+        }
+    }
+
+    readme
+}
+
+// checks that need no external tools, used when hadolint is not installed
+pub fn lint_dockerfile(contents: &str) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+    let mut instructions: Vec<(usize, String)> = vec![];
+    let mut continued: bool = false;
+
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed: &str = line.trim();
+        let is_continuation: bool = continued;
+        continued = trimmed.ends_with('\\');
+        if is_continuation || trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let keyword: String = trimmed
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_uppercase();
+        if !DOCKERFILE_INSTRUCTIONS.contains(&keyword.as_str()) {
+            problems.push(format!(
+                "line {}: unknown instruction {}",
+                index + 1,
+                keyword
+            ));
+        }
+        if keyword == "FROM" && (trimmed.contains(":latest") || !trimmed.contains(':')) {
+            problems.push(format!(
+                "line {}: base image is not pinned to a version",
+                index + 1
+            ));
+        }
+        instructions.push((index + 1, keyword));
+    }
+
+    if instructions.first().map(|(_, keyword)| keyword.as_str()) != Some("FROM") {
+        problems.push("the first instruction must be FROM".to_string());
+    }
+    let final_stage: Vec<&String> = instructions
+        .iter()
+        .rev()
+        .take_while(|(_, keyword)| keyword != "FROM")
+        .map(|(_, keyword)| keyword)
+        .collect();
+    if !final_stage
+        .iter()
+        .any(|keyword| *keyword == "CMD" || *keyword == "ENTRYPOINT")
+    {
+        problems.push("the final stage has no CMD or ENTRYPOINT".to_string());
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(storage: Option<StorageBackend>) -> PackageSpec {
+        PackageSpec {
+            package_name: "contacts-api".to_string(),
+            project_description: "Stores contacts".to_string(),
+            port: 6678,
+            storage,
+            has_auth: false,
+            has_config: storage.is_some(),
+            routes: vec![RouteObject {
+                is_route_dynamic: "false".to_string(),
+                method: "get".to_string(),
+                request_body: serde_json::Value::Null,
+                response: serde_json::Value::Null,
+                route: "/contacts".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn tests_renders_lint_clean_packaging() {
+        let files: FileMap = render_packaging(&spec(Some(StorageBackend::Sqlite)));
+
+        assert!(lint_dockerfile(&files[DOCKERFILE]).is_empty());
+        assert!(files[DOCKERFILE].contains("COPY --from=builder /app/target/release/contacts-api"));
+        assert!(files[DOCKERFILE].contains("COPY --from=builder /app/config ./config\n"));
+        assert!(files[COMPOSE_FILE].contains("backend-data:/app/data"));
+        assert!(files[README_FILE].contains("| GET | `/contacts` |"));
+        assert!(!has_health_route(&spec(None).routes));
+
+        let in_memory: FileMap = render_packaging(&spec(Some(StorageBackend::InMemory)));
+        assert!(!in_memory[COMPOSE_FILE].contains("volumes"));
+
+        let without_config: FileMap = render_packaging(&spec(None));
+        assert!(!without_config[DOCKERFILE].contains("config"));
+        assert!(lint_dockerfile(&without_config[DOCKERFILE]).is_empty());
+    }
+
+    #[test]
+    fn tests_lints_dockerfile_problems() {
+        let problems: Vec<String> =
+            lint_dockerfile("RUN cargo build\nFROM rust:latest\nCOPYY . .\n");
+        assert_eq!(
+            problems,
+            vec![
+                "line 2: base image is not pinned to a version",
+                "line 3: unknown instruction COPYY",
+                "the first instruction must be FROM",
+                "the final stage has no CMD or ENTRYPOINT",
+            ]
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use crate::helpers::workspace::FileMap;
+use std::fmt;
+
+// how many context lines may be dropped from each end of a hunk when matching
+const MAX_FUZZ: usize = 2;
+
+// a single edit: replace old_lines in a file with new_lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replacement {
+    pub path: String,
+    pub old_lines: Vec<String>,
+    pub new_lines: Vec<String>,
+    // 1-based line the edit is expected near, from a unified diff hunk header
+    pub line_hint: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchRejection {
+    NoEdits,
+    Malformed(String),
+    FileNotFound(String),
+    EmptySearch(String),
+    ContextNotFound { path: String, first_line: String },
+    AmbiguousContext { path: String, matches: usize },
+}
+
+impl fmt::Display for PatchRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoEdits => write!(
+                f,
+                "response contained no search/replace blocks or diff hunks"
+            ),
+            Self::Malformed(reason) => write!(f, "malformed edit: {}", reason),
+            Self::FileNotFound(path) => {
+                write!(f, "{} does not exist and the edit is not a new file", path)
+            }
+            Self::EmptySearch(path) => write!(f, "empty SEARCH block for existing file {}", path),
+            Self::ContextNotFound { path, first_line } => {
+                write!(
+                    f,
+                    "could not find the lines starting '{}' in {}",
+                    first_line, path
+                )
+            }
+            Self::AmbiguousContext { path, matches } => write!(
+                f,
+                "the lines to replace match {} places in {}, include more context",
+                matches, path
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MatchLevel {
+    Exact,
+    IgnoreTrailingWhitespace,
+    IgnoreIndentation,
+}
+
+impl MatchLevel {
+    fn lines_equal(&self, a: &str, b: &str) -> bool {
+        match self {
+            Self::Exact => a == b,
+            Self::IgnoreTrailingWhitespace => a.trim_end() == b.trim_end(),
+            Self::IgnoreIndentation => a.trim() == b.trim(),
+        }
+    }
+}
+
+// parse either search/replace blocks or a unified diff against the files being patched
+pub fn parse_edits(response: &str, files: &FileMap) -> Result<Vec<Replacement>, PatchRejection> {
+    let edits: Vec<Replacement> = if response.contains("<<<<<<< SEARCH") {
+        parse_search_replace(response, files)?
+    } else if response.lines().any(|line| line.starts_with("@@")) {
+        parse_unified_diff(response)?
+    } else {
+        vec![]
+    };
+
+    if edits.is_empty() {
+        return Err(PatchRejection::NoEdits);
+    }
+    Ok(edits)
+}
+
+fn parse_search_replace(
+    response: &str,
+    files: &FileMap,
+) -> Result<Vec<Replacement>, PatchRejection> {
+    enum Section {
+        Outside,
+        Search,
+        Replace,
+    }
+
+    let mut edits: Vec<Replacement> = vec![];
+    let mut current_path: Option<String> = None;
+    let mut section = Section::Outside;
+    let mut old_lines: Vec<String> = vec![];
+    let mut new_lines: Vec<String> = vec![];
+
+    for line in response.lines() {
+        match section {
+            Section::Outside => {
+                let trimmed = line.trim();
+                if trimmed.starts_with("<<<<<<< SEARCH") {
+                    if current_path.is_none() {
+                        return Err(PatchRejection::Malformed(
+                            "SEARCH block without a FILE: line before it".to_string(),
+                        ));
+                    }
+                    section = Section::Search;
+                } else if let Some(path) = parse_path_line(trimmed, files) {
+                    current_path = Some(path);
+                }
+            }
+            Section::Search => {
+                if line.trim() == "=======" {
+                    section = Section::Replace;
+                } else {
+                    old_lines.push(line.to_string());
+                }
+            }
+            Section::Replace => {
+                if line.trim().starts_with(">>>>>>> REPLACE") {
+                    edits.push(Replacement {
+                        path: current_path.clone().unwrap_or_default(),
+                        old_lines: std::mem::take(&mut old_lines),
+                        new_lines: std::mem::take(&mut new_lines),
+                        line_hint: None,
+                    });
+                    section = Section::Outside;
+                } else {
+                    new_lines.push(line.to_string());
+                }
+            }
+        }
+    }
+
+    if !matches!(section, Section::Outside) {
+        return Err(PatchRejection::Malformed(
+            "unterminated SEARCH/REPLACE block".to_string(),
+        ));
+    }
+    Ok(edits)
+}
+
+// "FILE: src/main.rs", or a bare path on its own line naming a file being patched,
+// so urls or paths mentioned in prose do not move the next block to another file
+fn parse_path_line(line: &str, files: &FileMap) -> Option<String> {
+    match line
+        .strip_prefix("FILE:")
+        .or_else(|| line.strip_prefix("File:"))
+    {
+        Some(path) => {
+            let path = path.trim().trim_matches('`');
+            (!path.is_empty() && !path.contains(char::is_whitespace)).then(|| path.to_string())
+        }
+        None => {
+            let path = line.trim_matches('`');
+            files.contains_key(path).then(|| path.to_string())
+        }
+    }
+}
+
+fn parse_unified_diff(response: &str) -> Result<Vec<Replacement>, PatchRejection> {
+    let lines: Vec<&str> = response.lines().collect();
+    let mut edits: Vec<Replacement> = vec![];
+    let mut current_path: Option<String> = None;
+    let mut hunk: Option<Replacement> = None;
+
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let next = lines.get(index + 1).copied().unwrap_or("");
+
+        if line.starts_with("--- ") && next.starts_with("+++ ") {
+            edits.extend(hunk.take());
+            current_path = Some(diff_header_path(line, next));
+            index += 2;
+            continue;
+        }
+
+        if line.starts_with("@@") {
+            edits.extend(hunk.take());
+            let path = current_path.clone().ok_or_else(|| {
+                PatchRejection::Malformed("hunk without ---/+++ file headers".to_string())
+            })?;
+            hunk = Some(Replacement {
+                path,
+                old_lines: vec![],
+                new_lines: vec![],
+                line_hint: parse_hunk_start(line),
+            });
+        } else if let Some(current) = hunk.as_mut() {
+            if line.starts_with("diff --git") {
+                edits.extend(hunk.take());
+            } else if let Some(removed) = line.strip_prefix('-') {
+                current.old_lines.push(removed.to_string());
+            } else if let Some(added) = line.strip_prefix('+') {
+                current.new_lines.push(added.to_string());
+            } else if line.starts_with('\\') {
+                // "\ No newline at end of file"
+            } else {
+                let context = line.strip_prefix(' ').unwrap_or(line).to_string();
+                current.old_lines.push(context.clone());
+                current.new_lines.push(context);
+            }
+        }
+
+        index += 1;
+    }
+    edits.extend(hunk.take());
+
+    // trailing blank context lines are usually an artifact of the response formatting
+    for edit in edits.iter_mut() {
+        while edit.old_lines.last().is_some_and(|l| l.trim().is_empty())
+            && edit.new_lines.last().is_some_and(|l| l.trim().is_empty())
+        {
+            edit.old_lines.pop();
+            edit.new_lines.pop();
+        }
+    }
+
+    Ok(edits)
+}
+
+// new files use --- /dev/null so take the path from whichever side is real
+fn diff_header_path(old_header: &str, new_header: &str) -> String {
+    let clean = |header: &str, prefix: &str| -> String {
+        let path = header[4..].split('\t').next().unwrap_or("").trim();
+        path.strip_prefix(prefix).unwrap_or(path).to_string()
+    };
+
+    let new_path = clean(new_header, "b/");
+    if new_path != "/dev/null" {
+        new_path
+    } else {
+        clean(old_header, "a/")
+    }
+}
+
+// "@@ -12,7 +12,8 @@" -> 12
+fn parse_hunk_start(header: &str) -> Option<usize> {
+    let old_range = header.split_whitespace().nth(1)?.strip_prefix('-')?;
+    old_range.split(',').next()?.parse().ok()
+}
+
+// apply every edit, returning the full contents of each changed file
+pub fn apply_edits(files: &FileMap, edits: &[Replacement]) -> Result<FileMap, Vec<PatchRejection>> {
+    let mut changed: FileMap = FileMap::new();
+    let mut rejections: Vec<PatchRejection> = vec![];
+
+    for edit in edits {
+        let current: Option<String> = changed
+            .get(&edit.path)
+            .or_else(|| files.get(&edit.path))
+            .cloned();
+
+        match apply_replacement(current.as_deref(), edit) {
+            Ok(contents) => {
+                changed.insert(edit.path.clone(), contents);
+            }
+            Err(rejection) => rejections.push(rejection),
+        }
+    }
+
+    if rejections.is_empty() {
+        Ok(changed)
+    } else {
+        Err(rejections)
+    }
+}
+
+fn apply_replacement(current: Option<&str>, edit: &Replacement) -> Result<String, PatchRejection> {
+    let contents: &str = match current {
+        Some(contents) => contents,
+        None if edit.old_lines.is_empty() => return Ok(join_lines(&edit.new_lines, true)),
+        None => return Err(PatchRejection::FileNotFound(edit.path.clone())),
+    };
+
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    let trailing_newline: bool = contents.ends_with('\n') || contents.is_empty();
+
+    if edit.old_lines.is_empty() {
+        // pure insertion, only possible when a diff says where
+        let hint = edit
+            .line_hint
+            .ok_or_else(|| PatchRejection::EmptySearch(edit.path.clone()))?;
+        let at = hint.min(lines.len());
+        lines.splice(at..at, edit.new_lines.iter().cloned());
+        return Ok(join_lines(&lines, trailing_newline));
+    }
+
+    let (start, old_len, new_lines) = locate(&lines, edit)?;
+    lines.splice(start..start + old_len, new_lines);
+    Ok(join_lines(&lines, trailing_newline))
+}
+
+// find where the edit applies, loosening whitespace and then context until it matches once
+fn locate(
+    lines: &[String],
+    edit: &Replacement,
+) -> Result<(usize, usize, Vec<String>), PatchRejection> {
+    let (leading_context, trailing_context) = shared_context(edit);
+
+    for fuzz in 0..=MAX_FUZZ {
+        let drop_front = fuzz.min(leading_context);
+        let drop_back = fuzz.min(trailing_context);
+        if fuzz > 0 && drop_front < fuzz && drop_back < fuzz {
+            break;
+        }
+
+        let old: &[String] = &edit.old_lines[drop_front..edit.old_lines.len() - drop_back];
+        let new: &[String] = &edit.new_lines[drop_front..edit.new_lines.len() - drop_back];
+        if old.is_empty() {
+            break;
+        }
+
+        for level in [
+            MatchLevel::Exact,
+            MatchLevel::IgnoreTrailingWhitespace,
+            MatchLevel::IgnoreIndentation,
+        ] {
+            let matches: Vec<usize> = find_matches(lines, old, level);
+            let chosen: Option<usize> = match (matches.len(), edit.line_hint) {
+                (0, _) => None,
+                (1, _) => Some(matches[0]),
+                (_, Some(hint)) => closest_to(&matches, (hint + drop_front).saturating_sub(1)),
+                (count, None) => {
+                    return Err(PatchRejection::AmbiguousContext {
+                        path: edit.path.clone(),
+                        matches: count,
+                    })
+                }
+            };
+
+            if let Some(start) = chosen {
+                return Ok((start, old.len(), new.to_vec()));
+            }
+        }
+    }
+
+    Err(PatchRejection::ContextNotFound {
+        path: edit.path.clone(),
+        first_line: edit
+            .old_lines
+            .iter()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line.trim().to_string())
+            .unwrap_or_default(),
+    })
+}
+
+// number of identical lines at the start and end of old and new, ie unchanged context
+fn shared_context(edit: &Replacement) -> (usize, usize) {
+    let leading = edit
+        .old_lines
+        .iter()
+        .zip(edit.new_lines.iter())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let max_trailing = edit.old_lines.len().min(edit.new_lines.len()) - leading;
+    let trailing = edit
+        .old_lines
+        .iter()
+        .rev()
+        .zip(edit.new_lines.iter().rev())
+        .take(max_trailing)
+        .take_while(|(old, new)| old == new)
+        .count();
+    (leading, trailing)
+}
+
+fn find_matches(lines: &[String], old: &[String], level: MatchLevel) -> Vec<usize> {
+    if old.len() > lines.len() {
+        return vec![];
+    }
+    (0..=lines.len() - old.len())
+        .filter(|&start| {
+            old.iter()
+                .enumerate()
+                .all(|(offset, line)| level.lines_equal(&lines[start + offset], line))
+        })
+        .collect()
+}
+
+fn closest_to(matches: &[usize], target: usize) -> Option<usize> {
+    matches
+        .iter()
+        .copied()
+        .min_by_key(|&start| (start as isize - target as isize).unsigned_abs())
+}
+
+fn join_lines(lines: &[String], trailing_newline: bool) -> String {
+    let mut joined: String = lines.join("\n");
+    if trailing_newline && !joined.is_empty() {
+        joined.push('\n');
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn main_rs() -> FileMap {
+        let mut files = FileMap::new();
+        files.insert(
+            "src/main.rs".to_string(),
+            "use actix_web::web;\n\nfn routes() {\n    let a = 1;\n    let b = 2;\n}\n\nfn main() {\n    routes();\n}\n"
+                .to_string(),
+        );
+        files
+    }
+
+    #[test]
+    fn tests_applies_search_replace_blocks() {
+        let response = "FILE: src/main.rs\n<<<<<<< SEARCH\n    let a = 1;\n=======\n    let a = 10;\n>>>>>>> REPLACE\n\nFILE: src/models.rs\n<<<<<<< SEARCH\n=======\npub struct Item;\n>>>>>>> REPLACE\n";
+        let edits = parse_edits(response, &main_rs()).unwrap();
+        assert_eq!(edits.len(), 2);
+
+        let changed = apply_edits(&main_rs(), &edits).unwrap();
+        assert!(changed["src/main.rs"].contains("let a = 10;"));
+        assert!(changed["src/main.rs"].contains("let b = 2;"));
+        assert_eq!(changed["src/models.rs"], "pub struct Item;\n");
+    }
+
+    #[test]
+    fn tests_applies_unified_diff_with_fuzzy_context() {
+        // context has drifted indentation and one stale context line
+        let response = "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -3,5 +3,5 @@\n fn routes() {\n   let a = 1;\n-    let b = 2;\n+    let b = 20;\n }\n-stale context\n+stale context\n";
+        let edits = parse_edits(response, &main_rs()).unwrap();
+
+        let changed = apply_edits(&main_rs(), &edits).unwrap();
+        assert!(changed["src/main.rs"].contains("    let b = 20;\n"));
+        assert!(changed["src/main.rs"].ends_with("routes();\n}\n"));
+    }
+
+    #[test]
+    fn tests_rejects_edits_with_reasons() {
+        let missing = "FILE: src/main.rs\n<<<<<<< SEARCH\nfn nowhere() {}\n=======\nfn somewhere() {}\n>>>>>>> REPLACE\n";
+        let rejections =
+            apply_edits(&main_rs(), &parse_edits(missing, &main_rs()).unwrap()).unwrap_err();
+        assert_eq!(
+            rejections[0],
+            PatchRejection::ContextNotFound {
+                path: "src/main.rs".to_string(),
+                first_line: "fn nowhere() {}".to_string(),
+            }
+        );
+
+        let ambiguous = "FILE: src/main.rs\n<<<<<<< SEARCH\n}\n=======\n};\n>>>>>>> REPLACE\n";
+        let rejections =
+            apply_edits(&main_rs(), &parse_edits(ambiguous, &main_rs()).unwrap()).unwrap_err();
+        assert!(matches!(
+            rejections[0],
+            PatchRejection::AmbiguousContext { matches: 2, .. }
+        ));
+
+        assert_eq!(
+            parse_edits("fn main() {}", &main_rs()).unwrap_err(),
+            PatchRejection::NoEdits
+        );
+    }
+
+    #[test]
+    fn tests_reads_paths_only_from_file_lines_or_known_files() {
+        let response = "`src/main.rs`\n<<<<<<< SEARCH\n    let a = 1;\n=======\n    let a = 10;\n>>>>>>> REPLACE\nhttps://example.com/api.json\ndocs/API.md\n<<<<<<< SEARCH\n    let b = 2;\n=======\n    let b = 20;\n>>>>>>> REPLACE\n";
+        let edits = parse_edits(response, &main_rs()).unwrap();
+        assert!(edits.iter().all(|edit| edit.path == "src/main.rs"));
+
+        let changed = apply_edits(&main_rs(), &edits).unwrap();
+        assert!(changed["src/main.rs"].contains("let a = 10;"));
+        assert!(changed["src/main.rs"].contains("let b = 20;"));
+
+        let unnamed = "src/models.rs\n<<<<<<< SEARCH\n=======\npub struct Item;\n>>>>>>> REPLACE\n";
+        assert!(matches!(
+            parse_edits(unnamed, &main_rs()),
+            Err(PatchRejection::Malformed(_))
+        ));
+    }
+}
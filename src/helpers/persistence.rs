@@ -0,0 +1,594 @@
+use crate::helpers::workspace::FileMap;
+use crate::models::agents::agent_traits::{Entity, FieldType};
+use crate::models::general::errors::AgentError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+
+pub const STORAGE_ENV: &str = "AUTO_GIPPITY_STORAGE";
+
+// files of the generated data layer inside the backend workspace
+pub const REPOSITORY_FILE: &str = "src/repository.rs";
+pub const SCHEMA_FILE: &str = "config/data_model.json";
+const SQL_MIGRATION_FILE: &str = "config/migrations/0001_init.sql";
+const JSON_MIGRATION_FILE: &str = "config/migrations/0001_init.json";
+
+// rust keywords would not compile as struct fields, id collides with the generated id
+const RESERVED_NAMES: [&str; 52] = [
+    "id", "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    #[default]
+    JsonFile,
+    Sqlite,
+    InMemory,
+}
+
+impl StorageBackend {
+    // read the storage backend for this run from the environment
+    pub fn from_env() -> Result<Self, AgentError> {
+        match env::var(STORAGE_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    // crate the backend manifest must already depend on
+    pub fn required_crate(&self) -> Option<&'static str> {
+        match self {
+            Self::Sqlite => Some("rusqlite"),
+            Self::JsonFile | Self::InMemory => None,
+        }
+    }
+}
+
+impl std::str::FromStr for StorageBackend {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "json" | "json_file" => Ok(Self::JsonFile),
+            "sqlite" => Ok(Self::Sqlite),
+            "memory" | "in_memory" => Ok(Self::InMemory),
+            other => Err(AgentError::Config(format!(
+                "unknown storage backend '{}' in {}",
+                other, STORAGE_ENV
+            ))),
+        }
+    }
+}
+
+// entity and field names end up as rust identifiers and table names
+pub fn validate_entities(entities: &[Entity]) -> Result<(), AgentError> {
+    let invalid =
+        |reason: String| AgentError::LlmResponse(format!("invalid data model: {}", reason));
+    let is_identifier = |name: &str| {
+        name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            && !RESERVED_NAMES.contains(&name)
+    };
+
+    let mut entity_names: HashSet<&str> = HashSet::new();
+    for entity in entities {
+        if !is_identifier(&entity.name) || !entity_names.insert(&entity.name) {
+            return Err(invalid(format!(
+                "bad or duplicate entity name '{}'",
+                entity.name
+            )));
+        }
+        if entity.fields.is_empty() {
+            return Err(invalid(format!("{} has no fields", entity.name)));
+        }
+
+        let mut field_names: HashSet<&str> = HashSet::new();
+        for field in &entity.fields {
+            if !is_identifier(&field.name) || !field_names.insert(&field.name) {
+                return Err(invalid(format!(
+                    "bad or duplicate field name '{}.{}'",
+                    entity.name, field.name
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// schema, migrations and repository module for the chosen storage backend
+pub fn render_data_layer(storage: StorageBackend, entities: &[Entity]) -> FileMap {
+    let mut files: FileMap = FileMap::new();
+    files.insert(
+        SCHEMA_FILE.to_string(),
+        serde_json::to_string_pretty(entities).unwrap_or_default(),
+    );
+
+    let runtime: [&str; 2] = match storage {
+        StorageBackend::Sqlite => {
+            files.insert(
+                SQL_MIGRATION_FILE.to_string(),
+                render_sql_migration(entities),
+            );
+            [SQLITE_RUNTIME, ""]
+        }
+        StorageBackend::JsonFile => {
+            files.insert(
+                JSON_MIGRATION_FILE.to_string(),
+                render_json_migration(entities),
+            );
+            [TABLE_STORE_RUNTIME, JSON_FILE_RUNTIME]
+        }
+        StorageBackend::InMemory => [TABLE_STORE_RUNTIME, IN_MEMORY_RUNTIME],
+    };
+
+    let mut repository: String = format!(
+        "// generated by the database designer for {:?} storage, call repository::connect() once at startup\n",
+        storage
+    );
+    repository.push_str(COMMON_RUNTIME);
+    for entity in entities {
+        repository.push_str(&render_entity(entity));
+    }
+    repository.push_str(&runtime.concat());
+    files.insert(REPOSITORY_FILE.to_string(), repository);
+
+    files
+}
+
+fn render_sql_migration(entities: &[Entity]) -> String {
+    entities
+        .iter()
+        .map(|entity| {
+            let columns: Vec<String> = entity
+                .fields
+                .iter()
+                .map(|field| {
+                    let sql_type: &str = match field.field_type {
+                        FieldType::String => "TEXT",
+                        FieldType::Integer | FieldType::Boolean => "INTEGER",
+                        FieldType::Float => "REAL",
+                    };
+                    let null: &str = if field.optional { "" } else { " NOT NULL" };
+                    format!(",\n    \"{}\" {}{}", field.name, sql_type, null)
+                })
+                .collect();
+            format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (\n    \"id\" INTEGER PRIMARY KEY AUTOINCREMENT{}\n);\n",
+                entity.name,
+                columns.concat()
+            )
+        })
+        .collect()
+}
+
+fn render_json_migration(entities: &[Entity]) -> String {
+    let tables: serde_json::Map<String, serde_json::Value> = entities
+        .iter()
+        .map(|entity| (entity.name.clone(), serde_json::Value::Array(vec![])))
+        .collect();
+    serde_json::to_string_pretty(&tables).unwrap_or_default()
+}
+
+fn render_entity(entity: &Entity) -> String {
+    let struct_name: String = entity
+        .name
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+
+    let mut fields: String = String::new();
+    let mut columns: Vec<String> = vec![];
+    for field in &entity.fields {
+        let (rust_type, column_type) = match field.field_type {
+            FieldType::String => ("String", "string"),
+            FieldType::Integer => ("i64", "integer"),
+            FieldType::Float => ("f64", "float"),
+            FieldType::Boolean => ("bool", "boolean"),
+        };
+        let rust_type: String = if field.optional {
+            format!("Option<{}>", rust_type)
+        } else {
+            rust_type.to_string()
+        };
+        fields.push_str(&format!("    pub {}: {},\n", field.name, rust_type));
+        columns.push(format!("(\"{}\", \"{}\")", field.name, column_type));
+    }
+
+    format!(
+        "
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct {name} {{
+    #[serde(default)]
+    pub id: u64,
+{fields}}}
+
+impl Record for {name} {{
+    const TABLE: &'static str = \"{table}\";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[{columns}];
+
+    fn id(&self) -> u64 {{
+        self.id
+    }}
+
+    fn set_id(&mut self, id: u64) {{
+        self.id = id;
+    }}
+}}
+",
+        name = struct_name,
+        fields = fields,
+        table = entity.name,
+        columns = columns.join(", ")
+    )
+}
+
+const COMMON_RUNTIME: &str = r#"#![allow(dead_code)]
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    Storage(String),
+    Serialization(serde_json::Error),
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Storage(msg) => write!(f, "storage error: {}", msg),
+            Self::Serialization(e) => write!(f, "serialization error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<serde_json::Error> for RepositoryError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serialization(e)
+    }
+}
+
+// implemented for every entity so the repository can store it
+pub trait Record: Clone + Serialize + DeserializeOwned {
+    const TABLE: &'static str;
+    // (field name, field type) of every field except the id
+    const COLUMNS: &'static [(&'static str, &'static str)];
+    fn id(&self) -> u64;
+    fn set_id(&mut self, id: u64);
+}
+"#;
+
+// json file and in memory storage share one table store
+const TABLE_STORE_RUNTIME: &str = r#"
+type Tables = std::collections::BTreeMap<String, Vec<serde_json::Value>>;
+
+pub struct Repository {
+    path: Option<std::path::PathBuf>,
+    tables: Mutex<Tables>,
+}
+
+impl Repository {
+    pub fn in_memory() -> Result<Self, RepositoryError> {
+        Ok(Self {
+            path: None,
+            tables: Mutex::new(Tables::new()),
+        })
+    }
+
+    pub fn create<T: Record>(&self, mut record: T) -> Result<T, RepositoryError> {
+        let mut tables = self.lock()?;
+        let rows = tables.entry(T::TABLE.to_string()).or_default();
+        let next_id: u64 = rows.iter().filter_map(|row| row["id"].as_u64()).max().unwrap_or(0) + 1;
+        record.set_id(next_id);
+        rows.push(serde_json::to_value(&record)?);
+        self.persist(&tables)?;
+        Ok(record)
+    }
+
+    pub fn get<T: Record>(&self, id: u64) -> Result<Option<T>, RepositoryError> {
+        Ok(self.list::<T>()?.into_iter().find(|record| record.id() == id))
+    }
+
+    pub fn list<T: Record>(&self) -> Result<Vec<T>, RepositoryError> {
+        let tables = self.lock()?;
+        let rows: Vec<serde_json::Value> = tables.get(T::TABLE).cloned().unwrap_or_default();
+        rows.into_iter()
+            .map(|row| serde_json::from_value(row).map_err(RepositoryError::from))
+            .collect()
+    }
+
+    pub fn update<T: Record>(&self, id: u64, mut record: T) -> Result<Option<T>, RepositoryError> {
+        let mut tables = self.lock()?;
+        let rows = tables.entry(T::TABLE.to_string()).or_default();
+        let position = match rows.iter().position(|row| row["id"].as_u64() == Some(id)) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        record.set_id(id);
+        rows[position] = serde_json::to_value(&record)?;
+        self.persist(&tables)?;
+        Ok(Some(record))
+    }
+
+    pub fn delete<T: Record>(&self, id: u64) -> Result<bool, RepositoryError> {
+        let mut tables = self.lock()?;
+        let rows = tables.entry(T::TABLE.to_string()).or_default();
+        let count: usize = rows.len();
+        rows.retain(|row| row["id"].as_u64() != Some(id));
+        let deleted: bool = rows.len() != count;
+        if deleted {
+            self.persist(&tables)?;
+        }
+        Ok(deleted)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Tables>, RepositoryError> {
+        self.tables
+            .lock()
+            .map_err(|_| RepositoryError::Storage("repository lock poisoned".to_string()))
+    }
+
+    fn persist(&self, tables: &Tables) -> Result<(), RepositoryError> {
+        match &self.path {
+            Some(path) => std::fs::write(path, serde_json::to_string_pretty(tables)?)
+                .map_err(|e| RepositoryError::Storage(e.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+"#;
+
+const IN_MEMORY_RUNTIME: &str = r#"
+pub fn connect() -> Result<Repository, RepositoryError> {
+    Repository::in_memory()
+}
+"#;
+
+const JSON_FILE_RUNTIME: &str = r#"
+pub const DATABASE_PATH: &str = "data/database.json";
+const INITIAL_TABLES: &str = include_str!("../config/migrations/0001_init.json");
+
+// open the json database, creating it from the initial migration if needed
+pub fn connect() -> Result<Repository, RepositoryError> {
+    let storage_error = |e: std::io::Error| RepositoryError::Storage(e.to_string());
+    let path = std::path::Path::new(DATABASE_PATH);
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+        std::fs::write(path, INITIAL_TABLES).map_err(storage_error)?;
+    }
+
+    let contents: String = std::fs::read_to_string(path).map_err(storage_error)?;
+    let tables: Tables = serde_json::from_str(&contents)?;
+    Ok(Repository {
+        path: Some(path.to_path_buf()),
+        tables: Mutex::new(tables),
+    })
+}
+"#;
+
+const SQLITE_RUNTIME: &str = r#"
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::Connection;
+
+pub const DATABASE_PATH: &str = "data/database.sqlite";
+const MIGRATIONS: &str = include_str!("../config/migrations/0001_init.sql");
+
+impl From<rusqlite::Error> for RepositoryError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Storage(e.to_string())
+    }
+}
+
+pub struct Repository {
+    connection: Mutex<Connection>,
+}
+
+// open the sqlite database and apply the migrations
+pub fn connect() -> Result<Repository, RepositoryError> {
+    if let Some(parent) = std::path::Path::new(DATABASE_PATH).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| RepositoryError::Storage(e.to_string()))?;
+    }
+    Repository::from_connection(Connection::open(DATABASE_PATH)?)
+}
+
+impl Repository {
+    pub fn in_memory() -> Result<Self, RepositoryError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self, RepositoryError> {
+        connection.execute_batch(MIGRATIONS)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn create<T: Record>(&self, mut record: T) -> Result<T, RepositoryError> {
+        let names: Vec<String> = T::COLUMNS.iter().map(|(name, _)| quoted(name)).collect();
+        let placeholders: Vec<&str> = vec!["?"; names.len()];
+        let sql: String = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quoted(T::TABLE),
+            names.join(", "),
+            placeholders.join(", ")
+        );
+
+        let connection = self.lock()?;
+        connection.execute(&sql, rusqlite::params_from_iter(column_values(&record)?))?;
+        record.set_id(connection.last_insert_rowid() as u64);
+        Ok(record)
+    }
+
+    pub fn get<T: Record>(&self, id: u64) -> Result<Option<T>, RepositoryError> {
+        Ok(self.query::<T>("WHERE \"id\" = ?1", Some(id))?.into_iter().next())
+    }
+
+    pub fn list<T: Record>(&self) -> Result<Vec<T>, RepositoryError> {
+        self.query::<T>("ORDER BY \"id\"", None)
+    }
+
+    pub fn update<T: Record>(&self, id: u64, mut record: T) -> Result<Option<T>, RepositoryError> {
+        let assignments: Vec<String> = T::COLUMNS.iter().map(|(name, _)| format!("{} = ?", quoted(name))).collect();
+        let sql: String = format!("UPDATE {} SET {} WHERE \"id\" = ?", quoted(T::TABLE), assignments.join(", "));
+
+        let mut values: Vec<Value> = column_values(&record)?;
+        values.push(Value::Integer(id as i64));
+        let changed: usize = self.lock()?.execute(&sql, rusqlite::params_from_iter(values))?;
+        if changed == 0 {
+            return Ok(None);
+        }
+        record.set_id(id);
+        Ok(Some(record))
+    }
+
+    pub fn delete<T: Record>(&self, id: u64) -> Result<bool, RepositoryError> {
+        let sql: String = format!("DELETE FROM {} WHERE \"id\" = ?1", quoted(T::TABLE));
+        let changed: usize = self.lock()?.execute(&sql, [id as i64])?;
+        Ok(changed > 0)
+    }
+
+    fn query<T: Record>(&self, clause: &str, id: Option<u64>) -> Result<Vec<T>, RepositoryError> {
+        let names: Vec<String> = T::COLUMNS.iter().map(|(name, _)| quoted(name)).collect();
+        let sql: String = format!("SELECT \"id\", {} FROM {} {}", names.join(", "), quoted(T::TABLE), clause);
+
+        let connection = self.lock()?;
+        let mut statement = connection.prepare(&sql)?;
+        let params: Vec<i64> = id.map(|id| id as i64).into_iter().collect();
+        let rows = statement.query_map(rusqlite::params_from_iter(params), |row| {
+            let mut object = serde_json::Map::new();
+            object.insert("id".to_string(), serde_json::json!(row.get::<_, i64>(0)?));
+            for (index, (name, kind)) in T::COLUMNS.iter().enumerate() {
+                let value: serde_json::Value = match row.get_ref(index + 1)? {
+                    ValueRef::Null | ValueRef::Blob(_) => serde_json::Value::Null,
+                    ValueRef::Integer(n) if *kind == "boolean" => serde_json::json!(n != 0),
+                    ValueRef::Integer(n) => serde_json::json!(n),
+                    ValueRef::Real(n) => serde_json::json!(n),
+                    ValueRef::Text(text) => serde_json::json!(String::from_utf8_lossy(text)),
+                };
+                object.insert(name.to_string(), value);
+            }
+            Ok(serde_json::Value::Object(object))
+        })?;
+
+        let mut records: Vec<T> = vec![];
+        for row in rows {
+            records.push(serde_json::from_value(row?)?);
+        }
+        Ok(records)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, RepositoryError> {
+        self.connection
+            .lock()
+            .map_err(|_| RepositoryError::Storage("repository lock poisoned".to_string()))
+    }
+}
+
+// table and column names may be sql keywords such as order or group
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn column_values<T: Record>(record: &T) -> Result<Vec<Value>, RepositoryError> {
+    let json: serde_json::Value = serde_json::to_value(record)?;
+    Ok(T::COLUMNS
+        .iter()
+        .map(|(name, _)| match &json[*name] {
+            serde_json::Value::Bool(b) => Value::Integer(*b as i64),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(n) => Value::Integer(n),
+                None => Value::Real(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(s) => Value::Text(s.clone()),
+            serde_json::Value::Null => Value::Null,
+            other => Value::Text(other.to_string()),
+        })
+        .collect())
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::workspace::validate_file_map;
+    use crate::models::agents::agent_traits::EntityField;
+
+    fn contact() -> Entity {
+        Entity {
+            name: "contact".to_string(),
+            fields: vec![
+                EntityField {
+                    name: "full_name".to_string(),
+                    field_type: FieldType::String,
+                    optional: false,
+                },
+                EntityField {
+                    name: "is_favourite".to_string(),
+                    field_type: FieldType::Boolean,
+                    optional: true,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn tests_renders_data_layer_per_backend() {
+        let entities: Vec<Entity> = vec![contact()];
+        assert!(validate_entities(&entities).is_ok());
+
+        let sqlite: FileMap = render_data_layer(StorageBackend::Sqlite, &entities);
+        assert_eq!(
+            sqlite[SQL_MIGRATION_FILE],
+            "CREATE TABLE IF NOT EXISTS \"contact\" (\n    \"id\" INTEGER PRIMARY KEY AUTOINCREMENT,\n    \"full_name\" TEXT NOT NULL,\n    \"is_favourite\" INTEGER\n);\n"
+        );
+        assert!(sqlite[REPOSITORY_FILE].contains("SELECT \\\"id\\\", {} FROM {} {}"));
+        assert!(sqlite[REPOSITORY_FILE].contains("pub struct Contact {"));
+        assert!(sqlite[REPOSITORY_FILE].contains("pub is_favourite: Option<bool>,"));
+
+        let json: FileMap = render_data_layer(StorageBackend::JsonFile, &entities);
+        assert_eq!(json[JSON_MIGRATION_FILE], "{\n  \"contact\": []\n}");
+        assert!(validate_file_map(&json).is_ok());
+
+        let memory: FileMap = render_data_layer(StorageBackend::InMemory, &entities);
+        assert_eq!(memory.len(), 2);
+        assert!(
+            memory[REPOSITORY_FILE].contains("pub fn in_memory() -> Result<Self, RepositoryError>")
+        );
+    }
+
+    #[test]
+    fn tests_rejects_unusable_entity_names() {
+        let mut entity: Entity = contact();
+        entity.fields[0].name = "type".to_string();
+        assert!(validate_entities(&[entity]).is_err());
+
+        let mut entity: Entity = contact();
+        entity.fields[0].name = "async".to_string();
+        assert!(validate_entities(&[entity]).is_err());
+
+        let mut entity: Entity = contact();
+        entity.name = "Contact Card".to_string();
+        assert!(validate_entities(&[entity]).is_err());
+
+        assert!(validate_entities(&[contact(), contact()]).is_err());
+    }
+}
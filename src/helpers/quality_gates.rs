@@ -0,0 +1,455 @@
+use crate::helpers::sandbox::CodeRunner;
+use crate::models::general::errors::AgentError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fmt;
+use std::process::Output;
+
+pub const GATES_ENV: &str = "AUTO_GIPPITY_GATES";
+
+// keeps prompts and terminal output readable when a gate produces a flood of findings
+const MAX_FINDINGS_PER_GATE: usize = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Gate {
+    Check,
+    Clippy,
+    Test,
+    Fmt,
+}
+
+impl Gate {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Check => "check",
+            Self::Clippy => "clippy",
+            Self::Test => "test",
+            Self::Fmt => "fmt",
+        }
+    }
+
+    fn cargo_args(&self) -> Vec<&'static str> {
+        match self {
+            Self::Check => vec!["check", "--message-format=json"],
+            Self::Clippy => vec!["clippy", "--message-format=json", "--", "-D", "warnings"],
+            Self::Test => vec!["test", "--message-format=json"],
+            Self::Fmt => vec!["fmt", "--check"],
+        }
+    }
+
+    // gates that can repair their own findings
+    fn fix_args(&self) -> Option<Vec<&'static str>> {
+        match self {
+            Self::Clippy => Some(vec!["clippy", "--fix", "--allow-dirty", "--allow-no-vcs"]),
+            Self::Fmt => Some(vec!["fmt"]),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for Gate {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "check" => Ok(Self::Check),
+            "clippy" => Ok(Self::Clippy),
+            "test" => Ok(Self::Test),
+            "fmt" => Ok(Self::Fmt),
+            other => Err(AgentError::Config(format!(
+                "unknown gate '{}' in {}",
+                other, GATES_ENV
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GatePolicy {
+    // failures go back to the developer as bugs
+    Blocking,
+    // findings are passed on as improvement hints
+    Advisory,
+    // the gate fixes what it can, remaining findings are advisory
+    AutoFix,
+}
+
+impl std::str::FromStr for GatePolicy {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "blocking" => Ok(Self::Blocking),
+            "advisory" => Ok(Self::Advisory),
+            "autofix" | "auto-fix" => Ok(Self::AutoFix),
+            other => Err(AgentError::Config(format!(
+                "unknown gate policy '{}' in {}",
+                other, GATES_ENV
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub gate: Gate,
+    pub level: String,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+    pub code: Option<String>,
+    // full compiler output for the finding, when available
+    pub detail: Option<String>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{} {}]", self.gate.name(), self.level)?;
+        if let Some(file) = &self.file {
+            write!(f, " {}", file)?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+            }
+        }
+        if let Some(code) = &self.code {
+            write!(f, " {}:", code)?;
+        }
+        write!(f, " {}", self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GateResult {
+    pub gate: Gate,
+    pub policy: GatePolicy,
+    pub passed: bool,
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GateReport {
+    pub results: Vec<GateResult>,
+}
+
+impl GateReport {
+    pub fn blocking_failures(&self) -> Vec<&GateResult> {
+        self.results
+            .iter()
+            .filter(|result| result.policy == GatePolicy::Blocking && !result.passed)
+            .collect()
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        !self.blocking_failures().is_empty()
+    }
+
+    // findings from gates that do not block, to feed back as improvement hints
+    pub fn advisory_findings(&self) -> Vec<Finding> {
+        self.results
+            .iter()
+            .filter(|result| result.policy != GatePolicy::Blocking)
+            .flat_map(|result| result.findings.iter().cloned())
+            .collect()
+    }
+
+    // the blocking findings with full compiler output, as the bug report for the developer
+    pub fn render_blocking(&self) -> String {
+        self.blocking_failures()
+            .into_iter()
+            .flat_map(|result| result.findings.iter())
+            .map(|finding| match &finding.detail {
+                Some(detail) => format!("{}\n", detail.trim_end()),
+                None => format!("{}\n", finding),
+            })
+            .collect()
+    }
+}
+
+pub fn render_findings<'a>(findings: impl Iterator<Item = &'a Finding>) -> String {
+    findings.map(|finding| format!("{}\n", finding)).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GatePipeline {
+    gates: Vec<(Gate, GatePolicy)>,
+}
+
+impl Default for GatePipeline {
+    fn default() -> Self {
+        Self {
+            gates: vec![
+                (Gate::Check, GatePolicy::Blocking),
+                (Gate::Clippy, GatePolicy::Advisory),
+                (Gate::Test, GatePolicy::Blocking),
+                (Gate::Fmt, GatePolicy::AutoFix),
+            ],
+        }
+    }
+}
+
+impl GatePipeline {
+    pub fn new(gates: Vec<(Gate, GatePolicy)>) -> Self {
+        Self { gates }
+    }
+
+    // eg AUTO_GIPPITY_GATES="check=blocking,clippy=advisory,test=blocking,fmt=autofix"
+    pub fn from_env() -> Result<Self, AgentError> {
+        match env::var(GATES_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn gates(&self) -> &[(Gate, GatePolicy)] {
+        &self.gates
+    }
+
+    // run every gate in order, stopping early once a blocking gate fails
+    pub async fn run(&self, runner: &CodeRunner) -> Result<GateReport, AgentError> {
+        let mut report: GateReport = GateReport::default();
+
+        for (gate, policy) in &self.gates {
+            let mut output: Output = runner.cargo_output(&gate.cargo_args()).await?;
+
+            if *policy == GatePolicy::AutoFix && !output.status.success() {
+                if let Some(fix_args) = gate.fix_args() {
+                    runner.cargo_output(&fix_args).await?;
+                    output = runner.cargo_output(&gate.cargo_args()).await?;
+                }
+            }
+
+            let result: GateResult = GateResult {
+                gate: *gate,
+                policy: *policy,
+                passed: output.status.success(),
+                findings: parse_gate_output(*gate, &output),
+            };
+            let stop: bool = result.policy == GatePolicy::Blocking && !result.passed;
+            report.results.push(result);
+
+            if stop {
+                break;
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl std::str::FromStr for GatePipeline {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut gates: Vec<(Gate, GatePolicy)> = vec![];
+        for entry in value.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (gate, policy) = entry.split_once('=').ok_or_else(|| {
+                AgentError::Config(format!(
+                    "expected gate=policy in {}, got '{}'",
+                    GATES_ENV, entry
+                ))
+            })?;
+            gates.push((gate.parse()?, policy.parse()?));
+        }
+        Ok(Self { gates })
+    }
+}
+
+fn parse_gate_output(gate: Gate, output: &Output) -> Vec<Finding> {
+    let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+
+    let mut findings: Vec<Finding> = match gate {
+        Gate::Check | Gate::Clippy => parse_compiler_messages(gate, &stdout),
+        Gate::Test => {
+            let mut findings = parse_compiler_messages(gate, &stdout);
+            findings.extend(parse_test_failures(&stdout));
+            findings
+        }
+        Gate::Fmt => parse_fmt_diffs(&stdout),
+    };
+
+    // a failure we could not attribute, eg a missing component or manifest error
+    if findings.is_empty() && !output.status.success() {
+        let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+        findings.push(Finding {
+            gate,
+            level: "error".to_string(),
+            message: tail.into_iter().rev().collect::<Vec<&str>>().join("\n"),
+            file: None,
+            line: None,
+            code: None,
+            detail: None,
+        });
+    }
+
+    findings.truncate(MAX_FINDINGS_PER_GATE);
+    findings
+}
+
+// cargo --message-format=json compiler messages with a source location
+fn parse_compiler_messages(gate: Gate, stdout: &str) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = vec![];
+
+    for line in stdout.lines() {
+        let value: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        if value["reason"] != "compiler-message" {
+            continue;
+        }
+
+        let message: &Value = &value["message"];
+        let level: &str = message["level"].as_str().unwrap_or("");
+        if level != "error" && level != "warning" {
+            continue;
+        }
+
+        let span: Option<&Value> = message["spans"]
+            .as_array()
+            .and_then(|spans| spans.iter().find(|span| span["is_primary"] == true));
+        let span: &Value = match span {
+            Some(span) => span,
+            None => continue,
+        };
+
+        let finding: Finding = Finding {
+            gate,
+            level: level.to_string(),
+            message: message["message"].as_str().unwrap_or("").to_string(),
+            file: span["file_name"].as_str().map(String::from),
+            line: span["line_start"].as_u64(),
+            code: message["code"]["code"].as_str().map(String::from),
+            detail: message["rendered"].as_str().map(String::from),
+        };
+        if !findings.contains(&finding) {
+            findings.push(finding);
+        }
+    }
+
+    findings
+}
+
+// "test routes::creates_item ... FAILED" with the panic message from the failures section
+fn parse_test_failures(stdout: &str) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = vec![];
+
+    for line in stdout.lines() {
+        let name: Option<&str> = line
+            .strip_prefix("test ")
+            .and_then(|rest| rest.strip_suffix(" ... FAILED"));
+        if let Some(name) = name {
+            findings.push(Finding {
+                gate: Gate::Test,
+                level: "error".to_string(),
+                message: format!("test {} failed{}", name, failure_detail(stdout, name)),
+                file: None,
+                line: None,
+                code: None,
+                detail: None,
+            });
+        }
+    }
+
+    findings
+}
+
+fn failure_detail(stdout: &str, test_name: &str) -> String {
+    let header: String = format!("---- {} stdout ----", test_name);
+    let section: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| line.trim() != header)
+        .skip(1)
+        .take_while(|line| !line.starts_with("---- ") && line.trim() != "failures:")
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+
+    if section.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", section.join(" "))
+    }
+}
+
+// "Diff in /workspace/src/main.rs at line 12:"
+fn parse_fmt_diffs(stdout: &str) -> Vec<Finding> {
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("Diff in "))
+        .map(|rest| {
+            let (file, line) = match rest.trim_end_matches(':').rsplit_once(" at line ") {
+                Some((file, line)) => (file.to_string(), line.parse().ok()),
+                None => (rest.trim_end_matches(':').to_string(), None),
+            };
+            Finding {
+                gate: Gate::Fmt,
+                level: "warning".to_string(),
+                message: "code is not formatted with rustfmt".to_string(),
+                file: Some(file),
+                line,
+                code: None,
+                detail: None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_parses_gate_configuration() {
+        let pipeline: GatePipeline = "check=blocking, clippy=autofix".parse().unwrap();
+        assert_eq!(
+            pipeline.gates(),
+            &[
+                (Gate::Check, GatePolicy::Blocking),
+                (Gate::Clippy, GatePolicy::AutoFix)
+            ]
+        );
+        assert!("check".parse::<GatePipeline>().is_err());
+        assert!("lint=blocking".parse::<GatePipeline>().is_err());
+    }
+
+    #[test]
+    fn tests_parses_compiler_and_test_output() {
+        let stdout = r#"{"reason":"compiler-artifact","target":{}}
+{"reason":"compiler-message","message":{"level":"warning","message":"unneeded `return` statement","code":{"code":"clippy::needless_return"},"spans":[{"file_name":"src/routes.rs","line_start":14,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"level":"warning","message":"1 warning emitted","code":null,"spans":[]}}
+running 2 tests
+test tests::lists_items ... ok
+test tests::creates_item ... FAILED
+
+failures:
+
+---- tests::creates_item stdout ----
+thread 'tests::creates_item' panicked at 'assertion failed: resp.status().is_success()'
+
+failures:
+    tests::creates_item
+"#;
+        let findings: Vec<Finding> = parse_compiler_messages(Gate::Clippy, stdout);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].to_string(),
+            "[clippy warning] src/routes.rs:14 clippy::needless_return: unneeded `return` statement"
+        );
+
+        let failures: Vec<Finding> = parse_test_failures(stdout);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0]
+            .message
+            .starts_with("test tests::creates_item failed: thread"));
+    }
+
+    #[test]
+    fn tests_parses_fmt_diffs() {
+        let findings =
+            parse_fmt_diffs("Diff in /ws/src/main.rs at line 3:\n-fn main(){}\n+fn main() {}\n");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].file.as_deref(), Some("/ws/src/main.rs"));
+        assert_eq!(findings[0].line, Some(3));
+    }
+}
@@ -0,0 +1,616 @@
+use reqwest::Url;
+use std::env;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+
+pub const EXECUTION_MODE_ENV: &str = "AUTO_GIPPITY_EXECUTION_MODE";
+
+// hosts cargo needs to reach when building inside the sandbox
+const CRATES_IO_HOSTS: [&str; 3] = ["crates.io", "index.crates.io", "static.crates.io"];
+
+// port the allowlisting proxy is exposed on inside the sandbox
+const SANDBOX_PROXY_PORT: u16 = 3128;
+
+// directory inside the workspace used for sockets and the sandbox cargo home
+const SANDBOX_DIR: &str = ".sandbox";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExecutionMode {
+    #[default]
+    Host,
+    Sandboxed,
+}
+
+impl ExecutionMode {
+    // read the execution mode for this run from the environment
+    pub fn from_env() -> Result<Self, SandboxError> {
+        match env::var(EXECUTION_MODE_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+impl std::str::FromStr for ExecutionMode {
+    type Err = SandboxError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "host" => Ok(Self::Host),
+            "sandbox" | "sandboxed" => Ok(Self::Sandboxed),
+            other => Err(SandboxError::UnknownMode(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SandboxError {
+    UnknownMode(String),
+    ToolingUnavailable { tool: String, hint: String },
+    NamespacesUnavailable(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownMode(mode) => write!(
+                f,
+                "unknown execution mode '{}' in {}, expected 'host' or 'sandbox'",
+                mode, EXECUTION_MODE_ENV
+            ),
+            Self::ToolingUnavailable { tool, hint } => write!(
+                f,
+                "sandboxed execution requires '{}' but it was not found on PATH ({}). \
+                 Install it or set {}=host to run on the host",
+                tool, hint, EXECUTION_MODE_ENV
+            ),
+            Self::NamespacesUnavailable(stderr) => write!(
+                f,
+                "bubblewrap could not create a sandbox, unprivileged user namespaces may be \
+                 disabled on this machine: {}",
+                stderr.trim()
+            ),
+            Self::Io(e) => write!(f, "sandbox io error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SandboxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SandboxError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+// runs cargo in the web server workspace, either directly or inside a sandbox
+#[derive(Debug, Clone)]
+pub struct CodeRunner {
+    mode: ExecutionMode,
+    workspace: PathBuf,
+    allowed_hosts: Vec<String>,
+    target_dir: Option<PathBuf>,
+}
+
+impl CodeRunner {
+    pub fn new(
+        mode: ExecutionMode,
+        workspace: &str,
+        external_urls: &[String],
+    ) -> Result<Self, SandboxError> {
+        if mode == ExecutionMode::Sandboxed {
+            check_sandbox_tooling()?;
+        }
+
+        Ok(Self {
+            mode,
+            workspace: PathBuf::from(workspace),
+            allowed_hosts: hosts_from_urls(external_urls),
+            target_dir: None,
+        })
+    }
+
+    // build into a target directory shared with other workspaces, eg candidate copies
+    pub fn with_target_dir(mut self, target_dir: &Path) -> Self {
+        self.target_dir = Some(target_dir.to_path_buf());
+        self
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    // run a cargo command to completion, eg cargo build
+    pub async fn cargo_output(&self, args: &[&str]) -> Result<Output, SandboxError> {
+        match self.mode {
+            ExecutionMode::Host => {
+                let output = self
+                    .host_command(args)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+                    .await?;
+                Ok(output)
+            }
+            ExecutionMode::Sandboxed => {
+                // building only needs the crates registry
+                let hosts: Vec<String> = CRATES_IO_HOSTS.iter().map(|h| h.to_string()).collect();
+                let socket_dir = self.prepare_socket_dir()?;
+                let proxy = spawn_allowlist_proxy(&socket_dir, hosts)?;
+
+                let output = self
+                    .bwrap_command(&socket_dir, args, None)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+                    .await;
+
+                proxy.abort();
+                Ok(output?)
+            }
+        }
+    }
+
+    // spawn a long running cargo command, eg cargo run, serving on the given port
+    pub async fn spawn_cargo(
+        &self,
+        args: &[&str],
+        port: u16,
+    ) -> Result<RunningServer, SandboxError> {
+        match self.mode {
+            ExecutionMode::Host => {
+                let child = self
+                    .host_command(args)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()?;
+                Ok(RunningServer {
+                    child,
+                    tasks: vec![],
+                    port,
+                })
+            }
+            ExecutionMode::Sandboxed => {
+                let socket_dir = self.prepare_socket_dir()?;
+                let proxy = spawn_allowlist_proxy(&socket_dir, self.allowed_hosts.clone())?;
+
+                let child = self
+                    .bwrap_command(&socket_dir, args, Some(port))
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()?;
+
+                // expose the sandboxed server on the host loopback
+                let ingress =
+                    spawn_ingress_forwarder(socket_dir.join("ingress.sock"), port).await?;
+
+                Ok(RunningServer {
+                    child,
+                    tasks: vec![proxy, ingress],
+                    port,
+                })
+            }
+        }
+    }
+
+    fn host_command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("cargo");
+        command.args(args).current_dir(&self.workspace);
+        if let Some(target_dir) = &self.target_dir {
+            command.env("CARGO_TARGET_DIR", target_dir);
+        }
+        command
+    }
+
+    fn prepare_socket_dir(&self) -> Result<PathBuf, SandboxError> {
+        let socket_dir = self.workspace.join(SANDBOX_DIR);
+        std::fs::create_dir_all(socket_dir.join("cargo-home"))?;
+        for socket in ["egress.sock", "ingress.sock"] {
+            let _ = std::fs::remove_file(socket_dir.join(socket));
+        }
+        Ok(socket_dir)
+    }
+
+    // build the bubblewrap invocation wrapping a cargo command
+    fn bwrap_command(
+        &self,
+        socket_dir: &Path,
+        args: &[&str],
+        ingress_port: Option<u16>,
+    ) -> Command {
+        let mut command = Command::new("bwrap");
+        for arg in self.bwrap_args(socket_dir, ingress_port) {
+            command.arg(arg);
+        }
+        command
+            .arg("--")
+            .arg("sh")
+            .arg("-c")
+            .arg(sandbox_entrypoint(ingress_port));
+        command.arg("sh").arg("cargo").args(args);
+        command
+    }
+
+    fn bwrap_args(&self, socket_dir: &Path, ingress_port: Option<u16>) -> Vec<String> {
+        let workspace = self.workspace.to_string_lossy().to_string();
+        let proxy_url = format!("http://127.0.0.1:{}", SANDBOX_PROXY_PORT);
+        let home = env::var("HOME").unwrap_or_else(|_| "/root".to_string());
+        let cargo_home = env::var("CARGO_HOME").unwrap_or_else(|_| format!("{}/.cargo", home));
+        let rustup_home = env::var("RUSTUP_HOME").unwrap_or_else(|_| format!("{}/.rustup", home));
+        let sandbox_cargo_home = socket_dir.join("cargo-home").to_string_lossy().to_string();
+
+        let mut args: Vec<String> = vec![
+            "--ro-bind",
+            "/",
+            "/",
+            "--dev",
+            "/dev",
+            "--proc",
+            "/proc",
+            "--tmpfs",
+            "/tmp",
+            "--tmpfs",
+            "/home",
+            "--tmpfs",
+            "/root",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        // toolchain is visible read only, registry downloads go into the workspace
+        for toolchain_dir in [&cargo_home, &rustup_home] {
+            if Path::new(toolchain_dir).exists() {
+                args.extend([
+                    "--ro-bind".to_string(),
+                    toolchain_dir.clone(),
+                    toolchain_dir.clone(),
+                ]);
+            }
+        }
+        args.extend(["--bind".to_string(), workspace.clone(), workspace.clone()]);
+        if let Some(target_dir) = &self.target_dir {
+            let target_dir = target_dir.to_string_lossy().to_string();
+            args.extend(["--bind".to_string(), target_dir.clone(), target_dir.clone()]);
+            args.extend([
+                "--setenv".to_string(),
+                "CARGO_TARGET_DIR".to_string(),
+                target_dir,
+            ]);
+        }
+
+        let path = format!(
+            "{}/bin:{}",
+            cargo_home,
+            env::var("PATH").unwrap_or_else(|_| "/usr/bin:/bin".to_string())
+        );
+        let env_vars: Vec<(&str, String)> = vec![
+            ("HOME", workspace.clone()),
+            ("PATH", path),
+            ("RUSTUP_HOME", rustup_home),
+            ("CARGO_HOME", sandbox_cargo_home),
+            ("HTTP_PROXY", proxy_url.clone()),
+            ("HTTPS_PROXY", proxy_url.clone()),
+            ("http_proxy", proxy_url.clone()),
+            ("https_proxy", proxy_url.clone()),
+            ("CARGO_HTTP_PROXY", proxy_url),
+            ("NO_PROXY", "localhost,127.0.0.1".to_string()),
+            (
+                "SANDBOX_SOCKET_DIR",
+                socket_dir.to_string_lossy().to_string(),
+            ),
+        ];
+        for (key, value) in env_vars {
+            args.extend(["--setenv".to_string(), key.to_string(), value]);
+        }
+        if let Some(port) = ingress_port {
+            args.extend([
+                "--setenv".to_string(),
+                "SANDBOX_PORT".to_string(),
+                port.to_string(),
+            ]);
+        }
+
+        args.extend(
+            [
+                "--unshare-all",
+                "--die-with-parent",
+                "--new-session",
+                "--chdir",
+            ]
+            .into_iter()
+            .map(String::from),
+        );
+        args.push(workspace);
+        args
+    }
+}
+
+// shell run inside the sandbox: bridges the proxy socket onto loopback and
+// optionally exposes the server port through a socket back to the host
+fn sandbox_entrypoint(ingress_port: Option<u16>) -> String {
+    let mut script = format!(
+        "socat TCP-LISTEN:{},bind=127.0.0.1,fork,reuseaddr \
+         UNIX-CONNECT:\"$SANDBOX_SOCKET_DIR/egress.sock\" &\n",
+        SANDBOX_PROXY_PORT
+    );
+    if ingress_port.is_some() {
+        script.push_str(
+            "socat UNIX-LISTEN:\"$SANDBOX_SOCKET_DIR/ingress.sock\",fork,unlink-early \
+             TCP:127.0.0.1:\"$SANDBOX_PORT\" &\n",
+        );
+    }
+    script.push_str("exec \"$@\"");
+    script
+}
+
+// a cargo process serving the web server, plus any sandbox plumbing
+#[derive(Debug)]
+pub struct RunningServer {
+    child: Child,
+    tasks: Vec<JoinHandle<()>>,
+    port: u16,
+}
+
+impl RunningServer {
+    // localhost port the server answers on, forwarded when sandboxed
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    // whether the cargo process has stopped, eg because the server panicked on start
+    pub fn has_exited(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(Some(_)))
+    }
+
+    pub async fn kill(&mut self) -> io::Result<()> {
+        for task in &self.tasks {
+            task.abort();
+        }
+        self.child.kill().await
+    }
+}
+
+impl Drop for RunningServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// confirm bubblewrap and socat exist and that namespaces can be created
+pub fn check_sandbox_tooling() -> Result<(), SandboxError> {
+    if find_on_path("bwrap").is_none() {
+        return Err(SandboxError::ToolingUnavailable {
+            tool: "bwrap".to_string(),
+            hint: "install bubblewrap, eg apt install bubblewrap".to_string(),
+        });
+    }
+    if find_on_path("socat").is_none() {
+        return Err(SandboxError::ToolingUnavailable {
+            tool: "socat".to_string(),
+            hint: "needed to bridge the sandbox network, eg apt install socat".to_string(),
+        });
+    }
+
+    let probe = std::process::Command::new("bwrap")
+        .args(["--ro-bind", "/", "/", "--unshare-all", "--", "true"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !probe.status.success() {
+        return Err(SandboxError::NamespacesUnavailable(
+            String::from_utf8_lossy(&probe.stderr).to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn find_on_path(tool: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .map(|dir| dir.join(tool))
+        .find(|candidate| candidate.is_file())
+}
+
+// extract unique host names from a list of urls
+pub fn hosts_from_urls(urls: &[String]) -> Vec<String> {
+    let mut hosts: Vec<String> = vec![];
+    for url in urls {
+        if let Some(host) = Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(String::from))
+        {
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+    }
+    hosts
+}
+
+// forward connections on the host loopback port into the sandbox socket
+async fn spawn_ingress_forwarder(socket: PathBuf, port: u16) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    Ok(tokio::spawn(async move {
+        while let Ok((mut inbound, _)) = listener.accept().await {
+            let socket = socket.clone();
+            tokio::spawn(async move {
+                if let Ok(mut outbound) = UnixStream::connect(&socket).await {
+                    let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                }
+            });
+        }
+    }))
+}
+
+// http proxy on a unix socket that only tunnels to allowed hosts
+fn spawn_allowlist_proxy(
+    socket_dir: &Path,
+    allowed_hosts: Vec<String>,
+) -> io::Result<JoinHandle<()>> {
+    let listener = UnixListener::bind(socket_dir.join("egress.sock"))?;
+    Ok(tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let allowed_hosts = allowed_hosts.clone();
+            tokio::spawn(async move {
+                let _ = handle_proxy_connection(stream, &allowed_hosts).await;
+            });
+        }
+    }))
+}
+
+async fn handle_proxy_connection(
+    mut client: UnixStream,
+    allowed_hosts: &[String],
+) -> io::Result<()> {
+    // read the request head
+    let mut head: Vec<u8> = vec![];
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = client.read(&mut buf).await?;
+        if n == 0 || head.len() > 16 * 1024 {
+            return Ok(());
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let request_line = String::from_utf8_lossy(&head)
+        .lines()
+        .next()
+        .unwrap_or("")
+        .to_string();
+    let target = match parse_proxy_target(&request_line) {
+        Some(target) => target,
+        None => {
+            client
+                .write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if !is_host_allowed(&target.host, allowed_hosts) {
+        let body = format!("sandbox: outbound access to {} is not allowed", target.host);
+        let response = format!(
+            "HTTP/1.1 403 Forbidden\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        client.write_all(response.as_bytes()).await?;
+        return Ok(());
+    }
+
+    let mut upstream = TcpStream::connect((target.host.as_str(), target.port)).await?;
+    if target.is_tunnel {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+    } else {
+        upstream.write_all(&head).await?;
+    }
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+struct ProxyTarget {
+    host: String,
+    port: u16,
+    is_tunnel: bool,
+}
+
+// CONNECT host:port for https, absolute urls for plain http
+fn parse_proxy_target(request_line: &str) -> Option<ProxyTarget> {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = target.rsplit_once(':')?;
+        return Some(ProxyTarget {
+            host: host.trim_matches(|c| c == '[' || c == ']').to_lowercase(),
+            port: port.parse().ok()?,
+            is_tunnel: true,
+        });
+    }
+
+    let url = Url::parse(target).ok()?;
+    Some(ProxyTarget {
+        host: url.host_str()?.to_lowercase(),
+        port: url.port_or_known_default()?,
+        is_tunnel: false,
+    })
+}
+
+fn is_host_allowed(host: &str, allowed_hosts: &[String]) -> bool {
+    allowed_hosts
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_parses_execution_mode() {
+        assert_eq!(
+            "host".parse::<ExecutionMode>().unwrap(),
+            ExecutionMode::Host
+        );
+        assert_eq!(
+            "Sandbox".parse::<ExecutionMode>().unwrap(),
+            ExecutionMode::Sandboxed
+        );
+        assert!("docker".parse::<ExecutionMode>().is_err());
+    }
+
+    #[test]
+    fn tests_extracts_allowed_hosts() {
+        let urls: Vec<String> = vec![
+            "http://worldtimeapi.org/api/timezone".to_string(),
+            "http://worldtimeapi.org/api/ip".to_string(),
+            "not a url".to_string(),
+        ];
+        assert_eq!(hosts_from_urls(&urls), vec!["worldtimeapi.org".to_string()]);
+    }
+
+    #[test]
+    fn tests_parses_proxy_targets() {
+        let tunnel = parse_proxy_target("CONNECT api.binance.com:443 HTTP/1.1").unwrap();
+        assert_eq!(tunnel.host, "api.binance.com");
+        assert_eq!(tunnel.port, 443);
+        assert!(tunnel.is_tunnel);
+
+        let plain = parse_proxy_target("GET http://worldtimeapi.org/api/ip HTTP/1.1").unwrap();
+        assert_eq!(plain.port, 80);
+        assert!(!plain.is_tunnel);
+        assert!(is_host_allowed(
+            &plain.host,
+            &["worldtimeapi.org".to_string()]
+        ));
+        assert!(!is_host_allowed(
+            "example.com",
+            &["worldtimeapi.org".to_string()]
+        ));
+    }
+}
@@ -0,0 +1,657 @@
+use crate::apis::http_client::shared_client;
+use crate::helpers::command_line::confirm_tool_call;
+use crate::helpers::general::URL_CHECK_TIMEOUT;
+use crate::helpers::sandbox::{CodeRunner, RunningServer};
+use crate::models::general::errors::AgentError;
+use crate::models::general::llm::{FunctionDefinition, ToolDefinition};
+use regex::Regex;
+use reqwest::{Client, Method};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Output;
+
+pub const TOOLS_ENV: &str = "AUTO_GIPPITY_TOOLS";
+pub const TOOL_PROTOCOL_ENV: &str = "AUTO_GIPPITY_TOOL_PROTOCOL";
+
+// rounds of tool calls before the llm has to answer
+const DEFAULT_MAX_TOOL_ITERATIONS: u8 = 6;
+
+// keeps a single tool result from filling the context window
+const MAX_TOOL_OUTPUT_CHARS: usize = 6000;
+const MAX_SEARCH_MATCHES: usize = 50;
+
+// build output and dependencies are not part of the code the llm works on
+const SKIPPED_DIRS: [&str; 3] = ["target", ".git", ".sandbox"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    ReadFile,
+    ListDir,
+    SearchCode,
+    CargoCheck,
+    HitEndpoint,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 5] = [
+        Self::ReadFile,
+        Self::ListDir,
+        Self::SearchCode,
+        Self::CargoCheck,
+        Self::HitEndpoint,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::ReadFile => "read_file",
+            Self::ListDir => "list_dir",
+            Self::SearchCode => "search_code",
+            Self::CargoCheck => "cargo_check",
+            Self::HitEndpoint => "hit_endpoint",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::ReadFile => "Read a file of the backend project, path relative to the project root",
+            Self::ListDir => "List the files and directories in a directory of the backend project",
+            Self::SearchCode => "Search the backend project for lines matching a regular expression",
+            Self::CargoCheck => "Run cargo check on the backend project and return the compiler output",
+            Self::HitEndpoint => "Send a request to the locally running backend server and return the status and body",
+        }
+    }
+
+    fn parameters(&self) -> Value {
+        match self {
+            Self::ReadFile => json!({
+                "type": "object",
+                "properties": { "path": { "type": "string" } },
+                "required": ["path"]
+            }),
+            Self::ListDir => json!({
+                "type": "object",
+                "properties": { "path": { "type": "string", "description": "defaults to the project root" } }
+            }),
+            Self::SearchCode => json!({
+                "type": "object",
+                "properties": { "pattern": { "type": "string" } },
+                "required": ["pattern"]
+            }),
+            Self::CargoCheck => json!({ "type": "object", "properties": {} }),
+            Self::HitEndpoint => json!({
+                "type": "object",
+                "properties": {
+                    "method": { "type": "string", "enum": ["GET", "POST", "PUT", "DELETE"] },
+                    "path": { "type": "string", "description": "route starting with /" },
+                    "body": { "type": "object" }
+                },
+                "required": ["method", "path"]
+            }),
+        }
+    }
+
+    pub fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            kind: "function".to_string(),
+            function: FunctionDefinition {
+                name: self.name().to_string(),
+                description: self.description().to_string(),
+                parameters: self.parameters(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Tool {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|tool| tool.name() == value.trim())
+            .ok_or_else(|| {
+                AgentError::Config(format!("unknown tool '{}' in {}", value.trim(), TOOLS_ENV))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToolPermission {
+    Allow,
+    // the user confirms every call
+    Ask,
+    Deny,
+}
+
+impl std::str::FromStr for ToolPermission {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "allow" => Ok(Self::Allow),
+            "ask" => Ok(Self::Ask),
+            "deny" => Ok(Self::Deny),
+            other => Err(AgentError::Config(format!(
+                "unknown tool permission '{}' in {}",
+                other, TOOLS_ENV
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolPermissions {
+    permissions: Vec<(Tool, ToolPermission)>,
+}
+
+impl Default for ToolPermissions {
+    // reading is free, anything that builds or runs generated code is confirmed
+    fn default() -> Self {
+        Self {
+            permissions: vec![
+                (Tool::ReadFile, ToolPermission::Allow),
+                (Tool::ListDir, ToolPermission::Allow),
+                (Tool::SearchCode, ToolPermission::Allow),
+                (Tool::CargoCheck, ToolPermission::Ask),
+                (Tool::HitEndpoint, ToolPermission::Ask),
+            ],
+        }
+    }
+}
+
+impl ToolPermissions {
+    // eg AUTO_GIPPITY_TOOLS="read_file=allow,search_code=allow,cargo_check=ask,hit_endpoint=deny"
+    pub fn from_env() -> Result<Self, AgentError> {
+        match env::var(TOOLS_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    // tools left out are denied
+    pub fn get(&self, tool: Tool) -> ToolPermission {
+        self.permissions
+            .iter()
+            .find(|(listed, _)| *listed == tool)
+            .map_or(ToolPermission::Deny, |(_, permission)| *permission)
+    }
+}
+
+impl std::str::FromStr for ToolPermissions {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut permissions: Vec<(Tool, ToolPermission)> = vec![];
+        for entry in value.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (tool, permission) = entry.split_once('=').ok_or_else(|| {
+                AgentError::Config(format!(
+                    "expected tool=permission in {}, got '{}'",
+                    TOOLS_ENV, entry
+                ))
+            })?;
+            permissions.push((tool.parse()?, permission.parse()?));
+        }
+        Ok(Self { permissions })
+    }
+}
+
+// how tool calls are exchanged with the llm
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToolProtocol {
+    // openai function calling, the only api call_request talks to. other providers such as
+    // anthropic are reached through an openai compatible endpoint or use the json protocol
+    #[default]
+    Native,
+    // tool calls printed as json, for local models without function calling
+    Json,
+}
+
+impl ToolProtocol {
+    pub fn from_env() -> Result<Self, AgentError> {
+        match env::var(TOOL_PROTOCOL_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+impl std::str::FromStr for ToolProtocol {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "native" => Ok(Self::Native),
+            "json" => Ok(Self::Json),
+            other => Err(AgentError::Config(format!(
+                "unknown tool protocol '{}' in {}, expected 'native' or 'json'",
+                other, TOOL_PROTOCOL_ENV
+            ))),
+        }
+    }
+}
+
+// a tool call printed by a model using the json protocol
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct JsonToolCall {
+    pub tool: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+impl JsonToolCall {
+    // None when the response is the answer rather than a tool call
+    pub fn parse(response: &str) -> Option<Self> {
+        let trimmed: &str = response
+            .trim()
+            .trim_start_matches("```json")
+            .trim_start_matches("```")
+            .trim_end_matches("```")
+            .trim();
+        serde_json::from_str::<Self>(trimmed).ok()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PathArgs {
+    #[serde(default)]
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchArgs {
+    pattern: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EndpointArgs {
+    method: String,
+    path: String,
+    body: Option<Value>,
+}
+
+// the tools an agent may use on the backend workspace
+#[derive(Debug, Clone)]
+pub struct ToolBox {
+    root: PathBuf,
+    // port of the backend server, only known while one is running
+    port: Option<u16>,
+    runner: Option<CodeRunner>,
+    permissions: ToolPermissions,
+    protocol: ToolProtocol,
+    max_iterations: u8,
+}
+
+impl ToolBox {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: PathBuf::from(root),
+            port: None,
+            runner: None,
+            permissions: ToolPermissions::default(),
+            protocol: ToolProtocol::default(),
+            max_iterations: DEFAULT_MAX_TOOL_ITERATIONS,
+        }
+    }
+
+    // permissions and protocol read from the environment
+    pub fn from_env(root: &str) -> Result<Self, AgentError> {
+        Ok(Self::new(root)
+            .with_permissions(ToolPermissions::from_env()?)
+            .with_protocol(ToolProtocol::from_env()?))
+    }
+
+    // cargo check is only offered with a runner, so it runs where the rest of the build does
+    pub fn with_runner(mut self, runner: CodeRunner) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    // endpoints are only offered with a server, the calls would fail without one
+    pub fn with_server(mut self, server: &RunningServer) -> Self {
+        self.port = Some(server.port());
+        self
+    }
+
+    pub fn with_permissions(mut self, permissions: ToolPermissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn with_protocol(mut self, protocol: ToolProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    pub fn protocol(&self) -> ToolProtocol {
+        self.protocol
+    }
+
+    pub fn max_iterations(&self) -> u8 {
+        self.max_iterations
+    }
+
+    pub fn permits(&self, tool: Tool) -> bool {
+        self.permissions.get(tool) != ToolPermission::Deny
+    }
+
+    // tools that are not denied and can run in this workspace
+    pub fn available(&self) -> Vec<Tool> {
+        Tool::ALL
+            .into_iter()
+            .filter(|tool| self.permits(*tool))
+            .filter(|tool| *tool != Tool::CargoCheck || self.runner.is_some())
+            .filter(|tool| *tool != Tool::HitEndpoint || self.port.is_some())
+            .collect()
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.available().iter().map(Tool::definition).collect()
+    }
+
+    // instructions for models that print tool calls as json
+    pub fn json_protocol_prompt(&self) -> String {
+        let tools: String = self
+            .available()
+            .iter()
+            .map(|tool| {
+                format!(
+                    "\n  {}: {}. arguments: {}",
+                    tool,
+                    tool.description(),
+                    tool.parameters()
+                )
+            })
+            .collect();
+        format!(
+            "TOOLS You can inspect the backend project before answering. To call a tool print ONLY \
+             {{\"tool\": \"<name>\", \"arguments\": {{...}}}} and nothing else, the result is sent back to you. \
+             Print the function result once you know enough. Available tools:{}",
+            tools
+        )
+    }
+
+    // run a call from the llm, failures are reported back to it as the result
+    pub async fn run(&self, agent_pos: &str, name: &str, arguments: &Value) -> String {
+        let result: Result<String, String> = match name.parse::<Tool>() {
+            Ok(tool) => self.run_tool(agent_pos, tool, arguments).await,
+            Err(_) => Err(format!("unknown tool '{}'", name)),
+        };
+
+        let output: String = result.unwrap_or_else(|reason| format!("error: {}", reason));
+        if output.len() <= MAX_TOOL_OUTPUT_CHARS {
+            return output;
+        }
+        let mut end: usize = MAX_TOOL_OUTPUT_CHARS;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}\n[output truncated]", &output[..end])
+    }
+
+    async fn run_tool(
+        &self,
+        agent_pos: &str,
+        tool: Tool,
+        arguments: &Value,
+    ) -> Result<String, String> {
+        match self.permissions.get(tool) {
+            ToolPermission::Allow => {}
+            ToolPermission::Deny => return Err(format!("{} is not permitted", tool)),
+            ToolPermission::Ask => {
+                if !confirm_tool_call(agent_pos, &format!("{} {}", tool, arguments)).await {
+                    return Err(format!("the user did not allow {}", tool));
+                }
+            }
+        }
+
+        let decode_error = |e: serde_json::Error| format!("invalid arguments for {}: {}", tool, e);
+        match tool {
+            Tool::ReadFile => {
+                let args: PathArgs =
+                    serde_json::from_value(arguments.clone()).map_err(decode_error)?;
+                let path: PathBuf = self.resolve(&args.path)?;
+                fs::read_to_string(&path).map_err(|e| format!("cannot read {}: {}", args.path, e))
+            }
+            Tool::ListDir => {
+                let args: PathArgs =
+                    serde_json::from_value(arguments.clone()).map_err(decode_error)?;
+                self.list_dir(&args.path)
+            }
+            Tool::SearchCode => {
+                let args: SearchArgs =
+                    serde_json::from_value(arguments.clone()).map_err(decode_error)?;
+                self.search_code(&args.pattern)
+            }
+            Tool::CargoCheck => self.cargo_check().await,
+            Tool::HitEndpoint => {
+                let args: EndpointArgs =
+                    serde_json::from_value(arguments.clone()).map_err(decode_error)?;
+                self.hit_endpoint(args).await
+            }
+        }
+    }
+
+    // paths stay inside the workspace and out of the directories the search skips
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let relative: &Path = Path::new(path);
+        let is_inside: bool = relative.components().all(|component| match component {
+            Component::Normal(name) => !SKIPPED_DIRS.contains(&name.to_string_lossy().as_ref()),
+            Component::CurDir => true,
+            _ => false,
+        });
+        if !is_inside {
+            return Err(format!("'{}' is outside the backend project", path));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn list_dir(&self, path: &str) -> Result<String, String> {
+        let dir: PathBuf = self.resolve(path)?;
+        let entries = fs::read_dir(&dir).map_err(|e| format!("cannot list '{}': {}", path, e))?;
+
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !SKIPPED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()))
+            .map(|entry| {
+                let name: String = entry.file_name().to_string_lossy().to_string();
+                if entry.path().is_dir() {
+                    format!("{}/", name)
+                } else {
+                    name
+                }
+            })
+            .collect();
+        names.sort();
+        Ok(names.join("\n"))
+    }
+
+    fn search_code(&self, pattern: &str) -> Result<String, String> {
+        let regex: Regex = Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))?;
+        let mut files: Vec<PathBuf> = vec![];
+        collect_source_files(&self.root, &mut files);
+        files.sort();
+
+        let mut matches: Vec<String> = vec![];
+        for file in files {
+            let Ok(contents) = fs::read_to_string(&file) else {
+                continue;
+            };
+            let relative: String = file
+                .strip_prefix(&self.root)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .to_string();
+            for (number, line) in contents.lines().enumerate() {
+                if regex.is_match(line) {
+                    matches.push(format!("{}:{}: {}", relative, number + 1, line.trim()));
+                }
+                if matches.len() >= MAX_SEARCH_MATCHES {
+                    return Ok(matches.join("\n"));
+                }
+            }
+        }
+
+        if matches.is_empty() {
+            return Ok("no matches".to_string());
+        }
+        Ok(matches.join("\n"))
+    }
+
+    async fn cargo_check(&self) -> Result<String, String> {
+        let runner: &CodeRunner = self
+            .runner
+            .as_ref()
+            .ok_or_else(|| "cargo check is not available here".to_string())?;
+        let output: Output = runner
+            .cargo_output(&["check", "--message-format=short"])
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+        if output.status.success() {
+            return Ok(format!("cargo check passed\n{}", stderr));
+        }
+        Ok(format!("cargo check failed\n{}", stderr))
+    }
+
+    // only the backend server on localhost can be reached
+    async fn hit_endpoint(&self, args: EndpointArgs) -> Result<String, String> {
+        if !args.path.starts_with('/') {
+            return Err("path must start with /".to_string());
+        }
+        let method: Method = args
+            .method
+            .to_uppercase()
+            .parse()
+            .map_err(|_| format!("unknown method '{}'", args.method))?;
+
+        let port: u16 = self
+            .port
+            .ok_or_else(|| "no backend server is running".to_string())?;
+        let client: &Client = shared_client().map_err(|e| e.to_string())?;
+        let url: String = format!("http://localhost:{}{}", port, args.path);
+        let mut request = client.request(method, &url).timeout(URL_CHECK_TIMEOUT);
+        if let Some(body) = &args.body {
+            request = request.json(body);
+        }
+
+        let response: reqwest::Response = request
+            .send()
+            .await
+            .map_err(|e| format!("request to {} failed: {}", url, e))?;
+        let status: u16 = response.status().as_u16();
+        let body: String = response.text().await.unwrap_or_default();
+        Ok(format!("status: {}\n{}", status, body))
+    }
+}
+
+fn collect_source_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path: PathBuf = entry.path();
+        let name: String = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_str()) {
+                collect_source_files(&path, files);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tests_runs_permitted_tools_inside_workspace() {
+        let root: PathBuf =
+            env::temp_dir().join(format!("auto_gippity_tools_{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {\n    serve();\n}\n").unwrap();
+
+        let permissions: ToolPermissions =
+            "read_file=allow,list_dir=allow,search_code=allow,hit_endpoint=deny"
+                .parse()
+                .unwrap();
+        let tools: ToolBox = ToolBox::new(root.to_str().unwrap()).with_permissions(permissions);
+        assert_eq!(
+            tools.available(),
+            vec![Tool::ReadFile, Tool::ListDir, Tool::SearchCode]
+        );
+
+        let agent: &str = "Backend Developer";
+        let contents: String = tools
+            .run(agent, "read_file", &json!({"path": "src/main.rs"}))
+            .await;
+        assert!(contents.contains("serve();"));
+        assert_eq!(tools.run(agent, "list_dir", &json!({})).await, "src/");
+        assert_eq!(
+            tools
+                .run(agent, "search_code", &json!({"pattern": "serve\\("}))
+                .await,
+            "src/main.rs:2: serve();"
+        );
+
+        let escaped: String = tools
+            .run(agent, "read_file", &json!({"path": "../etc/passwd"}))
+            .await;
+        assert!(escaped.starts_with("error:"));
+        let skipped: String = tools
+            .run(agent, "list_dir", &json!({"path": "target"}))
+            .await;
+        assert!(skipped.starts_with("error:"));
+        let denied: String = tools
+            .run(
+                agent,
+                "hit_endpoint",
+                &json!({"method": "GET", "path": "/"}),
+            )
+            .await;
+        assert_eq!(denied, "error: hit_endpoint is not permitted");
+
+        let call: Option<JsonToolCall> = JsonToolCall::parse(
+            "```json\n{\"tool\": \"list_dir\", \"arguments\": {\"path\": \"src\"}}\n```",
+        );
+        assert_eq!(call.map(|call| call.tool), Some("list_dir".to_string()));
+        assert!(JsonToolCall::parse("{\"edits\": []}").is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tests_offers_endpoints_only_with_a_server() {
+        let permissions: ToolPermissions = "read_file=allow,cargo_check=allow,hit_endpoint=allow"
+            .parse()
+            .unwrap();
+        let tools: ToolBox = ToolBox::new("web_template").with_permissions(permissions);
+        assert_eq!(tools.available(), vec![Tool::ReadFile]);
+        assert!(tools
+            .definitions()
+            .iter()
+            .all(|definition| definition.function.name != "hit_endpoint"));
+
+        let called: String = tools
+            .run(
+                "Backend Developer",
+                "hit_endpoint",
+                &json!({"method": "GET", "path": "/"}),
+            )
+            .await;
+        assert_eq!(called, "error: no backend server is running");
+    }
+}
@@ -0,0 +1,137 @@
+use crate::ai_functions::prompt_registry::Prompt;
+use crate::apis::model_routing::ModelRoute;
+use crate::helpers::command_line::print_agent_error;
+use crate::helpers::general::load_env;
+use crate::models::general::errors::AgentError;
+use crate::models::general::llm::Message;
+use serde::Serialize;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// path of a jsonl file every llm call is appended to, with the prompt version it used
+pub const TRANSCRIPT_ENV: &str = "AUTO_GIPPITY_TRANSCRIPT";
+
+// agents running in parallel append to the same file
+static TRANSCRIPT_LOCK: Mutex<()> = Mutex::new(());
+
+// one llm call, as sent and as answered
+#[derive(Debug, Serialize)]
+pub struct TranscriptEntry<'a> {
+    pub unix_time: u64,
+    pub position: &'a str,
+    pub operation: &'a str,
+    pub prompt_version: &'a str,
+    pub model: &'a str,
+    pub temperature: f32,
+    pub messages: &'a [Message],
+    pub responses: &'a [String],
+}
+
+impl<'a> TranscriptEntry<'a> {
+    pub fn new(
+        position: &'a str,
+        operation: &'a str,
+        prompt: &'a Prompt,
+        route: &'a ModelRoute,
+        messages: &'a [Message],
+        responses: &'a [String],
+    ) -> Self {
+        Self {
+            unix_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            position,
+            operation,
+            prompt_version: &prompt.version,
+            model: &route.model,
+            temperature: route.temperature,
+            messages,
+            responses,
+        }
+    }
+
+    // append to the configured transcript, nothing is written when none is configured.
+    // a transcript that cannot be written is reported, the call it records still counts
+    pub fn record(&self) {
+        load_env();
+        let Ok(path) = env::var(TRANSCRIPT_ENV) else {
+            return;
+        };
+        if path.trim().is_empty() {
+            return;
+        }
+        if let Err(e) = self.append_to(Path::new(&path)) {
+            print_agent_error(self.position, &e);
+        }
+    }
+
+    fn append_to(&self, path: &Path) -> Result<(), AgentError> {
+        let mut line: String = serde_json::to_string(self)
+            .map_err(|e| AgentError::Config(format!("transcript entry is not json: {}", e)))?;
+        line.push('\n');
+
+        let _guard = TRANSCRIPT_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| {
+                AgentError::io(&format!("Failed to write transcript {}", path.display()), e)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::fs;
+
+    #[test]
+    fn tests_appends_calls_with_prompt_version() {
+        let path = std::env::temp_dir().join(format!(
+            "autogippity-transcript-{}.jsonl",
+            std::process::id()
+        ));
+        let prompt: Prompt = Prompt {
+            version: "3".to_string(),
+            ..Prompt::builtin("fn print_goal() {}")
+        };
+        let route: ModelRoute = ModelRoute::new("gpt-4o");
+        let messages: Vec<Message> = vec![Message::user("login page")];
+        let responses: Vec<String> = vec!["build a website".to_string()];
+
+        for operation in ["print_goal", "print_project_scope"] {
+            TranscriptEntry::new(
+                "Managing Agent",
+                operation,
+                &prompt,
+                &route,
+                &messages,
+                &responses,
+            )
+            .append_to(&path)
+            .unwrap();
+        }
+
+        let contents: String = fs::read_to_string(&path).unwrap();
+        let entries: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1]["operation"], "print_project_scope");
+        assert_eq!(entries[1]["prompt_version"], "3");
+        assert_eq!(entries[1]["model"], "gpt-4o");
+        assert_eq!(entries[1]["messages"][0]["content"], "login page");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
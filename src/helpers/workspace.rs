@@ -0,0 +1,341 @@
+use crate::models::general::errors::AgentError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// relative file path -> full file contents
+pub type FileMap = BTreeMap<String, String>;
+
+// directories of the workspace the agents may read and write
+const EDITABLE_DIRS: [&str; 4] = ["src", "tests", "config", "docs"];
+const EDITABLE_EXTENSIONS: [&str; 5] = ["rs", "json", "toml", "sql", "md"];
+// packaging files only the devops agent may write at the top of the workspace
+const ROOT_FILES: [&str; 4] = [
+    "Dockerfile",
+    ".dockerignore",
+    "docker-compose.yml",
+    "README.md",
+];
+const MAX_FILES: usize = 40;
+// added to a file name while it is written, before it is swapped into place
+const STAGED_SUFFIX: &str = ".autogippity-staged";
+const MAX_FILE_BYTES: usize = 256 * 1024;
+
+// decode the json file map printed by an ai function
+pub fn decode_file_map(llm_response: &str, operation: &str) -> Result<FileMap, AgentError> {
+    let files: FileMap =
+        serde_json::from_str(llm_response.trim()).map_err(|source| AgentError::Decode {
+            operation: operation.to_string(),
+            source,
+        })?;
+    validate_file_map(&files)?;
+    Ok(files)
+}
+
+// check every path is a plain relative path inside an editable directory
+pub fn validate_file_map(files: &FileMap) -> Result<(), AgentError> {
+    validate_files(files, false)
+}
+
+fn validate_files(files: &FileMap, allow_root_files: bool) -> Result<(), AgentError> {
+    if files.is_empty() {
+        return Err(AgentError::InvalidFiles(
+            "no files were provided".to_string(),
+        ));
+    }
+    if files.len() > MAX_FILES {
+        return Err(AgentError::InvalidFiles(format!(
+            "{} files provided, at most {} are allowed",
+            files.len(),
+            MAX_FILES
+        )));
+    }
+
+    for (path, contents) in files {
+        validate_path(path, allow_root_files)?;
+        if contents.len() > MAX_FILE_BYTES {
+            return Err(AgentError::InvalidFiles(format!(
+                "{} is larger than {} bytes",
+                path, MAX_FILE_BYTES
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// files written by a fixed generator may be repeated but never changed
+pub fn check_locked_files(files: &FileMap, locked: &FileMap) -> Result<(), AgentError> {
+    for (path, contents) in files {
+        if locked
+            .get(path)
+            .is_some_and(|locked_contents| locked_contents != contents)
+        {
+            return Err(AgentError::InvalidFiles(format!(
+                "{}: file is generated and must not be changed",
+                path
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_path(path: &str, allow_root_files: bool) -> Result<(), AgentError> {
+    let invalid = |reason: &str| AgentError::InvalidFiles(format!("{}: {}", path, reason));
+
+    let relative = Path::new(path);
+    let components: Vec<Component> = relative.components().collect();
+    if components.is_empty() {
+        return Err(invalid("empty path"));
+    }
+    if !components.iter().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(invalid("paths must be relative and must not contain '..'"));
+    }
+    if allow_root_files && ROOT_FILES.contains(&path) {
+        return Ok(());
+    }
+
+    let top_dir = components[0].as_os_str().to_string_lossy();
+    if components.len() < 2 || !EDITABLE_DIRS.contains(&top_dir.as_ref()) {
+        return Err(invalid(&format!(
+            "files may only be written inside {}",
+            EDITABLE_DIRS.join(", ")
+        )));
+    }
+
+    let extension = relative.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !EDITABLE_EXTENSIONS.contains(&extension) {
+        return Err(invalid("unsupported file type"));
+    }
+
+    Ok(())
+}
+
+// resolve a validated path and make sure no symlink leads outside the workspace
+fn confined_path(root: &Path, path: &str, allow_root_files: bool) -> Result<PathBuf, AgentError> {
+    validate_path(path, allow_root_files)?;
+
+    let mut current: PathBuf = root.to_path_buf();
+    for component in Path::new(path).components() {
+        current.push(component);
+        if let Ok(metadata) = fs::symlink_metadata(&current) {
+            if metadata.file_type().is_symlink() {
+                return Err(AgentError::InvalidFiles(format!(
+                    "{}: refusing to write through a symlink",
+                    path
+                )));
+            }
+        }
+    }
+
+    Ok(current)
+}
+
+// read every editable file currently in the workspace
+pub fn read_workspace_files(root: &Path) -> Result<FileMap, AgentError> {
+    let mut files: FileMap = FileMap::new();
+    for dir in EDITABLE_DIRS {
+        collect_files(root, &root.join(dir), &mut files)?;
+    }
+    Ok(files)
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut FileMap) -> Result<(), AgentError> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(dir).map_err(|e| AgentError::io("Failed to list workspace", e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| AgentError::io("Failed to list workspace", e))?;
+        let path: PathBuf = entry.path();
+        let file_type = entry
+            .file_type()
+            .map_err(|e| AgentError::io("Failed to list workspace", e))?;
+
+        if file_type.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if file_type.is_file() {
+            let relative: String = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if validate_path(&relative, false).is_ok() {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| AgentError::io(&format!("Failed to read {}", relative), e))?;
+                files.insert(relative, contents);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// write all files or none: stage next to each target, then swap into place
+pub fn write_file_map(root: &Path, files: &FileMap) -> Result<(), AgentError> {
+    write_files(root, files, false)
+}
+
+// like write_file_map, but the packaging files at the top of the workspace may be written too
+pub fn write_packaging_files(root: &Path, files: &FileMap) -> Result<(), AgentError> {
+    write_files(root, files, true)
+}
+
+fn write_files(root: &Path, files: &FileMap, allow_root_files: bool) -> Result<(), AgentError> {
+    validate_files(files, allow_root_files)?;
+
+    let mut targets: Vec<(PathBuf, PathBuf, Option<String>)> = vec![];
+    for (path, contents) in files {
+        let target: PathBuf = confined_path(root, path, allow_root_files)?;
+        let staged: PathBuf = staged_path(&target);
+        let previous: Option<String> = fs::read_to_string(&target).ok();
+
+        let staged_result = target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&staged, contents));
+        if let Err(e) = staged_result {
+            discard_staged(&targets);
+            let _ = fs::remove_file(&staged);
+            return Err(AgentError::io(&format!("Failed to stage {}", path), e));
+        }
+
+        targets.push((target, staged, previous));
+    }
+
+    for (index, (target, staged, _)) in targets.iter().enumerate() {
+        if let Err(e) = fs::rename(staged, target) {
+            restore_previous(&targets[..index]);
+            discard_staged(&targets[index..]);
+            return Err(AgentError::io(
+                &format!("Failed to write {}", target.display()),
+                e,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// the full file name is kept so app.toml and app.json do not stage to the same file
+fn staged_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(STAGED_SUFFIX);
+    target.with_file_name(name)
+}
+
+fn discard_staged(targets: &[(PathBuf, PathBuf, Option<String>)]) {
+    for (_, staged, _) in targets {
+        let _ = fs::remove_file(staged);
+    }
+}
+
+fn restore_previous(targets: &[(PathBuf, PathBuf, Option<String>)]) {
+    for (target, _, previous) in targets {
+        let _ = match previous {
+            Some(contents) => fs::write(target, contents),
+            None => fs::remove_file(target),
+        };
+    }
+}
+
+// indented tree of the workspace files for prompts
+pub fn render_file_tree(files: &FileMap) -> String {
+    let mut tree: String = String::new();
+    let mut printed_dirs: Vec<String> = vec![];
+
+    for path in files.keys() {
+        let parts: Vec<&str> = path.split('/').collect();
+        for depth in 0..parts.len() - 1 {
+            let dir: String = parts[..=depth].join("/");
+            if !printed_dirs.contains(&dir) {
+                tree.push_str(&format!("{}{}/\n", "  ".repeat(depth), parts[depth]));
+                printed_dirs.push(dir);
+            }
+        }
+        let lines: usize = files[path].lines().count();
+        tree.push_str(&format!(
+            "{}{} ({} lines)\n",
+            "  ".repeat(parts.len() - 1),
+            parts[parts.len() - 1],
+            lines
+        ));
+    }
+
+    tree
+}
+
+// every file with a header line, for prompts that need the full code
+pub fn render_file_contents(files: &FileMap) -> String {
+    files
+        .iter()
+        .map(|(path, contents)| format!("--- FILE: {} ---\n{}\n", path, contents))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_map(entries: &[(&str, &str)]) -> FileMap {
+        entries
+            .iter()
+            .map(|(path, contents)| (path.to_string(), contents.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn tests_rejects_paths_outside_workspace() {
+        assert!(validate_file_map(&file_map(&[("src/main.rs", "fn main() {}")])).is_ok());
+        assert!(validate_file_map(&file_map(&[("../main.rs", "")])).is_err());
+        assert!(validate_file_map(&file_map(&[("/etc/passwd", "")])).is_err());
+        assert!(validate_file_map(&file_map(&[("src/../../x.rs", "")])).is_err());
+        assert!(validate_file_map(&file_map(&[("Cargo.toml", "")])).is_err());
+        assert!(validate_file_map(&file_map(&[("Dockerfile", "FROM rust")])).is_err());
+        assert!(validate_files(&file_map(&[("Dockerfile", "FROM rust")]), true).is_ok());
+        assert!(validate_file_map(&file_map(&[("src/Dockerfile", "")])).is_err());
+        assert!(validate_file_map(&file_map(&[("src/run.sh", "")])).is_err());
+    }
+
+    #[test]
+    fn tests_rejects_changes_to_locked_files() {
+        let locked = file_map(&[("src/auth.rs", "pub fn login() {}\n")]);
+        assert!(check_locked_files(&file_map(&[("src/main.rs", "fn main() {}")]), &locked).is_ok());
+        assert!(check_locked_files(
+            &file_map(&[("src/auth.rs", "pub fn login() {}\n")]),
+            &locked
+        )
+        .is_ok());
+        assert!(matches!(
+            check_locked_files(
+                &file_map(&[("src/auth.rs", "pub fn login() { todo!() }\n")]),
+                &locked
+            ),
+            Err(AgentError::InvalidFiles(_))
+        ));
+    }
+
+    #[test]
+    fn tests_writes_and_reads_file_map() {
+        let root: PathBuf =
+            std::env::temp_dir().join(format!("autogippity-ws-{}", std::process::id()));
+        let files = file_map(&[
+            ("src/main.rs", "mod routes;\nfn main() {}\n"),
+            ("src/routes.rs", "pub fn index() {}\n"),
+            ("config/app.toml", "port = 8080\n"),
+            ("config/app.json", "{\"port\": 8080}\n"),
+        ]);
+
+        write_file_map(&root, &files).unwrap();
+        assert_eq!(read_workspace_files(&root).unwrap(), files);
+
+        let tree: String = render_file_tree(&files);
+        assert_eq!(
+            tree,
+            "config/\n  app.json (1 lines)\n  app.toml (1 lines)\nsrc/\n  main.rs (2 lines)\n  routes.rs (1 lines)\n"
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
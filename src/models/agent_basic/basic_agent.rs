@@ -1,6 +1,25 @@
 use crate::models::agent_basic::basic_traits::BasicTraits;
 use crate::models::general::llm::Message;
 
+// earlier exchanges sent along with a follow up call
+const MAX_HISTORY_EXCHANGES: usize = 2;
+
+// ai functions working on the same problem share their history
+const RELATED_OPERATIONS: [&[&str]; 2] = [
+    &["print_fixed_code", "print_fixed_code_patch"],
+    &[
+        "print_improved_webserver_code",
+        "print_improved_webserver_patch",
+    ],
+];
+
+fn is_related_operation(operation: &str, other: &str) -> bool {
+    operation == other
+        || RELATED_OPERATIONS
+            .iter()
+            .any(|group| group.contains(&operation) && group.contains(&other))
+}
+
 #[derive(Debug, PartialEq)]
 pub enum AgentState {
     Discovery,
@@ -46,4 +65,94 @@ impl BasicTraits for BasicAgent {
     fn get_memory(&self) -> &Vec<Message> {
         &self.memory
     }
+
+    // keep a prompt and its response as one exchange
+    fn remember(&mut self, operation: &str, prompt: Message, response: String) {
+        self.memory.push(prompt.with_operation(operation));
+        self.memory
+            .push(Message::assistant(response).with_operation(operation));
+    }
+
+    // the latest exchanges of this or a related operation, oldest first
+    fn relevant_history(&self, operation: &str) -> Vec<Message> {
+        let related: Vec<&Message> = self
+            .memory
+            .iter()
+            .filter(|message| {
+                message
+                    .operation
+                    .as_deref()
+                    .is_some_and(|other| is_related_operation(operation, other))
+            })
+            .collect();
+
+        let skip: usize = related.len().saturating_sub(MAX_HISTORY_EXCHANGES * 2);
+        related.into_iter().skip(skip).cloned().collect()
+    }
+
+    // replace the related exchanges with one message summarising them
+    fn summarise_history(&mut self, operation: &str, summary: String) {
+        self.memory.retain(|message| {
+            !message
+                .operation
+                .as_deref()
+                .is_some_and(|other| is_related_operation(operation, other))
+        });
+        self.memory.push(
+            Message::system(format!("SUMMARY_OF_EARLIER_EXCHANGES: {}", summary))
+                .with_operation(operation),
+        );
+    }
+
+    fn clear_memory(&mut self) {
+        self.memory.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::general::llm::Role;
+
+    fn prompt(content: &str) -> Message {
+        Message::user(content)
+    }
+
+    #[test]
+    fn tests_recalls_related_exchanges() {
+        let mut agent: BasicAgent =
+            BasicTraits::new("Writes code".to_string(), "Backend Developer".to_string());
+        agent.remember(
+            "print_backend_webserver_code",
+            prompt("write"),
+            "fn main() {}".to_string(),
+        );
+        agent.remember(
+            "print_fixed_code_patch",
+            prompt("fix 1"),
+            "patch 1".to_string(),
+        );
+        agent.remember("print_fixed_code", prompt("fix 2"), "code 2".to_string());
+        agent.remember("print_fixed_code", prompt("fix 3"), "code 3".to_string());
+
+        let history: Vec<Message> = agent.relevant_history("print_fixed_code");
+        let contents: Vec<&str> = history
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect();
+        assert_eq!(contents, vec!["fix 2", "code 2", "fix 3", "code 3"]);
+        assert_eq!(history[1].role, Role::Assistant);
+        assert!(agent
+            .relevant_history("print_rest_api_endpoints")
+            .is_empty());
+
+        agent.summarise_history("print_fixed_code_patch", "tried 3 fixes".to_string());
+        let history: Vec<Message> = agent.relevant_history("print_fixed_code");
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content, "SUMMARY_OF_EARLIER_EXCHANGES: tried 3 fixes");
+        assert_eq!(agent.get_memory().len(), 3);
+
+        agent.clear_memory();
+        assert!(agent.get_memory().is_empty());
+    }
 }
@@ -8,4 +8,8 @@ pub trait BasicTraits {
     fn get_position(&self) -> &String;
     fn get_state(&self) -> &AgentState;
     fn get_memory(&self) -> &Vec<Message>;
+    fn remember(&mut self, operation: &str, prompt: Message, response: String);
+    fn relevant_history(&self, operation: &str) -> Vec<Message>;
+    fn summarise_history(&mut self, operation: &str, summary: String);
+    fn clear_memory(&mut self);
 }
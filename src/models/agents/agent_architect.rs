@@ -1,18 +1,30 @@
 // agent_architect.rs
-use crate::ai_functions::aifunc_architect::{print_project_scope, print_site_urls};
+use crate::ai_functions::aifunc_architect::{
+    PrintProjectScope, PrintSiteUrls, ProjectScopeInput, SiteUrlsInput,
+};
+use crate::apis::http_client::shared_client;
 use crate::helpers::command_line::PrintCommand;
-use crate::helpers::general::{ai_task_request_decoded, check_status_code};
+use crate::helpers::general::{ai_function_request, check_status_code};
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
 use crate::models::agent_basic::basic_traits::BasicTraits;
-use crate::models::agents::agent_traits::{FactSheet, ProjectScope, SpecialFunctions};
+use crate::models::agents::agent_traits::{
+    AgentRequest, AgentRequestKind, FactAccess, FactField, FactSheet, ProjectScope,
+    SpecialFunctions,
+};
+use crate::models::general::errors::AgentError;
 
 use async_trait::async_trait;
 use reqwest::Client;
-use std::time::Duration;
+
+// other agents address their requests to this position
+pub const ARCHITECT_POSITION: &str = "Solutions Architect";
 
 #[derive(Debug)]
 pub struct AgentSolutionArchitect {
     attributes: BasicAgent,
+    scope_feedback: Vec<String>,
+    rejected_urls: Vec<String>,
+    new_requirements: Vec<String>,
 }
 
 impl AgentSolutionArchitect {
@@ -20,45 +32,56 @@ impl AgentSolutionArchitect {
         let attributes = BasicAgent {
             objective: "Gathers information and design solutions for website development"
                 .to_string(),
-            position: "Solutions Architect".to_string(),
+            position: ARCHITECT_POSITION.to_string(),
             state: AgentState::Discovery,
             memory: vec![],
         };
-        Self { attributes }
+        Self {
+            attributes,
+            scope_feedback: vec![],
+            rejected_urls: vec![],
+            new_requirements: vec![],
+        }
     }
 
     // retrieve project scope
-    async fn call_project_scope(&mut self, factsheet: &mut FactSheet) -> ProjectScope {
-        let msg_context: String = format!("{:?}", factsheet.project_description);
+    async fn call_project_scope(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<ProjectScope, AgentError> {
+        let input: ProjectScopeInput = ProjectScopeInput {
+            project_description: factsheet.project_description.clone(),
+            feedback_on_previous_scope: Some(self.scope_feedback.join("; "))
+                .filter(|feedback| !feedback.is_empty()),
+        };
 
-        let ai_response: ProjectScope = ai_task_request_decoded::<ProjectScope>(
-            msg_context,
-            &self.attributes.position,
-            get_function_string!(print_project_scope),
-            print_project_scope,
-        )
-        .await;
+        let ai_response: ProjectScope =
+            ai_function_request::<PrintProjectScope>(&input, &mut self.attributes).await?;
 
         factsheet.project_scope = Some(ai_response.clone());
         self.attributes.update_state(AgentState::Finished);
-        return ai_response;
+        Ok(ai_response)
     }
 
     async fn call_determine_external_urls(
         &mut self,
         factsheet: &mut FactSheet,
-        msg_context: String,
-    ) {
-        let ai_response: Vec<String> = ai_task_request_decoded::<Vec<String>>(
-            msg_context,
-            &self.attributes.position,
-            get_function_string!(print_site_urls),
-            print_site_urls,
-        )
-        .await;
-
-        factsheet.external_urls = Some(ai_response);
+    ) -> Result<(), AgentError> {
+        let input: SiteUrlsInput = SiteUrlsInput {
+            project_description: factsheet.project_description.clone(),
+            do_not_use_urls: Some(self.rejected_urls.clone()).filter(|urls| !urls.is_empty()),
+        };
+
+        let ai_response: Vec<String> =
+            ai_function_request::<PrintSiteUrls>(&input, &mut self.attributes).await?;
+
+        let urls: Vec<String> = ai_response
+            .into_iter()
+            .filter(|url| !self.rejected_urls.contains(url))
+            .collect();
+        factsheet.external_urls = Some(urls);
         self.attributes.update_state(AgentState::UnitTesting);
+        Ok(())
     }
 }
 #[async_trait]
@@ -67,41 +90,91 @@ impl SpecialFunctions for AgentSolutionArchitect {
         &self.attributes
     }
 
+    fn fact_access(&self) -> FactAccess {
+        FactAccess {
+            reads: &[FactField::ProjectDescription],
+            writes: &[
+                FactField::ProjectDescription,
+                FactField::ProjectScope,
+                FactField::ExternalUrls,
+            ],
+            resources: &[],
+        }
+    }
+
+    // other agents can ask for a new scope, other urls or an extra requirement,
+    // requests already taken are ignored so the architect does not run again for nothing
+    fn handle_agent_request(&mut self, request: &AgentRequest) -> bool {
+        match &request.kind {
+            AgentRequestKind::Rescope { reason } => {
+                if self.scope_feedback.contains(reason) {
+                    return false;
+                }
+                self.scope_feedback.push(reason.clone());
+                self.attributes.state = AgentState::Discovery;
+            }
+            AgentRequestKind::ReplaceUrl { url, .. } => {
+                if self.rejected_urls.contains(url) {
+                    return false;
+                }
+                self.rejected_urls.push(url.clone());
+                // a rescope in the same round finds new urls as well
+                if self.attributes.state != AgentState::Discovery {
+                    self.attributes.state = AgentState::Working;
+                }
+            }
+            AgentRequestKind::AddRequirement { requirement } => {
+                if self.new_requirements.contains(requirement) {
+                    return false;
+                }
+                self.new_requirements.push(requirement.clone());
+                self.attributes.state = AgentState::Discovery;
+            }
+        }
+        true
+    }
+
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), AgentError> {
         // !!! WARNING - BE CAREFUL OF INFINITE LOOPS !!!
         while self.attributes.state != AgentState::Finished {
             match self.attributes.state {
                 AgentState::Discovery => {
-                    let project_scope = self.call_project_scope(factsheet).await;
+                    for requirement in &self.new_requirements {
+                        let line: String = format!("\nAdditional requirement: {}", requirement);
+                        if !factsheet.project_description.contains(&line) {
+                            factsheet.project_description.push_str(&line);
+                        }
+                    }
+                    let project_scope = self.call_project_scope(factsheet).await?;
 
                     // confirm if external urls
-                    if project_scope.is_external_urls_required {
-                        self.call_determine_external_urls(
-                            factsheet,
-                            factsheet.project_description.clone(),
-                        )
-                        .await;
+                    if !project_scope.is_external_urls_required {
+                        factsheet.external_urls = None;
+                    } else {
+                        self.call_determine_external_urls(factsheet).await?;
                         self.attributes.state = AgentState::UnitTesting;
                     }
                 }
 
+                // only the external urls are replaced
+                AgentState::Working => {
+                    self.call_determine_external_urls(factsheet).await?;
+                }
+
                 AgentState::UnitTesting => {
                     let mut exclude_urls: Vec<String> = vec![];
 
-                    let client: Client = Client::builder()
-                        .timeout(Duration::from_secs(5))
-                        .build()
-                        .unwrap();
+                    let client: &Client = shared_client()?;
 
                     let urls: Vec<String> = factsheet
                         .external_urls
                         .clone()
-                        .expect("No URL object on factsheet");
+                        .ok_or(AgentError::MissingFact("external_urls"))?;
 
-                    for url in urls {
+                    for url in urls.iter() {
                         let endpoint_str: String = format!("Testing URL Endpoint: {}", url);
                         PrintCommand::UnitTest.print_agent_message(
                             self.attributes.position.as_str(),
@@ -109,10 +182,12 @@ impl SpecialFunctions for AgentSolutionArchitect {
                         );
 
                         // perform url test
-                        match check_status_code(&client, &url).await {
+                        match check_status_code(client, url).await {
                             Ok(status_code) => {
                                 if status_code != 200 {
-                                    exclude_urls.push(url.clone())
+                                    exclude_urls.push(url.clone());
+                                    // not offered again when the urls are replaced later
+                                    self.rejected_urls.push(url.clone());
                                 }
                             }
                             Err(e) => {
@@ -122,10 +197,7 @@ impl SpecialFunctions for AgentSolutionArchitect {
 
                         // exclude any faulty urls
                         if exclude_urls.len() > 0 {
-                            let new_urls: Vec<String> = factsheet
-                                .external_urls
-                                .as_ref()
-                                .unwrap()
+                            let new_urls: Vec<String> = urls
                                 .iter()
                                 .filter(|url| !exclude_urls.contains(&url))
                                 .cloned()
@@ -152,17 +224,36 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn tests_ignores_requests_already_taken() {
+        let mut agent: AgentSolutionArchitect = AgentSolutionArchitect::new();
+        let request = |kind: AgentRequestKind| AgentRequest {
+            from: "Backend Developer".to_string(),
+            to: ARCHITECT_POSITION.to_string(),
+            kind,
+        };
+        let rescope = request(AgentRequestKind::Rescope {
+            reason: "nothing to store".to_string(),
+        });
+        let replace_url = request(AgentRequestKind::ReplaceUrl {
+            url: "https://example.com".to_string(),
+            reason: "returned status 404".to_string(),
+        });
+
+        assert!(agent.handle_agent_request(&rescope));
+        assert!(agent.handle_agent_request(&replace_url));
+        assert_eq!(agent.attributes.state, AgentState::Discovery);
+        assert!(!agent.handle_agent_request(&rescope));
+        assert!(!agent.handle_agent_request(&replace_url));
+    }
+
     #[tokio::test]
     async fn tests_solution_architect() {
         let mut agent: AgentSolutionArchitect = AgentSolutionArchitect::new();
 
-        let mut factsheet: FactSheet = FactSheet {
-            project_description: "build a full stack website with user login and logout that shows the latest forex price for gbp to usd".to_string(),
-            project_scope: None,
-            external_urls: None,
-            backend_code: None,
-            api_endpoint_schema: None,
-        };
+        let mut factsheet: FactSheet = FactSheet::new(
+            "build a full stack website with user login and logout that shows the latest forex price for gbp to usd".to_string(),
+        );
 
         agent
             .execute(&mut factsheet)
@@ -1,14 +1,42 @@
+use crate::apis::http_client::shared_client;
 use crate::ai_functions::aifunc_backend::{
-    print_backend_webserver_code, print_fixed_code, print_improved_webserver_code,
-    print_rest_api_endpoints,
+    print_fixed_code_patch, print_improved_webserver_code, print_improved_webserver_patch,
+    BackendCodeInput, FixedCodeInput, PrintBackendWebserverCode, PrintFixedCode,
+    PrintRestApiEndpoints, RestApiEndpointsInput,
 };
-use crate::helpers::command_line::{ PrintCommand, confirm_safe_code };
+use crate::ai_functions::typed_function::{render_input, AiFunction, AiOutput};
+use crate::helpers::command_line::{ PrintCommand, confirm_safe_code, print_agent_error };
+use crate::helpers::context_window::{AssembledContext, ContextAssembler, SectionPriority};
 use crate::helpers::general::{
-    check_status_code, read_code_template_contents, read_exec_main_contents, save_api_endpoints,
-    save_backend_code, ai_task_request_without_markdown, WEB_SERVER_PROJECT_PATH,
+    check_status_code, context_budget, manifest_has_dependency, read_backend_files, read_backend_manifest,
+    read_code_template_contents, save_api_endpoints, save_backend_files, start_web_server,
+    ai_function_candidates, ai_function_request, ai_task_request_with_tools,
+    ai_task_request_without_markdown, strip_markdown, WEB_SERVER_PORT,
+    WEB_SERVER_PROJECT_PATH,
+};
+use crate::helpers::auth_scaffold::{render_auth_module, AUTH_FILE, AUTH_REQUIRED_CRATES};
+use crate::helpers::persistence::REPOSITORY_FILE;
+use crate::helpers::candidates::{best_candidate, candidate_count_from_env, Candidate, CandidateJudge};
+use crate::helpers::git_history::{CodeHistory, GreenTag};
+use crate::helpers::patch::{apply_edits, parse_edits, PatchRejection};
+use crate::helpers::quality_gates::{
+    render_findings, Finding, GatePipeline, GatePolicy, GateReport,
+};
+use crate::helpers::sandbox::{CodeRunner, ExecutionMode, RunningServer};
+use crate::helpers::tools::{Tool, ToolBox};
+use crate::helpers::workspace::{
+    check_locked_files, decode_file_map, render_file_contents, render_file_tree,
+    validate_file_map, FileMap,
 };
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
-use crate::models::agents::agent_traits::{FactSheet, RouteObject, SpecialFunctions};
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agents::agent_architect::ARCHITECT_POSITION;
+use crate::models::agents::agent_traits::{
+    AbandonedAttempt, AgentRequest, AgentRequestKind, BackendStatus, BugReport, FactAccess,
+    FactField, FactSheet, Resource, RouteObject, SpecialFunctions,
+};
+use crate::models::general::errors::AgentError;
+use crate::models::general::llm::Message;
 
 use crossterm::{
     style::{ SetForegroundColor, Color, ResetColor },
@@ -16,17 +44,36 @@ use crossterm::{
 };
 
 use async_trait::async_trait;
-use reqwest::Client;
-use std::fs;
-use std::process::{Command, Stdio};
-use std::time::Duration;
-use tokio::time;
+use std::path::Path;
+
+// entry point every generated backend must contain
+const MAIN_FILE: &str = "src/main.rs";
+
+// build output kept per abandoned attempt in the factsheet
+const MAX_RECORDED_ERROR_CHARS: usize = 2000;
+
+// improvement rounds spent on advisory gate findings once the code is green
+const MAX_ADVISORY_ROUNDS: u8 = 1;
 
 #[derive(Debug)]
 pub struct AgentBackendDeveloper {
     attributes: BasicAgent,
     bug_errors: Option<String>,
     bug_count: u8,
+    execution_mode: Option<ExecutionMode>,
+    history: Option<CodeHistory>,
+    iteration: u32,
+    last_operation: String,
+    failed_attempts: Vec<AbandonedAttempt>,
+    degraded: bool,
+    gates: Option<GatePipeline>,
+    improvement_hints: Vec<Finding>,
+    advisory_rounds: u8,
+    auth_scaffolded: bool,
+    // the bugs being fixed were reported on code that builds, rather than found by the gates
+    bugs_reported: bool,
+    replaced_urls: Vec<String>,
+    candidates: Option<u8>,
 }
 
 impl AgentBackendDeveloper {
@@ -42,82 +89,600 @@ impl AgentBackendDeveloper {
             attributes,
             bug_errors: None,
             bug_count: 0,
+            execution_mode: None,
+            history: None,
+            iteration: 0,
+            last_operation: String::new(),
+            failed_attempts: vec![],
+            degraded: false,
+            gates: None,
+            improvement_hints: vec![],
+            advisory_rounds: 0,
+            auth_scaffolded: false,
+            bugs_reported: false,
+            replaced_urls: vec![],
+            candidates: None,
         }
     }
 
-    async fn call_initial_backend_code(&mut self, factsheet: &mut FactSheet) {
-        let code_template_str = read_code_template_contents();
+    // choose which quality gates run, otherwise read from the environment
+    pub fn with_gate_pipeline(mut self, gates: GatePipeline) -> Self {
+        self.gates = Some(gates);
+        self
+    }
 
-        let msg_context: String = format!(
-            "CODE TEMPLATE: {} \n PROJECT_DESCRIPTION: {} \n
-            OUTPUT IN PLAIN TEXT ONLY",
-            code_template_str, factsheet.project_description
-        );
+    // choose where generated code runs, otherwise read from the environment
+    pub fn with_execution_mode(mut self, mode: ExecutionMode) -> Self {
+        self.execution_mode = Some(mode);
+        self
+    }
 
-        let ai_response: String = ai_task_request_without_markdown(
-            msg_context,
-            &self.attributes.position,
-            get_function_string!(print_backend_webserver_code),
-            print_backend_webserver_code,
+    // choose how many versions of new code to compare, otherwise read from the environment
+    pub fn with_candidates(mut self, count: u8) -> Self {
+        self.candidates = Some(count.max(1));
+        self
+    }
+
+    fn execution_mode(&self) -> Result<ExecutionMode, AgentError> {
+        match self.execution_mode {
+            Some(mode) => Ok(mode),
+            None => Ok(ExecutionMode::from_env()?),
+        }
+    }
+
+    fn code_runner(&self, factsheet: &FactSheet) -> Result<CodeRunner, AgentError> {
+        let external_urls: Vec<String> = factsheet.external_urls.clone().unwrap_or_default();
+
+        Ok(CodeRunner::new(self.execution_mode()?, WEB_SERVER_PROJECT_PATH, &external_urls)?)
+    }
+
+    fn candidate_count(&self) -> Result<u8, AgentError> {
+        match self.candidates {
+            Some(count) => Ok(count),
+            None => candidate_count_from_env(),
+        }
+    }
+
+    // ask for several answers, build every one that decodes and keep the files of the best
+    async fn choose_candidate<F: AiFunction<Output = FileMap>>(
+        &mut self,
+        factsheet: &FactSheet,
+        input: &F::Input,
+        count: u8,
+    ) -> Result<FileMap, AgentError> {
+        let responses: Vec<String> =
+            ai_function_candidates::<F>(input, &mut self.attributes, count).await?;
+
+        let mut decoded: Vec<(String, FileMap)> = vec![];
+        let mut first_error: Option<AgentError> = None;
+        for response in responses {
+            let files: Result<FileMap, AgentError> = FileMap::decode(&response)
+                .map_err(|source| AgentError::Decode {
+                    operation: F::NAME.to_string(),
+                    source,
+                })
+                .and_then(|files| self.check_generated_files::<F>(factsheet, files));
+            match files {
+                Ok(files) => decoded.push((response, files)),
+                Err(e) => {
+                    PrintCommand::Issue.print_agent_message(
+                        self.attributes.position.as_str(),
+                        &format!("Candidate rejected: {}", e),
+                    );
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if decoded.is_empty() {
+            return Err(first_error.unwrap_or_else(|| {
+                AgentError::LlmResponse("no candidates returned".to_string())
+            }));
+        }
+
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            &format!("Building {} candidates...", decoded.len()),
+        );
+        let external_urls: Vec<String> = factsheet.external_urls.clone().unwrap_or_default();
+        let judge: CandidateJudge = CandidateJudge::new(
+            WEB_SERVER_PROJECT_PATH,
+            self.execution_mode()?,
+            &external_urls,
+            WEB_SERVER_PORT,
         )
-        .await;
+        .with_endpoints(checkable_endpoints(
+            factsheet.api_endpoint_schema.as_deref().unwrap_or_default(),
+        ));
+        let judged: Result<Vec<Candidate>, AgentError> = judge
+            .judge(decoded.iter().map(|(_, files)| files.clone()).collect())
+            .await;
+        if let Err(e) = judge.clean_up() {
+            print_agent_error(self.attributes.position.as_str(), &e);
+        }
+        let candidates: Vec<Candidate> = judged?;
+
+        for candidate in &candidates {
+            PrintCommand::UnitTest.print_agent_message(
+                self.attributes.position.as_str(),
+                &format!(
+                    "Candidate {} of {}: {}",
+                    candidate.index + 1,
+                    candidates.len(),
+                    candidate.score
+                ),
+            );
+        }
+        let best: Candidate = best_candidate(&candidates)
+            .cloned()
+            .ok_or_else(|| AgentError::LlmResponse("no candidates returned".to_string()))?;
+        let building: usize = candidates.iter().filter(|c| c.score.builds).count();
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            &format!(
+                "Continuing with candidate {}, {} of {} candidates build",
+                best.index + 1,
+                building,
+                candidates.len()
+            ),
+        );
+
+        let (response, _) = decoded.swap_remove(best.index);
+        self.attributes
+            .remember(F::NAME, Message::user(render_input(input)), response);
+        Ok(best.files)
+    }
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+    // files must stay inside the workspace and new code must keep its entry point
+    fn check_generated_files<F: AiFunction>(
+        &self,
+        factsheet: &FactSheet,
+        files: FileMap,
+    ) -> Result<FileMap, AgentError> {
+        validate_file_map(&files)?;
+        check_locked_files(&files, &self.locked_files(factsheet))?;
+        if F::NAME == PrintBackendWebserverCode::NAME && !files.contains_key(MAIN_FILE) {
+            return Err(AgentError::InvalidFiles(format!("{} is missing", MAIN_FILE)));
+        }
+        Ok(files)
     }
 
-    async fn call_improved_backend_code(&mut self, factsheet: &mut FactSheet) {
-        let msg_context: String = format!(
-            "CODE TEMPLATE: {:?} \n PROJECT_DESCRIPTION: {:?} \n",
-            factsheet.backend_code, factsheet
+    // the auth module and the data layer repository are fixed, the llm only builds around them
+    fn locked_files(&self, factsheet: &FactSheet) -> FileMap {
+        let mut locked_paths: Vec<&str> = vec![];
+        if self.auth_scaffolded {
+            locked_paths.push(AUTH_FILE);
+        }
+        if factsheet.data_model.is_some() {
+            locked_paths.push(REPOSITORY_FILE);
+        }
+
+        factsheet
+            .backend_code
+            .iter()
+            .flatten()
+            .filter(|(path, _)| locked_paths.contains(&path.as_str()))
+            .map(|(path, contents)| (path.clone(), contents.clone()))
+            .collect()
+    }
+
+    // write the fixed auth module so login and logout are never generated by the llm
+    fn inject_auth_scaffold(&mut self, factsheet: &FactSheet) -> Result<(), AgentError> {
+        let is_login_required: bool = factsheet
+            .project_scope
+            .is_some_and(|scope| scope.is_user_login_and_logout);
+        if !is_login_required {
+            return Ok(());
+        }
+
+        let manifest: String = read_backend_manifest()?;
+        if let Some(missing) = AUTH_REQUIRED_CRATES
+            .iter()
+            .find(|required| !manifest_has_dependency(&manifest, required))
+        {
+            return Err(AgentError::Config(format!(
+                "user login needs {} in the backend Cargo.toml",
+                missing
+            )));
+        }
+
+        save_backend_files(&render_auth_module())?;
+        self.auth_scaffolded = true;
+        self.record_step("auth scaffold");
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            "Auth scaffold: added register, login, logout and me routes",
         );
+        Ok(())
+    }
+
+    // ask the architect to replace external urls that fail while the endpoints are tested
+    async fn request_url_replacements(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        let mut failures: Vec<(String, String)> = vec![];
+        for url in factsheet.external_urls.clone().unwrap_or_default() {
+            let reason: String = match check_status_code(shared_client()?, &url).await {
+                Ok(200) => continue,
+                Ok(status_code) => format!("returned status {} while the endpoints were tested", status_code),
+                Err(e) => format!("could not be reached while the endpoints were tested: {}", e),
+            };
+            failures.push((url, reason));
+        }
+        self.raise_url_replacements(factsheet, failures);
+        Ok(())
+    }
+
+    // one replace url request for every failing url, a url is only raised once
+    pub fn raise_url_replacements(&mut self, factsheet: &mut FactSheet, failures: Vec<(String, String)>) {
+        for (url, reason) in failures {
+            if self.replaced_urls.contains(&url) {
+                continue;
+            }
+            PrintCommand::Issue.print_agent_message(
+                self.attributes.position.as_str(),
+                &format!("External url {} {}, asking for a replacement", url, reason),
+            );
+            factsheet.agent_requests.push(AgentRequest {
+                from: self.attributes.position.clone(),
+                to: ARCHITECT_POSITION.to_string(),
+                kind: AgentRequestKind::ReplaceUrl {
+                    url: url.clone(),
+                    reason,
+                },
+            });
+            self.replaced_urls.push(url);
+        }
+    }
+
+    async fn call_initial_backend_code(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        let code_template_str = read_code_template_contents()?;
+        let current_files: FileMap = read_backend_files()?;
+
+        // files written by the database designer stay part of the backend code
+        let data_layer: FileMap = match &factsheet.data_model {
+            Some(data_model) => current_files
+                .iter()
+                .filter(|(path, _)| data_model.files.contains(path))
+                .map(|(path, contents)| (path.clone(), contents.clone()))
+                .collect(),
+            None => FileMap::new(),
+        };
+        let auth_module: FileMap = match (self.auth_scaffolded, current_files.get(AUTH_FILE)) {
+            (true, Some(contents)) => FileMap::from([(AUTH_FILE.to_string(), contents.clone())]),
+            _ => FileMap::new(),
+        };
+
+        let input: BackendCodeInput = BackendCodeInput {
+            code_template: code_template_str,
+            current_file_tree: render_file_tree(&current_files),
+            project_description: factsheet.project_description.clone(),
+            data_layer: render_file_contents(&data_layer),
+            auth_module: render_file_contents(&auth_module),
+        };
+
+        let mut prebuilt: FileMap = data_layer;
+        prebuilt.extend(auth_module);
+        factsheet.backend_code = Some(prebuilt).filter(|files| !files.is_empty());
+
+        let candidate_count: u8 = self.candidate_count()?;
+        let files: FileMap = if candidate_count > 1 {
+            self.choose_candidate::<PrintBackendWebserverCode>(factsheet, &input, candidate_count)
+                .await?
+        } else {
+            let files: FileMap =
+                ai_function_request::<PrintBackendWebserverCode>(&input, &mut self.attributes).await?;
+            self.check_generated_files::<PrintBackendWebserverCode>(factsheet, files)?
+        };
+
+        self.save_files(factsheet, files, PrintBackendWebserverCode::NAME)
+    }
+
+    async fn call_improved_backend_code(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        let backend_code: FileMap = factsheet.backend_code.clone().unwrap_or_default();
+
+        // the code and what it is for always go, the rest only as far as the window allows
+        let (window, budget) = context_budget(
+            &self.attributes.position,
+            get_function_string!(print_improved_webserver_code),
+            print_improved_webserver_code,
+        )?;
+        let context: AssembledContext = ContextAssembler::new()
+            .with_section("CURRENT_FILE_TREE", render_file_tree(&backend_code), SectionPriority::High)
+            .with_section("CODE_FILES", render_file_contents(&backend_code), SectionPriority::Required)
+            .with_section("PROJECT_DESCRIPTION", factsheet.project_description.clone(), SectionPriority::Required)
+            .with_section("PROJECT_SCOPE", format!("{:?}", factsheet.project_scope), SectionPriority::High)
+            .with_section("EXTERNAL_URLS", format!("{:?}", factsheet.external_urls), SectionPriority::High)
+            .with_section("IMPROVEMENT_HINTS", render_findings(self.improvement_hints.iter()), SectionPriority::High)
+            .with_section("API_ENDPOINTS", format!("{:?}", factsheet.api_endpoint_schema), SectionPriority::Low)
+            .with_section("DATA_MODEL", format!("{:?}", factsheet.data_model), SectionPriority::Low)
+            .assemble(&window.counter, budget);
+        self.improvement_hints.clear();
+
+        if !context.dropped.is_empty() || !context.truncated.is_empty() {
+            PrintCommand::Issue.print_agent_message(
+                self.attributes.position.as_str(),
+                format!(
+                    "Context window full, left out {:?} and shortened {:?}",
+                    context.dropped, context.truncated
+                )
+                .as_str(),
+            );
+        }
+        let msg_context: String = context.text;
+
+        // ask for edits first and only rewrite whole files when they do not apply
+        let patch_response: String = ai_task_request_without_markdown(
+            msg_context.clone(),
+            &mut self.attributes,
+            get_function_string!(print_improved_webserver_patch),
+            print_improved_webserver_patch,
+        )
+        .await?;
+
+        let locked: FileMap = self.locked_files(factsheet);
+        if let Some(files) = self.apply_patch_response(&backend_code, &locked, &patch_response) {
+            return self.save_files(
+                factsheet,
+                files,
+                get_function_string!(print_improved_webserver_patch),
+            );
+        }
 
         let ai_response: String = ai_task_request_without_markdown(
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_improved_webserver_code),
             print_improved_webserver_code,
         )
-        .await;
+        .await?;
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        let files: FileMap =
+            decode_file_map(&ai_response, get_function_string!(print_improved_webserver_code))?;
+        self.save_files(factsheet, files, get_function_string!(print_improved_webserver_code))
     }
 
-    async fn call_fix_code_bugs(&mut self, factsheet: &mut FactSheet) {
-        let msg_context: String = format!(
-            "BROKEN_CODE: {:?} \n ERROR_BUGS: {:?} \n",
-            factsheet.backend_code, self.bug_count
-        );
+    async fn call_fix_code_bugs(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        let backend_code: FileMap = factsheet.backend_code.clone().unwrap_or_default();
 
-        let ai_response: String = ai_task_request_without_markdown(
-            msg_context,
-            &self.attributes.position,
-            get_function_string!(print_fixed_code),
-            print_fixed_code,
+        let input: FixedCodeInput = FixedCodeInput {
+            current_file_tree: render_file_tree(&backend_code),
+            broken_code: render_file_contents(&backend_code),
+            error_bugs: self.bug_errors.clone(),
+        };
+        let msg_context: String = render_input(&input);
+
+        // with several candidates the compiler picks between full rewrites instead of one patch
+        let candidate_count: u8 = self.candidate_count()?;
+        if candidate_count > 1 {
+            let files: FileMap = self
+                .choose_candidate::<PrintFixedCode>(factsheet, &input, candidate_count)
+                .await?;
+            return self.save_files(factsheet, files, PrintFixedCode::NAME);
+        }
+
+        // the developer can read files and rerun cargo check while working out the fix
+        let code_runner: CodeRunner = self.code_runner(factsheet)?;
+        let mut tools: ToolBox =
+            ToolBox::from_env(WEB_SERVER_PROJECT_PATH)?.with_runner(code_runner.clone());
+
+        // reported bugs are in code that builds and was confirmed safe, so it can be served
+        // for the developer to call its endpoints
+        let mut server: Option<RunningServer> = None;
+        if self.bugs_reported && tools.permits(Tool::HitEndpoint) {
+            match start_web_server(shared_client()?, &code_runner, WEB_SERVER_PORT).await {
+                Ok(running) => server = Some(running),
+                Err(e @ AgentError::CommandFailed { .. }) => {
+                    print_agent_error(self.attributes.position.as_str(), &e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if let Some(running) = &server {
+            tools = tools.with_server(running);
+        }
+
+        let patch_response: Result<String, AgentError> = ai_task_request_with_tools(
+            msg_context.clone(),
+            &mut self.attributes,
+            get_function_string!(print_fixed_code_patch),
+            print_fixed_code_patch,
+            &tools,
         )
         .await;
+        if let Some(mut running) = server {
+            running
+                .kill()
+                .await
+                .map_err(|e| AgentError::io("Failed to kill backend web server", e))?;
+        }
+        let patch_response: String = patch_response?;
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        let locked: FileMap = self.locked_files(factsheet);
+        if let Some(files) = self.apply_patch_response(&backend_code, &locked, &strip_markdown(&patch_response)) {
+            return self.save_files(factsheet, files, get_function_string!(print_fixed_code_patch));
+        }
+
+        let files: FileMap =
+            ai_function_request::<PrintFixedCode>(&input, &mut self.attributes).await?;
+        let files: FileMap = self.check_generated_files::<PrintFixedCode>(factsheet, files)?;
+        self.save_files(factsheet, files, PrintFixedCode::NAME)
     }
 
-    async fn call_extract_api_endpoints(&mut self) -> String {
+    // apply llm edits to the current code, returning None when a full rewrite is needed
+    fn apply_patch_response(
+        &self,
+        backend_code: &FileMap,
+        locked: &FileMap,
+        patch_response: &str,
+    ) -> Option<FileMap> {
+        let patched: Result<FileMap, Vec<PatchRejection>> = parse_edits(patch_response, backend_code)
+            .map_err(|rejection| vec![rejection])
+            .and_then(|edits| apply_edits(backend_code, &edits));
+
+        let rejection_reasons: Vec<String> = match patched {
+            Ok(files) => match validate_file_map(&files).and_then(|_| check_locked_files(&files, locked)) {
+                Ok(()) => return Some(files),
+                Err(e) => vec![e.to_string()],
+            },
+            Err(rejections) => rejections.iter().map(|r| r.to_string()).collect(),
+        };
 
-        dbg!("call_extract_api_endpoints");
-        let backend_code = read_exec_main_contents();
+        for reason in rejection_reasons {
+            PrintCommand::Issue.print_agent_message(
+                self.attributes.position.as_str(),
+                &format!("Patch rejected: {}", reason),
+            );
+        }
+        PrintCommand::Issue.print_agent_message(
+            self.attributes.position.as_str(),
+            "Falling back to a full rewrite of the changed files",
+        );
+        None
+    }
 
-        let msg_context: String = format!("CODE_INPUT: {}", backend_code);
+    // write generated files and keep the factsheet in step with the workspace
+    fn save_files(
+        &mut self,
+        factsheet: &mut FactSheet,
+        files: FileMap,
+        operation: &str,
+    ) -> Result<(), AgentError> {
+        check_locked_files(&files, &self.locked_files(factsheet))?;
+        save_backend_files(&files)?;
+
+        let mut backend_code: FileMap = factsheet.backend_code.take().unwrap_or_default();
+        backend_code.extend(files);
+        factsheet.backend_code = Some(backend_code);
+
+        self.iteration += 1;
+        self.last_operation = operation.to_string();
+        self.record_step(operation);
+        Ok(())
+    }
 
-        let ai_response: String = ai_task_request_without_markdown(
-            msg_context,
-            &self.attributes.position,
-            get_function_string!(print_rest_api_endpoints),
-            print_rest_api_endpoints,
-        )
-        .await;
+    // history is a debugging aid, so failures are reported without stopping the agent
+    fn open_history(&mut self) {
+        match CodeHistory::init(Path::new(WEB_SERVER_PROJECT_PATH)) {
+            Ok(history) => self.history = Some(history),
+            Err(e) => print_agent_error(self.attributes.position.as_str(), &e),
+        }
+    }
+
+    fn record_step(&self, operation: &str) {
+        if let Some(history) = &self.history {
+            if let Err(e) = history.commit_step(&self.attributes.position, operation, self.bug_count) {
+                print_agent_error(self.attributes.position.as_str(), &e);
+            }
+        }
+    }
+
+    // the last tested build is restored before the last one that only built
+    fn tag_green_build(&self, endpoints_passed: bool) {
+        if let Some(history) = &self.history {
+            let tagged: Result<String, AgentError> = if endpoints_passed {
+                history.tag_tested(self.iteration)
+            } else {
+                history.tag_green(self.iteration)
+            };
+            match tagged {
+                Ok(tag) => PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    &format!("Backend code unit testing: tagged build as {}", tag),
+                ),
+                Err(e) => print_agent_error(self.attributes.position.as_str(), &e),
+            }
+        }
+    }
+
+    fn gate_pipeline(&self) -> Result<GatePipeline, AgentError> {
+        match &self.gates {
+            Some(gates) => Ok(gates.clone()),
+            None => GatePipeline::from_env(),
+        }
+    }
+
+    // pick up edits made on disk by auto-fixing gates
+    fn refresh_backend_code(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        let workspace_files: FileMap = read_backend_files()?;
+        if let Some(backend_code) = factsheet.backend_code.as_mut() {
+            for (path, contents) in backend_code.iter_mut() {
+                if let Some(current) = workspace_files.get(path) {
+                    contents.clone_from(current);
+                }
+            }
+        }
+        self.record_step("quality gate auto-fix");
+        Ok(())
+    }
+
+    fn print_gate_report(&self, report: &GateReport) {
+        for result in &report.results {
+            let status: &str = if result.passed { "passed" } else { "failed" };
+            let command: PrintCommand = match (result.passed, result.policy) {
+                (false, GatePolicy::Blocking) => PrintCommand::Issue,
+                _ => PrintCommand::UnitTest,
+            };
+            command.print_agent_message(
+                self.attributes.position.as_str(),
+                &format!(
+                    "Gate {:?} ({:?}) {} with {} finding(s)",
+                    result.gate,
+                    result.policy,
+                    status,
+                    result.findings.len()
+                ),
+            );
+        }
+    }
+
+    // put the last green tag back in place of the abandoned attempts. only the directories
+    // holding backend code go back, files other agents wrote elsewhere are kept
+    fn restore_last_green(&mut self, factsheet: &mut FactSheet) -> Result<Option<u32>, AgentError> {
+        let history: &CodeHistory = match &self.history {
+            Some(history) => history,
+            None => return Ok(None),
+        };
+
+        let mut code_dirs: Vec<String> = factsheet
+            .backend_code
+            .iter()
+            .flat_map(|code| code.keys())
+            .filter_map(|path| path.split('/').next().map(String::from))
+            .collect();
+        code_dirs.dedup();
+        let tag: GreenTag = match history.rollback_to_last_green(&self.attributes.position, &code_dirs)? {
+            Some(tag) => tag,
+            None => return Ok(None),
+        };
+
+        let restored: FileMap = read_backend_files()?
+            .into_iter()
+            .filter(|(path, _)| {
+                code_dirs
+                    .iter()
+                    .any(|dir| path == dir || path.starts_with(&format!("{}/", dir)))
+            })
+            .collect();
+        factsheet.backend_code = Some(restored);
+
+        factsheet.abandoned_attempts.append(&mut self.failed_attempts);
+        factsheet.backend_status = Some(BackendStatus::Degraded {
+            reverted_to_iteration: tag.iteration,
+        });
+        Ok(Some(tag.iteration))
+    }
+
+    async fn call_extract_api_endpoints(&mut self, factsheet: &FactSheet) -> Result<Vec<RouteObject>, AgentError> {
+
+        dbg!("call_extract_api_endpoints");
+        let backend_code: FileMap = factsheet
+            .backend_code
+            .clone()
+            .ok_or(AgentError::MissingFact("backend_code"))?;
+
+        let input: RestApiEndpointsInput = RestApiEndpointsInput {
+            code_input: render_file_contents(&backend_code),
+        };
 
-        ai_response
+        ai_function_request::<PrintRestApiEndpoints>(&input, &mut self.attributes).await
     }
 }
 
@@ -127,25 +692,83 @@ impl SpecialFunctions for AgentBackendDeveloper {
         &self.attributes
     }
 
+    fn fact_access(&self) -> FactAccess {
+        FactAccess {
+            reads: &[
+                FactField::ProjectDescription,
+                FactField::ProjectScope,
+                FactField::ExternalUrls,
+                FactField::DataModel,
+                FactField::BackendCode,
+            ],
+            writes: &[
+                FactField::BackendCode,
+                FactField::ApiEndpointSchema,
+                FactField::BackendStatus,
+                FactField::AbandonedAttempts,
+                FactField::AgentRequests,
+            ],
+            resources: &[Resource::BackendWorkspace],
+        }
+    }
+
+    // a new scope means starting over without the old answers, new urls or data layer only need the code reworked
+    fn handle_fact_changes(&mut self, changed: &[FactField]) -> bool {
+        if changed
+            .iter()
+            .any(|field| matches!(field, FactField::ProjectScope | FactField::ProjectDescription))
+        {
+            self.attributes.clear_memory();
+            self.attributes.state = AgentState::Discovery;
+        } else if changed
+            .iter()
+            .any(|field| matches!(field, FactField::ExternalUrls | FactField::DataModel))
+        {
+            self.bug_count = 0;
+            self.bug_errors = None;
+            self.attributes.state = AgentState::Working;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // failing integration tests come back as bugs to fix in the existing code
+    fn handle_bug_reports(&mut self, reports: &[BugReport]) -> bool {
+        let bug_errors: String = reports
+            .iter()
+            .map(|report| format!("{} reported: {}\n{}", report.reporter, report.summary, report.detail))
+            .collect::<Vec<String>>()
+            .join("\n\n");
+
+        self.bug_errors = Some(bug_errors);
+        self.bug_count = 1;
+        self.bugs_reported = true;
+        self.attributes.state = AgentState::Working;
+        true
+    }
+
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), AgentError> {
         // !!! WARNING - BE CAREFUL OF INFINITE LOOPS !!!
         while self.attributes.state != AgentState::Finished {
             match self.attributes.state {
 
                 AgentState::Discovery => {
-                    self.call_initial_backend_code(factsheet).await;
+                    self.open_history();
+                    self.inject_auth_scaffold(factsheet)?;
+                    self.call_initial_backend_code(factsheet).await?;
                     self.attributes.state = AgentState::Working;
                     continue;
                 }
 
                 AgentState::Working => {
                     if self.bug_count == 0 {
-                        self.call_improved_backend_code(factsheet).await;
+                        self.call_improved_backend_code(factsheet).await?;
                     } else {
-                        self.call_fix_code_bugs(factsheet).await;
+                        self.call_fix_code_bugs(factsheet).await?;
                     }
 
                     self.attributes.state = AgentState::UnitTesting;
@@ -160,40 +783,65 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         "Backend code unit testing: ensuring safe code"
                     );
 
-                    let is_safe_code = confirm_safe_code();
+                    let is_safe_code = confirm_safe_code().await;
 
                     if ! is_safe_code {
-                        panic!("Better go work on some AI alignment instead");
+                        return Err(AgentError::Aborted(
+                            "Better go work on some AI alignment instead".to_string()
+                        ));
                     };
 
                     // build and test code
+                    let code_runner: CodeRunner = self.code_runner(factsheet)?;
+                    if code_runner.mode() == ExecutionMode::Sandboxed {
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Backend code unit testing: running inside sandbox..."
+                        );
+                    }
+
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
-                        "Backend code unit testing: building project..."
+                        "Backend code unit testing: running quality gates..."
                     );
 
-                    let build_backend_server: std::process::Output =
-                        Command::new("cargo")
-                        .arg("build")
-                        .current_dir(WEB_SERVER_PROJECT_PATH)
-                        .stdout(Stdio::piped())
-                        .output()
-                        .expect("Failed to build backed application");
+                    let gate_report: GateReport = self.gate_pipeline()?.run(&code_runner).await?;
+                    self.print_gate_report(&gate_report);
+                    self.refresh_backend_code(factsheet)?;
 
-                    // determine if build errors
-                    if build_backend_server.status.success(){
+                    // determine if any blocking gate failed
+                    if !gate_report.is_blocked() {
                         self.bug_count = 0;
                         PrintCommand::UnitTest.print_agent_message(
                             self.attributes.position.as_str(),
-                            "Backend code unit testing: build successful..."
+                            "Backend code unit testing: quality gates passed..."
                         );
+                        self.tag_green_build(false);
+                        self.failed_attempts.clear();
+
+                        // give advisory findings one round as improvement hints
+                        let advisory_findings: Vec<Finding> = gate_report.advisory_findings();
+                        if !advisory_findings.is_empty()
+                            && !self.degraded
+                            && self.advisory_rounds < MAX_ADVISORY_ROUNDS
+                        {
+                            self.advisory_rounds += 1;
+                            self.improvement_hints = advisory_findings;
+                            self.attributes.state = AgentState::Working;
+                            continue;
+                        }
                     } else {
-                        let error_arr: Vec<u8> = build_backend_server.stderr;
-                        let error_str: String = String::from_utf8(error_arr).unwrap();
+                        let error_str: String = gate_report.render_blocking();
 
                         // update error stats
                         self.bug_count += 1;
-                        self.bug_errors = Some(error_str);
+                        self.failed_attempts.push(AbandonedAttempt {
+                            iteration: self.iteration,
+                            operation: self.last_operation.clone(),
+                            errors: error_str.chars().take(MAX_RECORDED_ERROR_CHARS).collect(),
+                        });
+                        self.bug_errors = Some(error_str.clone());
+                        self.bugs_reported = false;
 
                         // exit if too many bugs
                         if self.bug_count > 2 {
@@ -201,7 +849,31 @@ impl SpecialFunctions for AgentBackendDeveloper {
                                 self.attributes.position.as_str(),
                                 "Backend code unit testing: too many bugs found in code"
                             );
-                            panic!("Error: Too many bugs");
+                            if self.degraded {
+                                return Err(AgentError::TooManyBugs {
+                                    bug_count: self.bug_count,
+                                    errors: error_str,
+                                });
+                            }
+
+                            match self.restore_last_green(factsheet)? {
+                                Some(iteration) => {
+                                    PrintCommand::Issue.print_agent_message(
+                                        self.attributes.position.as_str(),
+                                        &format!("Backend status: degraded: reverted to iteration {}", iteration),
+                                    );
+                                    self.degraded = true;
+                                    self.bug_count = 0;
+                                    self.bug_errors = None;
+                                    continue;
+                                }
+                                None => {
+                                    return Err(AgentError::TooManyBugs {
+                                        bug_count: self.bug_count,
+                                        errors: error_str,
+                                    });
+                                }
+                            }
                         }
                         
                         self.attributes.state = AgentState::Working;
@@ -212,24 +884,15 @@ impl SpecialFunctions for AgentBackendDeveloper {
                      * Extract and Test
                      * Rest API Endpoints
                      */
-                    let api_endpoints_str = self.call_extract_api_endpoints().await;
+                    let api_endpoints: Vec<RouteObject> = self.call_extract_api_endpoints(factsheet).await?;
+                    let api_endpoints_str: String = serde_json::to_string(&api_endpoints).unwrap_or_default();
 
                     dbg!(api_endpoints_str.as_str());
-                    //convert api endpoints into values
-                    let api_endpoints: Vec<RouteObject> =
-                        serde_json::from_str(api_endpoints_str.as_str())
-                        .expect("Failed to decode API Endpoints");
-
-                    let check_endpoints: Vec<RouteObject> = api_endpoints
-                        .iter()
-                        .filter(|&route_object| {
-                            route_object.method == "get" && route_object.is_route_dynamic == "false"
-                        })
-                        .cloned()
-                        .collect();
+
+                    let check_endpoints: Vec<RouteObject> = checkable_endpoints(&api_endpoints);
 
                     //store api endpoints
-                    factsheet.api_endpoint_schema = Some(check_endpoints.clone());
+                    factsheet.api_endpoint_schema = Some(api_endpoints.clone());
 
                     // run backed application
                     PrintCommand::UnitTest.print_agent_message(
@@ -237,25 +900,11 @@ impl SpecialFunctions for AgentBackendDeveloper {
                         "Backend code unit testing: starting web server..."
                     );
 
-                    let mut run_backend_server: std::process::Child =
-                        Command::new("cargo")
-                        .arg("run")
-                        .current_dir(WEB_SERVER_PROJECT_PATH)
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        .spawn()
-                        .expect("Failed to run backed application");
-
-                    // let user know testing on server will take place soon
-                    PrintCommand::UnitTest.print_agent_message(
-                        self.attributes.position.as_str(),
-                        "Backend code unit testing: launching tests on server in 5s..."
-                    );
-
-                    let seconds_sleep: Duration = Duration::from_secs(5);
-                    time::sleep( seconds_sleep ).await;
+                    let mut run_backend_server: RunningServer =
+                        start_web_server(shared_client()?, &code_runner, WEB_SERVER_PORT).await?;
 
                     // check status code
+                    let mut endpoints_passed: bool = true;
                     for endpoint in check_endpoints {
                         
                         let testing_msg: String = format!("Testing endpoint '{}'...", endpoint.route);
@@ -264,18 +913,13 @@ impl SpecialFunctions for AgentBackendDeveloper {
                             testing_msg.as_str()
                         );
 
-                        // create client with timeout
-                        let client : Client = Client::builder()
-                            .timeout(Duration::from_secs(5))
-                            .build()
-                            .unwrap();
-
                         // test url
-                        let url_string = format!("http://localhost:6678{}", endpoint.route);
-                        match check_status_code(&client, &url_string).await {
+                        let url_string = format!("http://localhost:{}{}", WEB_SERVER_PORT, endpoint.route);
+                        match check_status_code(shared_client()?, &url_string).await {
 
                             Ok(status_code) => {
                                 if status_code != 200 {
+                                    endpoints_passed = false;
                                     let error_msg: String = format!("WARNING: Failed to call backend url endpoint {}", endpoint.route);
                                     PrintCommand::Issue.print_agent_message(
                                         self.attributes.position.as_str(),
@@ -286,22 +930,30 @@ impl SpecialFunctions for AgentBackendDeveloper {
                             }
 
                             Err( e ) => {
-                                run_backend_server
-                                    .kill()
-                                    .expect("Failed to kill backend web server");
-
+                                endpoints_passed = false;
                                 let error_msg: String = format!("Errro checking backend {}", e );
                                 
                                 PrintCommand::Issue.print_agent_message(
                                     self.attributes.position.as_str(),
                                     error_msg.as_str()
-                                )
+                                );
+                                break;
                             }
                         }
 
                     }
 
-                    save_api_endpoints( &api_endpoints_str );
+                    save_api_endpoints( &api_endpoints_str )?;
+
+                    if endpoints_passed {
+                        self.tag_green_build(true);
+                    } else {
+                        // a failing endpoint may be down to an external url it depends on
+                        self.request_url_replacements(factsheet).await?;
+                    }
+                    if !self.degraded {
+                        factsheet.backend_status = Some(BackendStatus::Green);
+                    }
 
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
@@ -310,7 +962,8 @@ impl SpecialFunctions for AgentBackendDeveloper {
 
                     run_backend_server
                         .kill()
-                        .expect("Failed to kill backend web server on comp[letion");
+                        .await
+                        .map_err(|e| AgentError::io("Failed to kill backend web server", e))?;
 
                     self.attributes.state = AgentState::Finished;
                 }
@@ -325,11 +978,57 @@ impl SpecialFunctions for AgentBackendDeveloper {
     }
 }
 
+// get routes without path parameters, the only ones that can be called as they are
+fn checkable_endpoints(api_endpoints: &[RouteObject]) -> Vec<RouteObject> {
+    api_endpoints
+        .iter()
+        .filter(|&route_object| {
+            route_object.method == "get" && route_object.is_route_dynamic == "false"
+        })
+        .cloned()
+        .collect()
+}
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::helpers::quality_gates::Gate;
+
+    #[test]
+    fn tests_builder_settings_override_environment() {
+        let gates = GatePipeline::new(vec![(Gate::Check, GatePolicy::Blocking)]);
+        let agent = AgentBackendDeveloper::new()
+            .with_execution_mode(ExecutionMode::Sandboxed)
+            .with_gate_pipeline(gates.clone())
+            .with_candidates(3);
+        assert_eq!(agent.execution_mode().unwrap(), ExecutionMode::Sandboxed);
+        assert_eq!(agent.gate_pipeline().unwrap(), gates);
+        assert_eq!(agent.candidate_count().unwrap(), 3);
+        assert_eq!(
+            AgentBackendDeveloper::new()
+                .with_candidates(0)
+                .candidate_count()
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn tests_rescope_forgets_earlier_answers() {
+        let mut agent = AgentBackendDeveloper::new();
+        agent.attributes.remember(
+            "print_backend_webserver_code",
+            Message::user("todo app"),
+            "fn main() {}".to_string(),
+        );
+
+        assert!(agent.handle_fact_changes(&[FactField::ExternalUrls]));
+        assert_eq!(agent.attributes.get_memory().len(), 2);
+        assert!(agent.handle_fact_changes(&[FactField::ProjectScope]));
+        assert!(agent.attributes.get_memory().is_empty());
+        assert_eq!(agent.attributes.state, AgentState::Discovery);
+    }
 
     #[tokio::test]
     async fn tests_writing_backend_code() {
@@ -0,0 +1,196 @@
+use crate::ai_functions::aifunc_database::{DataEntitiesInput, PrintDataEntities};
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::{
+    ai_function_request, commit_backend_step, manifest_has_dependency, read_backend_manifest,
+    save_backend_files,
+};
+use crate::helpers::persistence::{render_data_layer, validate_entities, StorageBackend};
+use crate::helpers::workspace::FileMap;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agents::agent_architect::ARCHITECT_POSITION;
+use crate::models::agents::agent_traits::{
+    AgentRequest, AgentRequestKind, DataModel, Entity, FactAccess, FactField, FactSheet, Resource,
+    SpecialFunctions,
+};
+use crate::models::general::errors::AgentError;
+
+use async_trait::async_trait;
+
+#[derive(Debug)]
+pub struct AgentDatabaseDesigner {
+    attributes: BasicAgent,
+    storage: Option<StorageBackend>,
+}
+
+impl AgentDatabaseDesigner {
+    pub fn new() -> Self {
+        let attributes: BasicAgent = BasicAgent {
+            objective: "Designs the data model and persistence layer for the backend".to_string(),
+            position: "Database Designer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            storage: None,
+        }
+    }
+
+    // choose where records are stored, otherwise read from the environment
+    pub fn with_storage_backend(mut self, storage: StorageBackend) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    fn storage_backend(&self) -> Result<StorageBackend, AgentError> {
+        let storage: StorageBackend = match self.storage {
+            Some(storage) => storage,
+            None => StorageBackend::from_env()?,
+        };
+
+        // the backend manifest is not editable by agents, so the crate must already be there
+        if let Some(required_crate) = storage.required_crate() {
+            if !manifest_has_dependency(&read_backend_manifest()?, required_crate) {
+                return Err(AgentError::Config(format!(
+                    "{:?} storage needs {} in the backend Cargo.toml",
+                    storage, required_crate
+                )));
+            }
+        }
+
+        Ok(storage)
+    }
+
+    async fn call_data_entities(
+        &mut self,
+        factsheet: &FactSheet,
+    ) -> Result<Vec<Entity>, AgentError> {
+        let input: DataEntitiesInput = DataEntitiesInput {
+            project_description: factsheet.project_description.clone(),
+            project_scope: factsheet.project_scope,
+        };
+
+        let entities: Vec<Entity> =
+            ai_function_request::<PrintDataEntities>(&input, &mut self.attributes).await?;
+
+        validate_entities(&entities)?;
+        Ok(entities)
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDatabaseDesigner {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn fact_access(&self) -> FactAccess {
+        FactAccess {
+            reads: &[FactField::ProjectDescription, FactField::ProjectScope],
+            writes: &[
+                FactField::DataModel,
+                FactField::BackendCode,
+                FactField::AgentRequests,
+            ],
+            resources: &[Resource::BackendWorkspace],
+        }
+    }
+
+    fn handle_fact_changes(&mut self, changed: &[FactField]) -> bool {
+        if !changed.iter().any(|field| {
+            matches!(
+                field,
+                FactField::ProjectScope | FactField::ProjectDescription
+            )
+        }) {
+            return false;
+        }
+        // entities designed for the old scope would be repeated back from memory
+        self.attributes.clear_memory();
+        self.attributes.state = AgentState::Discovery;
+        true
+    }
+
+    async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovery => {
+                    let storage: StorageBackend = self.storage_backend()?;
+                    let entities: Vec<Entity> = self.call_data_entities(factsheet).await?;
+
+                    if entities.is_empty() {
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Data model: nothing needs to be stored",
+                        );
+
+                        // a crud scope with nothing to store is likely a wrong scope
+                        if factsheet
+                            .project_scope
+                            .is_some_and(|scope| scope.is_crud_required)
+                        {
+                            factsheet.agent_requests.push(AgentRequest {
+                                from: self.attributes.position.clone(),
+                                to: ARCHITECT_POSITION.to_string(),
+                                kind: AgentRequestKind::Rescope {
+                                    reason:
+                                        "CRUD is required but the description has nothing to store"
+                                            .to_string(),
+                                },
+                            });
+                        }
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+
+                    let files: FileMap = render_data_layer(storage, &entities);
+                    save_backend_files(&files)?;
+                    commit_backend_step(
+                        &self.attributes.position,
+                        &format!("{:?} data layer", storage),
+                    );
+
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        &format!(
+                            "Data model: {} entities stored as {:?}",
+                            entities.len(),
+                            storage
+                        ),
+                    );
+
+                    factsheet.data_model = Some(DataModel {
+                        storage,
+                        entities,
+                        files: files.keys().cloned().collect(),
+                    });
+                    factsheet
+                        .backend_code
+                        .get_or_insert_with(FileMap::new)
+                        .extend(files);
+                    self.attributes.state = AgentState::Finished;
+                }
+
+                _ => {
+                    self.attributes.state = AgentState::Finished;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_builder_storage_overrides_environment() {
+        let agent: AgentDatabaseDesigner =
+            AgentDatabaseDesigner::new().with_storage_backend(StorageBackend::InMemory);
+        assert_eq!(agent.storage_backend().unwrap(), StorageBackend::InMemory);
+    }
+}
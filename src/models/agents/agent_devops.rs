@@ -0,0 +1,251 @@
+use crate::helpers::auth_scaffold::AUTH_FILE;
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::{
+    commit_backend_step, read_backend_package_name, save_packaging_files, WEB_SERVER_PORT,
+    WEB_SERVER_PROJECT_PATH,
+};
+use crate::helpers::packaging::{
+    has_health_route, lint_dockerfile, render_packaging, PackageSpec, COMPOSE_FILE, DOCKERFILE,
+    HEALTH_ROUTE,
+};
+use crate::helpers::sandbox::find_on_path;
+use crate::helpers::workspace::FileMap;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agents::agent_traits::{
+    ArtifactCheck, BugReport, CheckStatus, DeploymentArtifacts, FactAccess, FactField, FactSheet,
+    Resource, SpecialFunctions,
+};
+use crate::models::general::errors::{AgentError, FailureAction};
+
+use async_trait::async_trait;
+use std::path::Path;
+use std::process::Output;
+use tokio::process::Command;
+
+// times packaging is redone after the developer worked on a missing health check
+const MAX_PACKAGING_ROUNDS: u8 = 2;
+
+#[derive(Debug)]
+pub struct AgentDevOps {
+    attributes: BasicAgent,
+    rounds: u8,
+}
+
+impl AgentDevOps {
+    pub fn new() -> Self {
+        let attributes: BasicAgent = BasicAgent {
+            objective: "Packages the backend as a container with run instructions".to_string(),
+            position: "DevOps Engineer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            rounds: 0,
+        }
+    }
+
+    fn package_spec(&self, factsheet: &FactSheet) -> Result<PackageSpec, AgentError> {
+        let backend_code: &FileMap = factsheet
+            .backend_code
+            .as_ref()
+            .ok_or(AgentError::MissingFact("backend_code"))?;
+
+        Ok(PackageSpec {
+            package_name: read_backend_package_name()?,
+            project_description: factsheet.project_description.clone(),
+            port: WEB_SERVER_PORT,
+            storage: factsheet
+                .data_model
+                .as_ref()
+                .map(|data_model| data_model.storage),
+            has_auth: backend_code.contains_key(AUTH_FILE),
+            has_config: backend_code.keys().any(|path| path.starts_with("config/")),
+            routes: factsheet.api_endpoint_schema.clone().unwrap_or_default(),
+        })
+    }
+
+    // validate with the real tools when they are installed
+    async fn validate(&self, files: &FileMap) -> Vec<ArtifactCheck> {
+        let workspace: &Path = Path::new(WEB_SERVER_PROJECT_PATH);
+
+        let dockerfile_check: ArtifactCheck = if find_on_path("hadolint").is_some() {
+            let mut command = Command::new("hadolint");
+            command.arg(DOCKERFILE).current_dir(workspace);
+            run_check(DOCKERFILE, "hadolint", &mut command).await
+        } else {
+            let problems: Vec<String> = lint_dockerfile(&files[DOCKERFILE]);
+            ArtifactCheck {
+                artifact: DOCKERFILE.to_string(),
+                validator: "built-in lint".to_string(),
+                status: if problems.is_empty() {
+                    CheckStatus::Passed
+                } else {
+                    CheckStatus::Failed
+                },
+                output: problems.join("\n"),
+            }
+        };
+
+        let compose_available: bool = find_on_path("docker").is_some()
+            && Command::new("docker")
+                .args(["compose", "version"])
+                .output()
+                .await
+                .is_ok_and(|output| output.status.success());
+
+        let compose_check: ArtifactCheck = if compose_available {
+            let mut command = Command::new("docker");
+            command
+                .args(["compose", "-f", COMPOSE_FILE, "config", "--quiet"])
+                .current_dir(workspace);
+            run_check(COMPOSE_FILE, "docker compose config", &mut command).await
+        } else {
+            ArtifactCheck {
+                artifact: COMPOSE_FILE.to_string(),
+                validator: "docker compose config".to_string(),
+                status: CheckStatus::Skipped,
+                output: "docker compose is not installed".to_string(),
+            }
+        };
+
+        vec![dockerfile_check, compose_check]
+    }
+}
+
+async fn run_check(artifact: &str, validator: &str, command: &mut Command) -> ArtifactCheck {
+    let (status, output) = match command.output().await {
+        Ok(output) => {
+            let Output {
+                status,
+                stdout,
+                stderr,
+            } = output;
+            let text: String = format!(
+                "{}{}",
+                String::from_utf8_lossy(&stdout),
+                String::from_utf8_lossy(&stderr)
+            );
+            let check_status = if status.success() {
+                CheckStatus::Passed
+            } else {
+                CheckStatus::Failed
+            };
+            (check_status, text.trim().to_string())
+        }
+        Err(e) => (
+            CheckStatus::Skipped,
+            format!("failed to run {}: {}", validator, e),
+        ),
+    };
+
+    ArtifactCheck {
+        artifact: artifact.to_string(),
+        validator: validator.to_string(),
+        status,
+        output,
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDevOps {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn fact_access(&self) -> FactAccess {
+        FactAccess {
+            reads: &[
+                FactField::ProjectDescription,
+                FactField::BackendCode,
+                FactField::ApiEndpointSchema,
+                FactField::BackendStatus,
+                FactField::DataModel,
+            ],
+            writes: &[FactField::Deployment, FactField::BugReports],
+            resources: &[Resource::BackendWorkspace],
+        }
+    }
+
+    // the packaging is rendered from everything it reads, so any change means packaging again
+    fn handle_fact_changes(&mut self, _changed: &[FactField]) -> bool {
+        self.attributes.state = AgentState::Discovery;
+        true
+    }
+
+    // package again once the developer has added the missing health check
+    fn handle_bug_reports(&mut self, reports: &[BugReport]) -> bool {
+        let own_reports: bool = reports
+            .iter()
+            .any(|report| report.reporter == self.attributes.position);
+        if !own_reports || self.rounds >= MAX_PACKAGING_ROUNDS {
+            return false;
+        }
+        self.attributes.state = AgentState::Discovery;
+        true
+    }
+
+    // the code still ships without a container
+    fn on_failure(&self, error: &AgentError) -> FailureAction {
+        FailureAction::skip_unless_aborted(error)
+    }
+
+    async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovery => {
+                    if factsheet.backend_status.is_none() {
+                        return Err(AgentError::MissingFact("backend_status"));
+                    }
+                    self.rounds += 1;
+
+                    let spec: PackageSpec = self.package_spec(factsheet)?;
+                    let files: FileMap = render_packaging(&spec);
+                    save_packaging_files(&files)?;
+                    commit_backend_step(&self.attributes.position, "container packaging");
+
+                    let checks: Vec<ArtifactCheck> = self.validate(&files).await;
+                    for check in &checks {
+                        let command: PrintCommand = match check.status {
+                            CheckStatus::Failed => PrintCommand::Issue,
+                            CheckStatus::Passed | CheckStatus::Skipped => PrintCommand::UnitTest,
+                        };
+                        command.print_agent_message(
+                            self.attributes.position.as_str(),
+                            &format!(
+                                "Packaging: {} {:?} by {}",
+                                check.artifact, check.status, check.validator
+                            ),
+                        );
+                    }
+
+                    // the container health check needs a route to call
+                    if !has_health_route(&spec.routes) {
+                        factsheet.bug_reports.push(BugReport {
+                            reporter: self.attributes.position.clone(),
+                            summary: format!("missing GET {} route", HEALTH_ROUTE),
+                            detail: format!(
+                                "Add GET {} returning 200 with {{\"status\": \"ok\"}} and register it in configure. The Docker HEALTHCHECK calls it.",
+                                HEALTH_ROUTE
+                            ),
+                        });
+                    }
+
+                    factsheet.deployment = Some(DeploymentArtifacts {
+                        files: files.keys().cloned().collect(),
+                        port: spec.port,
+                        checks,
+                    });
+                    self.attributes.state = AgentState::Finished;
+                }
+
+                _ => {
+                    self.attributes.state = AgentState::Finished;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
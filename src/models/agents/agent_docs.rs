@@ -0,0 +1,176 @@
+use crate::apis::http_client::shared_client;
+use crate::helpers::api_docs::{
+    matches_schema, render_api_reference, ApiDocSpec, EndpointCheck, API_DOCS_FILE,
+};
+use crate::helpers::command_line::{confirm_safe_code, print_agent_error, PrintCommand};
+use crate::helpers::general::{
+    commit_backend_step, fetch_json, save_backend_files, start_web_server, WEB_SERVER_PORT,
+    WEB_SERVER_PROJECT_PATH,
+};
+use crate::helpers::sandbox::{CodeRunner, ExecutionMode, RunningServer};
+use crate::helpers::workspace::FileMap;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agents::agent_traits::{
+    FactAccess, FactField, FactSheet, Resource, RouteObject, SpecialFunctions,
+};
+use crate::models::general::errors::{AgentError, FailureAction};
+
+use async_trait::async_trait;
+use reqwest::Client;
+
+#[derive(Debug)]
+pub struct AgentDocsWriter {
+    attributes: BasicAgent,
+}
+
+impl AgentDocsWriter {
+    pub fn new() -> Self {
+        let attributes: BasicAgent = BasicAgent {
+            objective: "Writes the API reference for the generated backend".to_string(),
+            position: "Technical Writer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self { attributes }
+    }
+
+    // call the documented GET routes on a running server, None when it cannot be run
+    async fn check_endpoints(
+        &self,
+        factsheet: &FactSheet,
+        routes: &[RouteObject],
+    ) -> Option<Vec<EndpointCheck>> {
+        PrintCommand::UnitTest.print_agent_message(
+            self.attributes.position.as_str(),
+            "Docs: checking examples against the running server",
+        );
+        if !confirm_safe_code().await {
+            return None;
+        }
+
+        let client: &Client = shared_client().ok()?;
+        let mut server: RunningServer = match start_server(client, factsheet).await {
+            Ok(server) => server,
+            Err(e) => {
+                print_agent_error(self.attributes.position.as_str(), &e);
+                return None;
+            }
+        };
+
+        let mut checks: Vec<EndpointCheck> = vec![];
+        for route in routes.iter().filter(|route| {
+            route.method.eq_ignore_ascii_case("get") && route.is_route_dynamic == "false"
+        }) {
+            let url: String = format!("http://localhost:{}{}", WEB_SERVER_PORT, route.route);
+            let response: Option<(u16, serde_json::Value)> = fetch_json(client, &url).await.ok();
+
+            checks.push(EndpointCheck {
+                method: route.method.clone(),
+                route: route.route.clone(),
+                status: response.as_ref().map(|(status, _)| *status),
+                matches_schema: response
+                    .is_some_and(|(_, body)| matches_schema(&route.response, &body)),
+            });
+        }
+
+        if let Err(e) = server.kill().await {
+            print_agent_error(
+                self.attributes.position.as_str(),
+                &AgentError::io("Failed to kill backend web server", e),
+            );
+        }
+        Some(checks)
+    }
+}
+
+async fn start_server(client: &Client, factsheet: &FactSheet) -> Result<RunningServer, AgentError> {
+    let external_urls: Vec<String> = factsheet.external_urls.clone().unwrap_or_default();
+    let code_runner: CodeRunner = CodeRunner::new(
+        ExecutionMode::from_env()?,
+        WEB_SERVER_PROJECT_PATH,
+        &external_urls,
+    )?;
+    start_web_server(client, &code_runner, WEB_SERVER_PORT).await
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDocsWriter {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn fact_access(&self) -> FactAccess {
+        FactAccess {
+            reads: &[
+                FactField::ProjectDescription,
+                FactField::ProjectScope,
+                FactField::ExternalUrls,
+                FactField::ApiEndpointSchema,
+            ],
+            writes: &[],
+            resources: &[Resource::BackendWorkspace],
+        }
+    }
+
+    // only asked about fields it reads, and all of them end up in the docs
+    fn handle_fact_changes(&mut self, _changed: &[FactField]) -> bool {
+        self.attributes.state = AgentState::Discovery;
+        true
+    }
+
+    // the code still ships without an api reference
+    fn on_failure(&self, error: &AgentError) -> FailureAction {
+        FailureAction::skip_unless_aborted(error)
+    }
+
+    async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovery => {
+                    let routes: Vec<RouteObject> = factsheet
+                        .api_endpoint_schema
+                        .clone()
+                        .ok_or(AgentError::MissingFact("api_endpoint_schema"))?;
+
+                    let checks: Option<Vec<EndpointCheck>> =
+                        self.check_endpoints(factsheet, &routes).await;
+                    if let Some(checks) = &checks {
+                        let verified: usize =
+                            checks.iter().filter(|check| check.is_verified()).count();
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            &format!(
+                                "Docs: {} of {} GET examples verified",
+                                verified,
+                                checks.len()
+                            ),
+                        );
+                    }
+
+                    let spec = ApiDocSpec {
+                        project_description: factsheet.project_description.clone(),
+                        project_scope: factsheet.project_scope,
+                        external_urls: factsheet.external_urls.clone().unwrap_or_default(),
+                        routes,
+                        base_url: format!("http://localhost:{}", WEB_SERVER_PORT),
+                    };
+                    let docs: FileMap = FileMap::from([(
+                        API_DOCS_FILE.to_string(),
+                        render_api_reference(&spec, checks.as_deref()),
+                    )]);
+                    save_backend_files(&docs)?;
+                    commit_backend_step(&self.attributes.position, "api reference");
+
+                    self.attributes.state = AgentState::Finished;
+                }
+
+                _ => {
+                    self.attributes.state = AgentState::Finished;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use crate::ai_functions::aifunc_reviewer::{CodeReviewInput, PrintCodeReview};
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::ai_function_request;
+use crate::helpers::workspace::{render_file_contents, FileMap};
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agents::agent_traits::{
+    BugReport, CodeReview, FactAccess, FactField, FactSheet, ProjectScope, ReviewCategory,
+    ReviewIssue, ReviewSeverity, RouteObject, SpecialFunctions,
+};
+use crate::models::general::errors::{AgentError, FailureAction};
+
+use async_trait::async_trait;
+
+// reviews per project, the first plus re-reviews after the developer addressed them
+const MAX_REVIEWS: u8 = 2;
+
+// issues at or above this severity are handed back to the backend developer
+const BLOCKING_SEVERITY: ReviewSeverity = ReviewSeverity::Medium;
+
+#[derive(Debug)]
+pub struct AgentCodeReviewer {
+    attributes: BasicAgent,
+    reviews: u8,
+}
+
+impl AgentCodeReviewer {
+    pub fn new() -> Self {
+        let attributes: BasicAgent = BasicAgent {
+            objective: "Reviews the backend code against the project scope".to_string(),
+            position: "Code Reviewer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            reviews: 0,
+        }
+    }
+
+    async fn call_code_review(&mut self, factsheet: &FactSheet) -> Result<CodeReview, AgentError> {
+        let project_scope: ProjectScope = factsheet
+            .project_scope
+            .ok_or(AgentError::MissingFact("project_scope"))?;
+        let backend_code: &FileMap = factsheet
+            .backend_code
+            .as_ref()
+            .ok_or(AgentError::MissingFact("backend_code"))?;
+        let routes: Vec<RouteObject> = factsheet.api_endpoint_schema.clone().unwrap_or_default();
+
+        let input: CodeReviewInput = CodeReviewInput {
+            project_description: factsheet.project_description.clone(),
+            project_scope,
+            api_endpoints: routes.clone(),
+            code_files: render_file_contents(backend_code),
+        };
+
+        let mut review: CodeReview =
+            ai_function_request::<PrintCodeReview>(&input, &mut self.attributes).await?;
+
+        // gaps visible from the route list do not depend on the model noticing them
+        let scope_issues: Vec<ReviewIssue> = scope_gaps(&project_scope, &routes);
+        if !scope_issues.is_empty() {
+            review.approved = false;
+            review.issues.extend(scope_issues);
+        }
+
+        self.reviews += 1;
+        Ok(review)
+    }
+}
+
+// requirements from the project scope with no matching route
+fn scope_gaps(scope: &ProjectScope, routes: &[RouteObject]) -> Vec<ReviewIssue> {
+    let has_route = |name: &str| routes.iter().any(|r| r.route.to_lowercase().contains(name));
+    let has_method = |method: &str| routes.iter().any(|r| r.method.eq_ignore_ascii_case(method));
+
+    let mut missing: Vec<String> = vec![];
+    if scope.is_user_login_and_logout {
+        missing.extend(
+            ["login", "logout"]
+                .iter()
+                .filter(|name| !has_route(name))
+                .map(|name| {
+                    format!(
+                        "no {} route although users must be able to log in and out",
+                        name
+                    )
+                }),
+        );
+    }
+    if scope.is_crud_required {
+        missing.extend(
+            ["post", "get", "put", "delete"]
+                .iter()
+                .filter(|method| !has_method(method))
+                .map(|method| {
+                    format!(
+                        "no {} route although CRUD is required",
+                        method.to_uppercase()
+                    )
+                }),
+        );
+    }
+
+    missing
+        .into_iter()
+        .map(|description| ReviewIssue {
+            category: ReviewCategory::MissingRequirement,
+            severity: ReviewSeverity::High,
+            file: None,
+            description,
+        })
+        .collect()
+}
+
+fn bug_reports_from_review(reporter: &str, review: &CodeReview) -> Vec<BugReport> {
+    review
+        .issues
+        .iter()
+        .filter(|issue| issue.severity >= BLOCKING_SEVERITY)
+        .map(|issue| BugReport {
+            reporter: reporter.to_string(),
+            summary: format!("code review: {:?}", issue.category),
+            detail: issue.to_string(),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentCodeReviewer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn fact_access(&self) -> FactAccess {
+        FactAccess {
+            reads: &[
+                FactField::ProjectDescription,
+                FactField::ProjectScope,
+                FactField::BackendCode,
+                FactField::ApiEndpointSchema,
+            ],
+            writes: &[FactField::CodeReview, FactField::BugReports],
+            resources: &[],
+        }
+    }
+
+    // review again when the code changed, up to the review limit
+    fn handle_fact_changes(&mut self, changed: &[FactField]) -> bool {
+        let code_changed: bool = changed
+            .iter()
+            .any(|field| matches!(field, FactField::BackendCode | FactField::ApiEndpointSchema));
+        if !code_changed || self.reviews >= MAX_REVIEWS {
+            return false;
+        }
+        self.attributes.state = AgentState::Working;
+        true
+    }
+
+    // look at the code again once the developer has worked on this reviewer's issues
+    fn handle_bug_reports(&mut self, reports: &[BugReport]) -> bool {
+        let own_reports: bool = reports
+            .iter()
+            .any(|report| report.reporter == self.attributes.position);
+        if !own_reports || self.reviews >= MAX_REVIEWS {
+            return false;
+        }
+        self.attributes.state = AgentState::Working;
+        true
+    }
+
+    // the code still ships without a review
+    fn on_failure(&self, error: &AgentError) -> FailureAction {
+        FailureAction::skip_unless_aborted(error)
+    }
+
+    async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovery | AgentState::Working => {
+                    let review: CodeReview = self.call_code_review(factsheet).await?;
+                    let bug_reports: Vec<BugReport> =
+                        bug_reports_from_review(&self.attributes.position, &review);
+
+                    if bug_reports.is_empty() {
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            &format!(
+                                "Code review: approved with {} minor issue(s)",
+                                review.issues.len()
+                            ),
+                        );
+                    } else {
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            &format!(
+                                "Code review: {} issue(s) must be addressed",
+                                bug_reports.len()
+                            ),
+                        );
+                    }
+
+                    factsheet.bug_reports.extend(bug_reports);
+                    factsheet.code_review = Some(review);
+                    self.attributes.state = AgentState::Finished;
+                }
+
+                _ => {
+                    self.attributes.state = AgentState::Finished;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(method: &str, path: &str) -> RouteObject {
+        RouteObject {
+            is_route_dynamic: "false".to_string(),
+            method: method.to_string(),
+            request_body: serde_json::Value::Null,
+            response: serde_json::Value::Null,
+            route: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn tests_scope_gaps_become_bug_reports() {
+        let scope = ProjectScope {
+            is_crud_required: false,
+            is_user_login_and_logout: true,
+            is_external_urls_required: false,
+        };
+        let routes = vec![route("post", "/api/login"), route("get", "/api/contacts")];
+
+        let issues: Vec<ReviewIssue> = scope_gaps(&scope, &routes);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].description.starts_with("no logout route"));
+
+        let mut review = CodeReview {
+            approved: false,
+            issues,
+        };
+        review.issues.push(ReviewIssue {
+            category: ReviewCategory::ErrorHandling,
+            severity: ReviewSeverity::Low,
+            file: Some("src/routes.rs".to_string()),
+            description: "unwrap on request body".to_string(),
+        });
+
+        let reports: Vec<BugReport> = bug_reports_from_review("Code Reviewer", &review);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].summary, "code review: MissingRequirement");
+    }
+}
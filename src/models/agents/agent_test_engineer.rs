@@ -0,0 +1,286 @@
+use crate::ai_functions::aifunc_test_engineer::{IntegrationTestsInput, PrintIntegrationTests};
+use crate::ai_functions::typed_function::AiFunction;
+use crate::helpers::command_line::{confirm_safe_code, PrintCommand};
+use crate::helpers::general::{
+    ai_function_request, commit_backend_step, read_backend_crate_name, save_backend_files,
+    WEB_SERVER_PROJECT_PATH,
+};
+use crate::helpers::quality_gates::{
+    render_findings, Finding, Gate, GatePipeline, GatePolicy, GateReport,
+};
+use crate::helpers::sandbox::{CodeRunner, ExecutionMode};
+use crate::helpers::workspace::{render_file_contents, validate_file_map, FileMap};
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agents::agent_traits::{
+    BugReport, FactAccess, FactField, FactSheet, Resource, SpecialFunctions,
+};
+use crate::models::general::errors::AgentError;
+
+use async_trait::async_trait;
+
+// directory every generated integration test is written to
+const TESTS_DIR: &str = "tests/";
+
+// times the tests are regenerated because they themselves do not compile
+const MAX_TEST_REWRITES: u8 = 2;
+
+#[derive(Debug)]
+pub struct AgentTestEngineer {
+    attributes: BasicAgent,
+    test_files: Option<FileMap>,
+    test_errors: Option<String>,
+    rewrites: u8,
+}
+
+impl AgentTestEngineer {
+    pub fn new() -> Self {
+        let attributes: BasicAgent = BasicAgent {
+            objective: "Writes and runs integration tests against the backend api".to_string(),
+            position: "Test Engineer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            test_files: None,
+            test_errors: None,
+            rewrites: 0,
+        }
+    }
+
+    async fn call_write_tests(&mut self, factsheet: &FactSheet) -> Result<(), AgentError> {
+        let api_endpoints = factsheet
+            .api_endpoint_schema
+            .as_ref()
+            .ok_or(AgentError::MissingFact("api_endpoint_schema"))?;
+        let backend_code: &FileMap = factsheet
+            .backend_code
+            .as_ref()
+            .ok_or(AgentError::MissingFact("backend_code"))?;
+
+        // the previous tests only go along with the errors they caused
+        let (current_tests, test_errors) = match (&self.test_files, &self.test_errors) {
+            (Some(test_files), Some(test_errors)) => (
+                Some(render_file_contents(test_files)),
+                Some(test_errors.clone()),
+            ),
+            _ => (None, None),
+        };
+        let input: IntegrationTestsInput = IntegrationTestsInput {
+            api_endpoints: api_endpoints.clone(),
+            code_files: render_file_contents(backend_code),
+            crate_name: read_backend_crate_name()?,
+            current_tests,
+            test_errors,
+        };
+
+        let files: FileMap =
+            ai_function_request::<PrintIntegrationTests>(&input, &mut self.attributes).await?;
+        validate_file_map(&files)?;
+        if let Some(path) = files.keys().find(|path| !path.starts_with(TESTS_DIR)) {
+            return Err(AgentError::InvalidFiles(format!(
+                "{}: integration tests may only be written inside {}",
+                path, TESTS_DIR
+            )));
+        }
+
+        save_backend_files(&files)?;
+        commit_backend_step(&self.attributes.position, PrintIntegrationTests::NAME);
+        self.test_files = Some(files);
+        self.test_errors = None;
+        Ok(())
+    }
+
+    async fn run_tests(&self, factsheet: &FactSheet) -> Result<GateReport, AgentError> {
+        let external_urls: Vec<String> = factsheet.external_urls.clone().unwrap_or_default();
+        let code_runner: CodeRunner = CodeRunner::new(
+            ExecutionMode::from_env()?,
+            WEB_SERVER_PROJECT_PATH,
+            &external_urls,
+        )?;
+
+        GatePipeline::new(vec![(Gate::Test, GatePolicy::Blocking)])
+            .run(&code_runner)
+            .await
+    }
+}
+
+// compiler errors inside the generated tests are the test engineer's own to fix
+fn is_test_file_error(finding: &Finding) -> bool {
+    finding
+        .file
+        .as_deref()
+        .is_some_and(|file| file.starts_with(TESTS_DIR))
+}
+
+fn bug_report_from_finding(reporter: &str, finding: &Finding) -> BugReport {
+    BugReport {
+        reporter: reporter.to_string(),
+        summary: finding
+            .message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        detail: finding
+            .detail
+            .clone()
+            .unwrap_or_else(|| finding.to_string()),
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentTestEngineer {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn fact_access(&self) -> FactAccess {
+        FactAccess {
+            reads: &[
+                FactField::ExternalUrls,
+                FactField::BackendCode,
+                FactField::ApiEndpointSchema,
+            ],
+            writes: &[FactField::BugReports],
+            resources: &[Resource::BackendWorkspace],
+        }
+    }
+
+    // new routes or code need new tests rather than a rerun of the old ones
+    fn handle_fact_changes(&mut self, changed: &[FactField]) -> bool {
+        if !changed
+            .iter()
+            .any(|field| matches!(field, FactField::BackendCode | FactField::ApiEndpointSchema))
+        {
+            return false;
+        }
+        self.test_errors = None;
+        self.rewrites = 0;
+        self.attributes.state = AgentState::Working;
+        true
+    }
+
+    // rerun the existing tests once the reported bugs have been worked on
+    fn handle_bug_reports(&mut self, _reports: &[BugReport]) -> bool {
+        if self.test_files.is_none() {
+            return false;
+        }
+        self.attributes.state = AgentState::UnitTesting;
+        true
+    }
+
+    async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        while self.attributes.state != AgentState::Finished {
+            match self.attributes.state {
+                AgentState::Discovery | AgentState::Working => {
+                    self.call_write_tests(factsheet).await?;
+                    self.attributes.state = AgentState::UnitTesting;
+                }
+
+                AgentState::UnitTesting => {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Integration testing: ensuring safe code",
+                    );
+                    if !confirm_safe_code().await {
+                        return Err(AgentError::Aborted(
+                            "Integration tests were not approved to run".to_string(),
+                        ));
+                    }
+
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Integration testing: running cargo test...",
+                    );
+                    let report: GateReport = self.run_tests(factsheet).await?;
+
+                    if !report.is_blocked() {
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Integration testing: all tests passed",
+                        );
+                        self.attributes.state = AgentState::Finished;
+                        continue;
+                    }
+
+                    let findings: Vec<Finding> = report
+                        .blocking_failures()
+                        .into_iter()
+                        .flat_map(|result| result.findings.iter().cloned())
+                        .collect();
+
+                    // broken tests are rewritten, anything else goes back to the developer
+                    let test_file_errors: Vec<&Finding> =
+                        findings.iter().filter(|f| is_test_file_error(f)).collect();
+                    if !test_file_errors.is_empty() && self.rewrites < MAX_TEST_REWRITES {
+                        self.rewrites += 1;
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Integration testing: tests do not compile, rewriting them",
+                        );
+                        self.test_errors = Some(render_findings(test_file_errors.into_iter()));
+                        self.attributes.state = AgentState::Working;
+                        continue;
+                    }
+
+                    PrintCommand::Issue.print_agent_message(
+                        self.attributes.position.as_str(),
+                        &format!("Integration testing: reporting {} bug(s)", findings.len()),
+                    );
+                    factsheet.bug_reports.extend(findings.iter().map(|finding| {
+                        bug_report_from_finding(&self.attributes.position, finding)
+                    }));
+                    self.attributes.state = AgentState::Finished;
+                }
+
+                _ => {
+                    self.attributes.state = AgentState::Finished;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(file: Option<&str>, message: &str) -> Finding {
+        Finding {
+            gate: Gate::Test,
+            level: "error".to_string(),
+            message: message.to_string(),
+            file: file.map(String::from),
+            line: None,
+            code: None,
+            detail: None,
+        }
+    }
+
+    #[test]
+    fn tests_separates_broken_tests_from_backend_bugs() {
+        assert!(is_test_file_error(&finding(
+            Some("tests/api.rs"),
+            "cannot find value"
+        )));
+        assert!(!is_test_file_error(&finding(
+            Some("src/routes.rs"),
+            "mismatched types"
+        )));
+        assert!(!is_test_file_error(&finding(None, "test get_users failed")));
+
+        let report: BugReport = bug_report_from_finding(
+            "Test Engineer",
+            &finding(None, "test get_users failed\nat line 3"),
+        );
+        assert_eq!(report.summary, "test get_users failed");
+        assert_eq!(
+            report.detail,
+            "[test error] test get_users failed\nat line 3"
+        );
+    }
+}
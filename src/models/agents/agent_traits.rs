@@ -1,4 +1,8 @@
+use crate::helpers::persistence::StorageBackend;
+use crate::helpers::workspace::FileMap;
 use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::general::errors::{AgentError, FailureAction};
+use crate::{ai_output_enum, ai_output_struct};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
@@ -10,6 +14,12 @@ pub struct ProjectScope {
     pub is_external_urls_required: bool,
 }
 
+ai_output_struct!(ProjectScope {
+    is_crud_required: bool,
+    is_user_login_and_logout: bool,
+    is_external_urls_required: bool,
+});
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct RouteObject {
     pub is_route_dynamic: String,
@@ -19,23 +29,415 @@ pub struct RouteObject {
     pub route: String,
 }
 
+ai_output_struct!(RouteObject {
+    is_route_dynamic: String,
+    method: String,
+    request_body: serde_json::Value,
+    response: serde_json::Value,
+    route: String,
+});
+
+// a code version the backend developer gave up on
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AbandonedAttempt {
+    pub iteration: u32,
+    pub operation: String,
+    pub errors: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum BackendStatus {
+    Green,
+    Degraded { reverted_to_iteration: u32 },
+}
+
+impl std::fmt::Display for BackendStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Green => write!(f, "green"),
+            Self::Degraded {
+                reverted_to_iteration,
+            } => write!(f, "degraded: reverted to iteration {}", reverted_to_iteration),
+        }
+    }
+}
+
+// a problem found by one agent for another agent to fix
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BugReport {
+    pub reporter: String,
+    pub summary: String,
+    pub detail: String,
+}
+
+// what one agent asks another agent to change
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AgentRequestKind {
+    // the project scope does not fit what has to be built
+    Rescope { reason: String },
+    // an external url cannot be used and needs a replacement
+    ReplaceUrl { url: String, reason: String },
+    // something the project description is missing
+    AddRequirement { requirement: String },
+}
+
+impl std::fmt::Display for AgentRequestKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rescope { reason } => write!(f, "re-scope: {}", reason),
+            Self::ReplaceUrl { url, reason } => write!(f, "replace {}: {}", url, reason),
+            Self::AddRequirement { requirement } => write!(f, "add requirement: {}", requirement),
+        }
+    }
+}
+
+// a request raised by one agent for the manager to deliver to another
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentRequest {
+    pub from: String,
+    pub to: String,
+    pub kind: AgentRequestKind,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewCategory {
+    MissingRequirement,
+    Security,
+    ErrorHandling,
+    Other,
+}
+
+ai_output_enum!(ReviewCategory {
+    MissingRequirement,
+    Security,
+    ErrorHandling,
+    Other,
+});
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewSeverity {
+    Low,
+    Medium,
+    High,
+}
+
+ai_output_enum!(ReviewSeverity { Low, Medium, High });
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReviewIssue {
+    pub category: ReviewCategory,
+    pub severity: ReviewSeverity,
+    pub file: Option<String>,
+    pub description: String,
+}
+
+ai_output_struct!(ReviewIssue {
+    category: ReviewCategory,
+    severity: ReviewSeverity,
+    file: Option<String>,
+    description: String,
+});
+
+impl std::fmt::Display for ReviewIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:?} {:?}]", self.category, self.severity)?;
+        if let Some(file) = &self.file {
+            write!(f, " {}", file)?;
+        }
+        write!(f, " {}", self.description)
+    }
+}
+
+// an independent review of the backend code against the project scope
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CodeReview {
+    pub approved: bool,
+    pub issues: Vec<ReviewIssue>,
+}
+
+ai_output_struct!(CodeReview {
+    approved: bool,
+    issues: Vec<ReviewIssue>,
+});
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+ai_output_enum!(FieldType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+});
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EntityField {
+    pub name: String,
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub optional: bool,
+}
+
+ai_output_struct!(EntityField {
+    name: String,
+    field_type: FieldType,
+    optional: bool,
+});
+
+// a stored record type, every entity also gets a numeric id
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Entity {
+    pub name: String,
+    pub fields: Vec<EntityField>,
+}
+
+ai_output_struct!(Entity {
+    name: String,
+    fields: Vec<EntityField>,
+});
+
+// the persistence layer written for the backend developer to build on
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DataModel {
+    pub storage: StorageBackend,
+    pub entities: Vec<Entity>,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    // the validating tool is not installed
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ArtifactCheck {
+    pub artifact: String,
+    pub validator: String,
+    pub status: CheckStatus,
+    pub output: String,
+}
+
+// the files that package the backend as a container
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DeploymentArtifacts {
+    pub files: Vec<String>,
+    pub port: u16,
+    pub checks: Vec<ArtifactCheck>,
+}
+
+// a factsheet field an agent can read or write
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FactField {
+    ProjectDescription,
+    ProjectScope,
+    ExternalUrls,
+    BackendCode,
+    ApiEndpointSchema,
+    BackendStatus,
+    AbandonedAttempts,
+    BugReports,
+    CodeReview,
+    DataModel,
+    Deployment,
+    AgentRequests,
+}
+
+impl FactField {
+    pub const ALL: [FactField; 12] = [
+        Self::ProjectDescription,
+        Self::ProjectScope,
+        Self::ExternalUrls,
+        Self::BackendCode,
+        Self::ApiEndpointSchema,
+        Self::BackendStatus,
+        Self::AbandonedAttempts,
+        Self::BugReports,
+        Self::CodeReview,
+        Self::DataModel,
+        Self::Deployment,
+        Self::AgentRequests,
+    ];
+
+    // fields several agents may add to at the same time
+    pub fn is_append_only(&self) -> bool {
+        matches!(self, Self::BugReports | Self::AgentRequests)
+    }
+}
+
+impl std::fmt::Display for FactField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name: &str = match self {
+            Self::ProjectDescription => "project_description",
+            Self::ProjectScope => "project_scope",
+            Self::ExternalUrls => "external_urls",
+            Self::BackendCode => "backend_code",
+            Self::ApiEndpointSchema => "api_endpoint_schema",
+            Self::BackendStatus => "backend_status",
+            Self::AbandonedAttempts => "abandoned_attempts",
+            Self::BugReports => "bug_reports",
+            Self::CodeReview => "code_review",
+            Self::DataModel => "data_model",
+            Self::Deployment => "deployment",
+            Self::AgentRequests => "agent_requests",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// something outside the factsheet that only one agent may use at a time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resource {
+    // the backend project: its files, cargo's build directory and the web server port
+    BackendWorkspace,
+}
+
+// the factsheet fields an agent depends on and the ones it fills in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FactAccess {
+    pub reads: &'static [FactField],
+    pub writes: &'static [FactField],
+    pub resources: &'static [Resource],
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FactSheet {
     pub project_description: String,
     pub project_scope: Option<ProjectScope>,
     pub external_urls: Option<Vec<String>>,
-    pub backend_code: Option<String>,
+    pub backend_code: Option<FileMap>,
     pub api_endpoint_schema: Option<Vec<RouteObject>>,
+    #[serde(default)]
+    pub backend_status: Option<BackendStatus>,
+    #[serde(default)]
+    pub abandoned_attempts: Vec<AbandonedAttempt>,
+    #[serde(default)]
+    pub bug_reports: Vec<BugReport>,
+    #[serde(default)]
+    pub code_review: Option<CodeReview>,
+    #[serde(default)]
+    pub data_model: Option<DataModel>,
+    #[serde(default)]
+    pub deployment: Option<DeploymentArtifacts>,
+    #[serde(default)]
+    pub agent_requests: Vec<AgentRequest>,
+}
+
+impl FactSheet {
+    pub fn new(project_description: String) -> Self {
+        Self {
+            project_description,
+            project_scope: None,
+            external_urls: None,
+            backend_code: None,
+            api_endpoint_schema: None,
+            backend_status: None,
+            abandoned_attempts: vec![],
+            bug_reports: vec![],
+            code_review: None,
+            data_model: None,
+            deployment: None,
+            agent_requests: vec![],
+        }
+    }
+
+    // fields whose value differs from an earlier copy of the factsheet
+    pub fn changed_fields(&self, base: &FactSheet) -> Vec<FactField> {
+        FactField::ALL
+            .into_iter()
+            .filter(|field| match field {
+                FactField::ProjectDescription => self.project_description != base.project_description,
+                FactField::ProjectScope => self.project_scope != base.project_scope,
+                FactField::ExternalUrls => self.external_urls != base.external_urls,
+                FactField::BackendCode => self.backend_code != base.backend_code,
+                FactField::ApiEndpointSchema => self.api_endpoint_schema != base.api_endpoint_schema,
+                FactField::BackendStatus => self.backend_status != base.backend_status,
+                FactField::AbandonedAttempts => self.abandoned_attempts != base.abandoned_attempts,
+                FactField::BugReports => self.bug_reports != base.bug_reports,
+                FactField::CodeReview => self.code_review != base.code_review,
+                FactField::DataModel => self.data_model != base.data_model,
+                FactField::Deployment => self.deployment != base.deployment,
+                FactField::AgentRequests => self.agent_requests != base.agent_requests,
+            })
+            .collect()
+    }
+
+    // take over one field from a copy an agent worked on, append only fields keep both sides
+    pub fn merge_field(&mut self, from: &FactSheet, base: &FactSheet, field: FactField) {
+        match field {
+            FactField::ProjectDescription => self.project_description = from.project_description.clone(),
+            FactField::ProjectScope => self.project_scope = from.project_scope,
+            FactField::ExternalUrls => self.external_urls = from.external_urls.clone(),
+            FactField::BackendCode => self.backend_code = from.backend_code.clone(),
+            FactField::ApiEndpointSchema => self.api_endpoint_schema = from.api_endpoint_schema.clone(),
+            FactField::BackendStatus => self.backend_status = from.backend_status.clone(),
+            FactField::AbandonedAttempts => self.abandoned_attempts = from.abandoned_attempts.clone(),
+            FactField::BugReports => self.bug_reports.extend(
+                from.bug_reports
+                    .iter()
+                    .filter(|report| !base.bug_reports.contains(report))
+                    .cloned(),
+            ),
+            FactField::CodeReview => self.code_review = from.code_review.clone(),
+            FactField::DataModel => self.data_model = from.data_model.clone(),
+            FactField::Deployment => self.deployment = from.deployment.clone(),
+            FactField::AgentRequests => self.agent_requests.extend(
+                from.agent_requests
+                    .iter()
+                    .filter(|request| !base.agent_requests.contains(request))
+                    .cloned(),
+            ),
+        }
+    }
 }
 
 #[async_trait]
-pub trait SpecialFunctions: Debug {
+pub trait SpecialFunctions: Debug + Send {
     // used so that the manager can get attributes from agents
     fn get_attributes_from_agent(&self) -> &BasicAgent;
 
+    // lets the manager work out which agents can run at the same time
+    fn fact_access(&self) -> FactAccess;
+
     //this function will allow agents to execute their logic
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+    ) -> Result<(), AgentError>;
+
+    // offered open bug reports by the manager, returns true if the agent should run again
+    fn handle_bug_reports(&mut self, _reports: &[BugReport]) -> bool {
+        false
+    }
+
+    // offered a request raised by another agent, returns true if the agent should run again
+    fn handle_agent_request(&mut self, _request: &AgentRequest) -> bool {
+        false
+    }
+
+    // told which fields changed after other agents ran again, returns true to run again as well
+    fn handle_fact_changes(&mut self, _changed: &[FactField]) -> bool {
+        false
+    }
+
+    // lets the manager decide how to handle a failed execution
+    fn on_failure(&self, error: &AgentError) -> FailureAction {
+        if error.is_transient() {
+            FailureAction::Retry
+        } else {
+            FailureAction::Abort
+        }
+    }
 }
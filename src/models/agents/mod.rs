@@ -1,3 +1,8 @@
 pub mod agent_architect;
 pub mod agent_backend;
+pub mod agent_database;
+pub mod agent_devops;
+pub mod agent_docs;
+pub mod agent_reviewer;
+pub mod agent_test_engineer;
 pub mod agent_traits;
@@ -0,0 +1,211 @@
+use crate::models::agents::agent_traits::{FactAccess, FactField, FactSheet, Resource};
+use crate::models::general::errors::AgentError;
+
+// agents grouped into waves, each wave only depends on the ones before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentGraph {
+    waves: Vec<Vec<usize>>,
+}
+
+impl AgentGraph {
+    // nodes are in registration order, which decides the order of dependent agents
+    pub fn new(nodes: &[(usize, FactAccess)]) -> Self {
+        let mut levels: Vec<usize> = vec![0; nodes.len()];
+        for later in 0..nodes.len() {
+            for earlier in 0..later {
+                if depends_on(&nodes[later].1, &nodes[earlier].1) {
+                    levels[later] = levels[later].max(levels[earlier] + 1);
+                }
+            }
+        }
+
+        let wave_count: usize = levels.iter().max().map_or(0, |level| level + 1);
+        let mut waves: Vec<Vec<usize>> = vec![vec![]; wave_count];
+        for ((agent, _), level) in nodes.iter().zip(levels) {
+            waves[level].push(*agent);
+        }
+
+        Self { waves }
+    }
+
+    pub fn waves(&self) -> &[Vec<usize>] {
+        &self.waves
+    }
+}
+
+// later agents wait for earlier ones that write what they read or also write,
+// or that use the same resource
+fn depends_on(later: &FactAccess, earlier: &FactAccess) -> bool {
+    let shares_resource = |resource: &Resource| later.resources.contains(resource);
+    earlier.writes.iter().any(|field| {
+        later.reads.contains(field) || (later.writes.contains(field) && !field.is_append_only())
+    }) || earlier.resources.iter().any(shares_resource)
+}
+
+// an agent's copy of the factsheet after it ran in a wave
+#[derive(Debug, Clone)]
+pub struct AgentUpdate {
+    pub agent: String,
+    pub writes: &'static [FactField],
+    pub factsheet: FactSheet,
+}
+
+// fold the copies back into the shared factsheet, refusing undeclared or conflicting writes
+pub fn merge_updates(
+    factsheet: &mut FactSheet,
+    base: &FactSheet,
+    updates: &[AgentUpdate],
+) -> Result<(), AgentError> {
+    let mut written: Vec<(FactField, &str)> = vec![];
+    for update in updates {
+        for field in update.factsheet.changed_fields(base) {
+            if !update.writes.contains(&field) {
+                return Err(AgentError::UndeclaredWrite {
+                    agent: update.agent.clone(),
+                    field,
+                });
+            }
+            if !field.is_append_only() {
+                if let Some((_, other)) = written
+                    .iter()
+                    .find(|(written_field, _)| *written_field == field)
+                {
+                    return Err(AgentError::ConflictingWrites {
+                        field,
+                        agents: vec![other.to_string(), update.agent.clone()],
+                    });
+                }
+                written.push((field, &update.agent));
+            }
+            factsheet.merge_field(&update.factsheet, base, field);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::{BugReport, CodeReview};
+
+    fn access(reads: &'static [FactField], writes: &'static [FactField]) -> FactAccess {
+        FactAccess {
+            reads,
+            writes,
+            resources: &[],
+        }
+    }
+
+    #[test]
+    fn tests_groups_independent_agents_into_waves() {
+        let nodes: Vec<(usize, FactAccess)> = vec![
+            (
+                0,
+                access(&[FactField::ProjectDescription], &[FactField::ProjectScope]),
+            ),
+            (
+                1,
+                access(
+                    &[FactField::ProjectScope],
+                    &[FactField::DataModel, FactField::BackendCode],
+                ),
+            ),
+            (
+                2,
+                access(
+                    &[FactField::DataModel],
+                    &[FactField::BackendCode, FactField::ApiEndpointSchema],
+                ),
+            ),
+            (
+                3,
+                access(
+                    &[FactField::BackendCode],
+                    &[FactField::CodeReview, FactField::BugReports],
+                ),
+            ),
+            (
+                4,
+                access(&[FactField::ApiEndpointSchema], &[FactField::BugReports]),
+            ),
+            (5, access(&[FactField::ApiEndpointSchema], &[])),
+        ];
+
+        let graph = AgentGraph::new(&nodes);
+        assert_eq!(graph.waves(), &[vec![0], vec![1], vec![2], vec![3, 4, 5]]);
+
+        let reruns = AgentGraph::new(&[nodes[2], nodes[4]]);
+        assert_eq!(reruns.waves(), &[vec![2], vec![4]]);
+
+        // agents that both run cargo in the workspace take turns
+        let mut sharing: Vec<(usize, FactAccess)> = nodes.clone();
+        for (_, access) in &mut sharing[4..] {
+            access.resources = &[Resource::BackendWorkspace];
+        }
+        let graph = AgentGraph::new(&sharing);
+        assert_eq!(
+            graph.waves(),
+            &[vec![0], vec![1], vec![2], vec![3, 4], vec![5]]
+        );
+    }
+
+    #[test]
+    fn tests_merges_parallel_updates() {
+        let base: FactSheet = FactSheet::new("Tracks contacts".to_string());
+        let report = |reporter: &str| BugReport {
+            reporter: reporter.to_string(),
+            summary: "broken".to_string(),
+            detail: String::new(),
+        };
+
+        let mut reviewed: FactSheet = base.clone();
+        reviewed.code_review = Some(CodeReview {
+            approved: true,
+            issues: vec![],
+        });
+        reviewed.bug_reports.push(report("Code Reviewer"));
+        let mut tested: FactSheet = base.clone();
+        tested.bug_reports.push(report("Test Engineer"));
+
+        let mut updates: Vec<AgentUpdate> = vec![
+            AgentUpdate {
+                agent: "Code Reviewer".to_string(),
+                writes: &[FactField::CodeReview, FactField::BugReports],
+                factsheet: reviewed,
+            },
+            AgentUpdate {
+                agent: "Test Engineer".to_string(),
+                writes: &[FactField::BugReports],
+                factsheet: tested,
+            },
+        ];
+        let mut factsheet: FactSheet = base.clone();
+        merge_updates(&mut factsheet, &base, &updates).expect("parallel updates should merge");
+        assert!(factsheet.code_review.is_some());
+        assert_eq!(factsheet.bug_reports.len(), 2);
+
+        updates[1].factsheet.code_review = Some(CodeReview {
+            approved: false,
+            issues: vec![],
+        });
+        let undeclared = merge_updates(&mut base.clone(), &base, &updates);
+        assert!(matches!(
+            undeclared,
+            Err(AgentError::UndeclaredWrite {
+                field: FactField::CodeReview,
+                ..
+            })
+        ));
+
+        updates[1].writes = &[FactField::CodeReview, FactField::BugReports];
+        let conflicting = merge_updates(&mut base.clone(), &base, &updates);
+        assert!(matches!(
+            conflicting,
+            Err(AgentError::ConflictingWrites {
+                field: FactField::CodeReview,
+                ..
+            })
+        ));
+    }
+}
@@ -1,49 +1,66 @@
-use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
-use crate::helpers::general::ai_task_request;
+use crate::ai_functions::aifunc_managing::ConvertUserInputToGoal;
+use crate::helpers::command_line::{print_agent_error, PrintCommand};
+use crate::helpers::general::ai_function_request;
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agent_basic::basic_traits::BasicTraits;
 use crate::models::agents::agent_architect::AgentSolutionArchitect;
-use crate::models::agents::agent_traits::{FactSheet, SpecialFunctions};
-use crate::models::general::llm::Message;
+use crate::models::agents::agent_backend::AgentBackendDeveloper;
+use crate::models::agents::agent_database::AgentDatabaseDesigner;
+use crate::models::agents::agent_devops::AgentDevOps;
+use crate::models::agents::agent_docs::AgentDocsWriter;
+use crate::models::agents::agent_reviewer::AgentCodeReviewer;
+use crate::models::agents::agent_test_engineer::AgentTestEngineer;
+use crate::models::agents::agent_traits::{
+    AgentRequest, BackendStatus, BugReport, FactAccess, FactField, FactSheet, SpecialFunctions,
+};
+use crate::models::agents_manager::agent_graph::{merge_updates, AgentGraph, AgentUpdate};
+use crate::models::agents_manager::message_bus::{Delivery, MessageBus};
+use crate::models::general::errors::{AgentError, FailureAction};
+
+use tokio::task::JoinSet;
+
+// attempts per agent before a retryable failure is treated as fatal
+const MAX_AGENT_ATTEMPTS: u8 = 3;
+
+// times bug reports are handed back before the project finishes with them open
+const MAX_BUG_REPORT_ROUNDS: u8 = 2;
+
+// times requests between agents are delivered before new ones are dropped
+const MAX_REQUEST_ROUNDS: u8 = 2;
+
+// an agent handed back from its task with its copy of the factsheet
+type FinishedRun = (usize, Box<dyn SpecialFunctions>, FactSheet, Result<(), AgentError>);
 
 #[derive(Debug)]
 pub struct ManagingAgent {
     attributes: BasicAgent,
     factsheet: FactSheet,
     agents: Vec<Box<dyn SpecialFunctions>>,
+    bus: MessageBus,
 }
 
 impl ManagingAgent {
-    pub async fn new(usr_request: String) -> Result<Self, Box<dyn std::error::Error>> {
+    pub async fn new(usr_request: String) -> Result<Self, AgentError> {
         let position: String = "Project Manager".to_string();
 
-        let attributes: BasicAgent = BasicAgent {
+        let mut attributes: BasicAgent = BasicAgent {
             objective: "Manage agents who are building an excelllent website for user".to_string(),
-            position: position.clone(),
+            position,
             state: AgentState::Discovery,
             memory: vec![],
         };
 
-        let project_description: String = ai_task_request(
-            usr_request,
-            &position,
-            get_function_string!(convert_user_input_to_goal),
-            convert_user_input_to_goal,
-        )
-        .await;
+        let project_description: String =
+            ai_function_request::<ConvertUserInputToGoal>(&usr_request, &mut attributes).await?;
 
         let agents: Vec<Box<dyn SpecialFunctions>> = vec![];
-        let factsheet: FactSheet = FactSheet {
-            project_description,
-            project_scope: None,
-            external_urls: None,
-            backend_code: None,
-            api_endpoint_schema: None,
-        };
+        let factsheet: FactSheet = FactSheet::new(project_description);
 
         Ok(Self {
             attributes,
             factsheet,
             agents,
+            bus: MessageBus::default(),
         })
     }
 
@@ -53,17 +70,292 @@ impl ManagingAgent {
 
     fn create_agents(&mut self) {
         self.add_agent(Box::new(AgentSolutionArchitect::new()));
+        self.add_agent(Box::new(AgentDatabaseDesigner::new()));
+        self.add_agent(Box::new(AgentBackendDeveloper::new()));
+        self.add_agent(Box::new(AgentCodeReviewer::new()));
+        self.add_agent(Box::new(AgentTestEngineer::new()));
+        self.add_agent(Box::new(AgentDocsWriter::new()));
+        self.add_agent(Box::new(AgentDevOps::new()));
+    }
+
+    // run one agent, retrying or skipping it as the agent's failure policy decides
+    async fn run_agent(
+        manager_position: &str,
+        agent: &mut Box<dyn SpecialFunctions>,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
+        let mut attempts: u8 = 0;
+        loop {
+            attempts += 1;
+            let agent_res: Result<(), AgentError> = agent.execute(factsheet).await;
+
+            let error: AgentError = match agent_res {
+                Ok(()) => break,
+                Err(e) => e,
+            };
+
+            let agent_position: String = agent.get_attributes_from_agent().position.clone();
+            print_agent_error(&agent_position, &error);
+
+            match agent.on_failure(&error) {
+                FailureAction::Retry if attempts < MAX_AGENT_ATTEMPTS => {
+                    PrintCommand::Issue.print_agent_message(
+                        manager_position,
+                        &format!("Retrying {} (attempt {})", agent_position, attempts + 1),
+                    );
+                }
+                FailureAction::Skip => {
+                    PrintCommand::Issue.print_agent_message(
+                        manager_position,
+                        &format!("Skipping {}", agent_position),
+                    );
+                    break;
+                }
+                _ => return Err(error),
+            }
+        }
+
+        let agent_info = agent.get_attributes_from_agent();
+        dbg!(agent_info);
+        Ok(())
+    }
+
+    // run the agents wave by wave, agents in one wave work in parallel on copies of the factsheet
+    async fn run_agents(&mut self, indices: &[usize]) -> Result<(), AgentError> {
+        let nodes: Vec<(usize, FactAccess)> = indices
+            .iter()
+            .map(|&index| (index, self.agents[index].fact_access()))
+            .collect();
+        let graph: AgentGraph = AgentGraph::new(&nodes);
+
+        let mut slots: Vec<Option<Box<dyn SpecialFunctions>>> =
+            std::mem::take(&mut self.agents).into_iter().map(Some).collect();
+        let mut result: Result<(), AgentError> = Ok(());
+        for wave in graph.waves() {
+            result = self.run_wave(&mut slots, wave).await;
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.agents = slots.into_iter().flatten().collect();
+        result
+    }
+
+    async fn run_wave(
+        &mut self,
+        slots: &mut [Option<Box<dyn SpecialFunctions>>],
+        wave: &[usize],
+    ) -> Result<(), AgentError> {
+        if wave.len() > 1 {
+            let positions: Vec<String> = wave
+                .iter()
+                .filter_map(|&index| slots[index].as_ref())
+                .map(|agent| agent.get_attributes_from_agent().position.clone())
+                .collect();
+            PrintCommand::AICall.print_agent_message(
+                &self.attributes.position,
+                &format!("Running in parallel: {}", positions.join(", ")),
+            );
+        }
+
+        let base: FactSheet = self.factsheet.clone();
+        let mut tasks: JoinSet<FinishedRun> = JoinSet::new();
+        for &index in wave {
+            let Some(mut agent) = slots[index].take() else {
+                continue;
+            };
+            let manager_position: String = self.attributes.position.clone();
+            let mut factsheet: FactSheet = base.clone();
+            tasks.spawn(async move {
+                let result = Self::run_agent(&manager_position, &mut agent, &mut factsheet).await;
+                (index, agent, factsheet, result)
+            });
+        }
+
+        let mut updates: Vec<(usize, AgentUpdate)> = vec![];
+        let mut first_error: Option<AgentError> = None;
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((index, agent, factsheet, result)) => {
+                    let update: AgentUpdate = AgentUpdate {
+                        agent: agent.get_attributes_from_agent().position.clone(),
+                        writes: agent.fact_access().writes,
+                        factsheet,
+                    };
+                    slots[index] = Some(agent);
+                    match result {
+                        Ok(()) => updates.push((index, update)),
+                        Err(e) => {
+                            first_error.get_or_insert(e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    first_error.get_or_insert(AgentError::Aborted(format!("agent task stopped: {}", e)));
+                }
+            }
+        }
+        if let Some(error) = first_error {
+            return Err(error);
+        }
+
+        // merging in registration order keeps bug reports in a stable order
+        updates.sort_by_key(|(index, _)| *index);
+        let updates: Vec<AgentUpdate> = updates.into_iter().map(|(_, update)| update).collect();
+        merge_updates(&mut self.factsheet, &base, &updates)
     }
 
-    pub async fn execute_project(&mut self) {
+    // rerun the agents, then every agent that reads a field they changed, until nothing changes
+    async fn rerun_with_dependents(&mut self, mut batch: Vec<usize>) -> Result<(), AgentError> {
+        let mut has_run: Vec<usize> = vec![];
+        while !batch.is_empty() {
+            let base: FactSheet = self.factsheet.clone();
+            self.run_agents(&batch).await?;
+            has_run.extend(&batch);
+
+            let changed: Vec<FactField> = self
+                .factsheet
+                .changed_fields(&base)
+                .into_iter()
+                .filter(|field| !field.is_append_only())
+                .collect();
+            if changed.is_empty() {
+                break;
+            }
+
+            batch = vec![];
+            for index in 0..self.agents.len() {
+                let agent: &mut Box<dyn SpecialFunctions> = &mut self.agents[index];
+                let reads_changed: bool = agent
+                    .fact_access()
+                    .reads
+                    .iter()
+                    .any(|field| changed.contains(field));
+                if reads_changed && !has_run.contains(&index) && agent.handle_fact_changes(&changed)
+                {
+                    batch.push(index);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // deliver requests raised by agents and rerun the ones that take them
+    async fn resolve_agent_requests(&mut self) -> Result<(), AgentError> {
+        let mut rounds: u8 = 0;
+        while !self.factsheet.agent_requests.is_empty() {
+            let requests: Vec<AgentRequest> = std::mem::take(&mut self.factsheet.agent_requests);
+            if rounds >= MAX_REQUEST_ROUNDS {
+                for request in requests {
+                    let entry = self.bus.record(rounds, request, Delivery::Dropped);
+                    PrintCommand::Issue
+                        .print_agent_message(&self.attributes.position, &entry.to_string());
+                }
+                break;
+            }
+            rounds += 1;
+
+            let mut recipients: Vec<usize> = vec![];
+            for request in requests {
+                let recipient: Option<usize> = self
+                    .agents
+                    .iter()
+                    .position(|agent| agent.get_attributes_from_agent().position == request.to);
+                let delivery: Delivery = match recipient {
+                    Some(index) if self.agents[index].handle_agent_request(&request) => {
+                        if !recipients.contains(&index) {
+                            recipients.push(index);
+                        }
+                        Delivery::Accepted
+                    }
+                    Some(_) => Delivery::Declined,
+                    None => Delivery::NoRecipient,
+                };
+
+                let entry = self.bus.record(rounds, request, delivery);
+                let command: PrintCommand = match delivery {
+                    Delivery::Accepted => PrintCommand::AICall,
+                    _ => PrintCommand::Issue,
+                };
+                command.print_agent_message(&self.attributes.position, &entry.to_string());
+            }
+
+            recipients.sort_unstable();
+            self.rerun_with_dependents(recipients).await?;
+        }
+
+        Ok(())
+    }
+
+    // hand open bug reports back to the agents and rerun the ones that take them
+    async fn resolve_bug_reports(&mut self) -> Result<(), AgentError> {
+        let mut rounds: u8 = 0;
+        while !self.factsheet.bug_reports.is_empty() {
+            if rounds >= MAX_BUG_REPORT_ROUNDS {
+                PrintCommand::Issue.print_agent_message(
+                    &self.attributes.position,
+                    &format!(
+                        "{} bug report(s) still open after {} rounds",
+                        self.factsheet.bug_reports.len(),
+                        rounds
+                    ),
+                );
+                break;
+            }
+            rounds += 1;
+
+            let reports: Vec<BugReport> = std::mem::take(&mut self.factsheet.bug_reports);
+            let rerun: Vec<usize> = (0..self.agents.len())
+                .filter(|&index| self.agents[index].handle_bug_reports(&reports))
+                .collect();
+
+            if rerun.is_empty() {
+                self.factsheet.bug_reports = reports;
+                break;
+            }
+
+            PrintCommand::Issue.print_agent_message(
+                &self.attributes.position,
+                &format!("Round {}: handing {} bug report(s) back", rounds, reports.len()),
+            );
+            self.run_agents(&rerun).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn execute_project(&mut self) -> Result<(), AgentError> {
         self.create_agents();
-        for agent in &mut self.agents {
-            let agent_res: Result<(), Box<dyn std::error::Error>> =
-                agent.execute(&mut self.factsheet).await;
+        let all_agents: Vec<usize> = (0..self.agents.len()).collect();
+        self.run_agents(&all_agents).await?;
+
+        self.resolve_agent_requests().await?;
+        self.resolve_bug_reports().await?;
+        self.attributes.update_state(AgentState::Finished);
 
-            let agent_info = agent.get_attributes_from_agent();
-            dbg!(agent_info);
+        if !self.bus.log().is_empty() {
+            PrintCommand::AICall.print_agent_message(
+                &self.attributes.position,
+                &format!(
+                    "{} request(s) exchanged between agents, {} accepted",
+                    self.bus.log().len(),
+                    self.bus.accepted()
+                ),
+            );
         }
+
+        if let Some(status) = &self.factsheet.backend_status {
+            let status_msg: String = format!("Backend status: {}", status);
+            match status {
+                BackendStatus::Green => PrintCommand::UnitTest,
+                BackendStatus::Degraded { .. } => PrintCommand::Issue,
+            }
+            .print_agent_message(&self.attributes.position, &status_msg);
+        }
+
+        Ok(())
     }
 }
 
@@ -71,6 +363,309 @@ impl ManagingAgent {
 mod tests {
 
     use super::*;
+    use crate::helpers::workspace::FileMap;
+    use crate::models::agents::agent_traits::AgentRequestKind;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    // records each run and raises one request on its first run
+    #[derive(Debug)]
+    struct MockAgent {
+        attributes: BasicAgent,
+        access: FactAccess,
+        request: Option<AgentRequest>,
+        runs: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockAgent {
+        fn boxed(
+            position: &str,
+            access: FactAccess,
+            request: Option<AgentRequest>,
+            runs: &Arc<Mutex<Vec<String>>>,
+        ) -> Box<dyn SpecialFunctions> {
+            Box::new(Self {
+                attributes: BasicAgent {
+                    objective: String::new(),
+                    position: position.to_string(),
+                    state: AgentState::Discovery,
+                    memory: vec![],
+                },
+                access,
+                request,
+                runs: Arc::clone(runs),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for MockAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        fn fact_access(&self) -> FactAccess {
+            self.access
+        }
+
+        fn handle_agent_request(&mut self, _request: &AgentRequest) -> bool {
+            self.attributes.state = AgentState::Discovery;
+            true
+        }
+
+        fn handle_fact_changes(&mut self, _changed: &[FactField]) -> bool {
+            self.attributes.state = AgentState::Discovery;
+            true
+        }
+
+        async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+            if self.attributes.state == AgentState::Finished {
+                return Ok(());
+            }
+            let mut runs = self.runs.lock().unwrap();
+            runs.push(self.attributes.position.clone());
+
+            let run_count: usize = runs
+                .iter()
+                .filter(|position| **position == self.attributes.position)
+                .count();
+            if self.access.writes.contains(&FactField::ExternalUrls) {
+                factsheet.external_urls = Some(vec![format!("https://example.com/{}", run_count)]);
+            }
+            if self.access.writes.contains(&FactField::BackendCode) {
+                factsheet.backend_code = Some(FileMap::from([(
+                    "src/main.rs".to_string(),
+                    run_count.to_string(),
+                )]));
+            }
+            if let Some(request) = self.request.take() {
+                factsheet.agent_requests.push(request);
+            }
+            self.attributes.state = AgentState::Finished;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn tests_requests_rerun_recipient_and_dependents() {
+        let runs: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let replace_url = AgentRequest {
+            from: "Backend".to_string(),
+            to: "Architect".to_string(),
+            kind: AgentRequestKind::ReplaceUrl {
+                url: "https://example.com/1".to_string(),
+                reason: "returned status 404".to_string(),
+            },
+        };
+        let unknown = AgentRequest {
+            from: "Docs".to_string(),
+            to: "Designer".to_string(),
+            kind: AgentRequestKind::AddRequirement {
+                requirement: "dark mode".to_string(),
+            },
+        };
+
+        let mut managing_agent: ManagingAgent = ManagingAgent {
+            attributes: BasicAgent {
+                objective: String::new(),
+                position: "Project Manager".to_string(),
+                state: AgentState::Discovery,
+                memory: vec![],
+            },
+            factsheet: FactSheet::new("Shows the time".to_string()),
+            agents: vec![
+                MockAgent::boxed(
+                    "Architect",
+                    FactAccess {
+                        reads: &[FactField::ProjectDescription],
+                        writes: &[FactField::ExternalUrls],
+                        resources: &[],
+                    },
+                    None,
+                    &runs,
+                ),
+                MockAgent::boxed(
+                    "Backend",
+                    FactAccess {
+                        reads: &[FactField::ExternalUrls],
+                        writes: &[FactField::BackendCode, FactField::AgentRequests],
+                        resources: &[],
+                    },
+                    Some(replace_url),
+                    &runs,
+                ),
+                MockAgent::boxed(
+                    "Docs",
+                    FactAccess {
+                        reads: &[FactField::BackendCode],
+                        writes: &[FactField::AgentRequests],
+                        resources: &[],
+                    },
+                    Some(unknown),
+                    &runs,
+                ),
+            ],
+            bus: MessageBus::default(),
+        };
+
+        managing_agent
+            .run_agents(&[0, 1, 2])
+            .await
+            .expect("agents should run");
+        managing_agent
+            .resolve_agent_requests()
+            .await
+            .expect("requests should resolve");
+
+        assert_eq!(
+            *runs.lock().unwrap(),
+            vec![
+                "Architect",
+                "Backend",
+                "Docs",
+                "Architect",
+                "Backend",
+                "Docs"
+            ]
+        );
+        let deliveries: Vec<Delivery> = managing_agent
+            .bus
+            .log()
+            .iter()
+            .map(|entry| entry.delivery)
+            .collect();
+        assert_eq!(deliveries, vec![Delivery::Accepted, Delivery::NoRecipient]);
+        assert_eq!(
+            managing_agent.factsheet.external_urls,
+            Some(vec!["https://example.com/2".to_string()])
+        );
+    }
+
+    // the real backend raising requests for the urls it found broken, without writing code
+    #[derive(Debug)]
+    struct UrlCheckingBackend {
+        backend: AgentBackendDeveloper,
+        runs: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for UrlCheckingBackend {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            self.backend.get_attributes_from_agent()
+        }
+
+        fn fact_access(&self) -> FactAccess {
+            self.backend.fact_access()
+        }
+
+        fn handle_fact_changes(&mut self, changed: &[FactField]) -> bool {
+            self.backend.handle_fact_changes(changed)
+        }
+
+        async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+            self.runs
+                .lock()
+                .unwrap()
+                .push(self.get_attributes_from_agent().position.clone());
+            let failures: Vec<(String, String)> = factsheet
+                .external_urls
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|url| url.ends_with("/broken"))
+                .map(|url| (url, "returned status 404".to_string()))
+                .collect();
+            self.backend.raise_url_replacements(factsheet, failures);
+            Ok(())
+        }
+    }
+
+    // the real architect taking requests, with made up urls instead of asking the llm
+    #[derive(Debug)]
+    struct ReplacingArchitect {
+        architect: AgentSolutionArchitect,
+        runs: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for ReplacingArchitect {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            self.architect.get_attributes_from_agent()
+        }
+
+        fn fact_access(&self) -> FactAccess {
+            self.architect.fact_access()
+        }
+
+        fn handle_agent_request(&mut self, request: &AgentRequest) -> bool {
+            self.architect.handle_agent_request(request)
+        }
+
+        async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+            let mut runs = self.runs.lock().unwrap();
+            let first_run: bool = runs.is_empty();
+            runs.push(self.get_attributes_from_agent().position.clone());
+            let url: &str = if first_run { "broken" } else { "working" };
+            factsheet.external_urls = Some(vec![format!("https://example.com/{}", url)]);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn tests_backend_url_request_reruns_architect() {
+        let runs: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let mut managing_agent: ManagingAgent = ManagingAgent {
+            attributes: BasicAgent {
+                objective: String::new(),
+                position: "Project Manager".to_string(),
+                state: AgentState::Discovery,
+                memory: vec![],
+            },
+            factsheet: FactSheet::new("Shows the weather".to_string()),
+            agents: vec![
+                Box::new(ReplacingArchitect {
+                    architect: AgentSolutionArchitect::new(),
+                    runs: Arc::clone(&runs),
+                }),
+                Box::new(UrlCheckingBackend {
+                    backend: AgentBackendDeveloper::new(),
+                    runs: Arc::clone(&runs),
+                }),
+            ],
+            bus: MessageBus::default(),
+        };
+
+        managing_agent
+            .run_agents(&[0, 1])
+            .await
+            .expect("agents should run");
+        managing_agent
+            .resolve_agent_requests()
+            .await
+            .expect("requests should resolve");
+
+        assert_eq!(
+            *runs.lock().unwrap(),
+            vec![
+                "Solutions Architect",
+                "Backend Developer",
+                "Solutions Architect",
+                "Backend Developer"
+            ]
+        );
+        let log = managing_agent.bus.log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].delivery, Delivery::Accepted);
+        assert_eq!(log[0].request.from, "Backend Developer");
+        assert!(matches!(
+            &log[0].request.kind,
+            AgentRequestKind::ReplaceUrl { url, .. } if url == "https://example.com/broken"
+        ));
+        assert_eq!(
+            managing_agent.factsheet.external_urls,
+            Some(vec!["https://example.com/working".to_string()])
+        );
+    }
 
     #[tokio::test]
     async fn tests_managing_agent() {
@@ -80,7 +675,10 @@ mod tests {
             .await
             .expect("Error creating managing request");
 
-        managing_agent.execute_project().await;
+        managing_agent
+            .execute_project()
+            .await
+            .expect("Error executing project");
 
         dbg!(managing_agent.factsheet);
     }
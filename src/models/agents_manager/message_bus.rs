@@ -0,0 +1,89 @@
+use crate::models::agents::agent_traits::AgentRequest;
+use std::fmt;
+
+// what happened to a request the manager tried to deliver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+    Accepted,
+    Declined,
+    // no agent holds the position the request was addressed to
+    NoRecipient,
+    // raised after the last round, so nobody was asked
+    Dropped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BusEntry {
+    pub round: u8,
+    pub request: AgentRequest,
+    pub delivery: Delivery,
+}
+
+impl fmt::Display for BusEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "round {}: {} -> {}: {} ({:?})",
+            self.round, self.request.from, self.request.to, self.request.kind, self.delivery
+        )
+    }
+}
+
+// the requests exchanged between agents during a project
+#[derive(Debug, Default)]
+pub struct MessageBus {
+    log: Vec<BusEntry>,
+}
+
+impl MessageBus {
+    pub fn record(&mut self, round: u8, request: AgentRequest, delivery: Delivery) -> &BusEntry {
+        self.log.push(BusEntry {
+            round,
+            request,
+            delivery,
+        });
+        &self.log[self.log.len() - 1]
+    }
+
+    pub fn log(&self) -> &[BusEntry] {
+        &self.log
+    }
+
+    pub fn accepted(&self) -> usize {
+        self.log
+            .iter()
+            .filter(|entry| entry.delivery == Delivery::Accepted)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::AgentRequestKind;
+
+    #[test]
+    fn tests_logs_delivered_requests() {
+        let mut bus: MessageBus = MessageBus::default();
+        let request = AgentRequest {
+            from: "Backend Developer".to_string(),
+            to: "Solutions Architect".to_string(),
+            kind: AgentRequestKind::ReplaceUrl {
+                url: "http://worldtimeapi.org/api/timezone".to_string(),
+                reason: "connection refused".to_string(),
+            },
+        };
+
+        let entry: String = bus
+            .record(1, request.clone(), Delivery::Accepted)
+            .to_string();
+        assert_eq!(
+            entry,
+            "round 1: Backend Developer -> Solutions Architect: replace http://worldtimeapi.org/api/timezone: connection refused (Accepted)"
+        );
+
+        bus.record(2, request, Delivery::Dropped);
+        assert_eq!(bus.log().len(), 2);
+        assert_eq!(bus.accepted(), 1);
+    }
+}
@@ -1 +1,3 @@
+pub mod agent_graph;
+pub mod message_bus;
 pub mod managing_agent;
@@ -0,0 +1,210 @@
+use crate::helpers::sandbox::SandboxError;
+use crate::models::agents::agent_traits::FactField;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum AgentError {
+    // missing or invalid settings such as api keys
+    Config(String),
+    // the request to the llm could not be completed
+    LlmRequest(reqwest::Error),
+    // the llm answered but not with something usable
+    LlmResponse(String),
+    // the llm output could not be decoded into the expected type
+    Decode {
+        operation: String,
+        source: serde_json::Error,
+    },
+    Io {
+        context: String,
+        source: io::Error,
+    },
+    Http(reqwest::Error),
+    // an external program such as git exited unsuccessfully
+    CommandFailed {
+        command: String,
+        stderr: String,
+    },
+    Sandbox(SandboxError),
+    // generated files failed validation
+    InvalidFiles(String),
+    // a field the agent depends on has not been filled in by an earlier agent
+    MissingFact(&'static str),
+    // the generated code kept failing to build
+    TooManyBugs {
+        bug_count: u8,
+        errors: String,
+    },
+    // the user stopped the run
+    Aborted(String),
+    // an agent changed a factsheet field it did not declare as written
+    UndeclaredWrite {
+        agent: String,
+        field: FactField,
+    },
+    // agents running in parallel changed the same factsheet field
+    ConflictingWrites {
+        field: FactField,
+        agents: Vec<String>,
+    },
+    // the prompt does not fit the model context window even without history
+    ContextOverflow {
+        operation: String,
+        tokens: usize,
+        budget: usize,
+    },
+}
+
+impl AgentError {
+    pub fn io(context: &str, source: io::Error) -> Self {
+        Self::Io {
+            context: context.to_string(),
+            source,
+        }
+    }
+
+    // failures that may succeed if the same work is attempted again
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::LlmRequest(_)
+                | Self::LlmResponse(_)
+                | Self::Decode { .. }
+                | Self::InvalidFiles(_)
+                | Self::Http(_)
+        )
+    }
+
+    // the error followed by each of its causes
+    pub fn chain(&self) -> Vec<String> {
+        let mut chain: Vec<String> = vec![self.to_string()];
+        let mut source: Option<&(dyn Error + 'static)> = self.source();
+        while let Some(cause) = source {
+            chain.push(cause.to_string());
+            source = cause.source();
+        }
+        chain
+    }
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(msg) => write!(f, "configuration error: {}", msg),
+            Self::LlmRequest(_) => write!(f, "failed to call LLM"),
+            Self::LlmResponse(msg) => write!(f, "unusable LLM response: {}", msg),
+            Self::Decode { operation, .. } => {
+                write!(f, "failed to decode ai response for {}", operation)
+            }
+            Self::Io { context, .. } => write!(f, "{}", context),
+            Self::Http(_) => write!(f, "http request failed"),
+            Self::CommandFailed { command, stderr } => {
+                write!(f, "'{}' failed: {}", command, stderr)
+            }
+            Self::Sandbox(_) => write!(f, "sandboxed execution failed"),
+            Self::InvalidFiles(reason) => write!(f, "invalid generated files: {}", reason),
+            Self::MissingFact(field) => write!(f, "no {} on factsheet", field),
+            Self::TooManyBugs { bug_count, errors } => write!(
+                f,
+                "too many bugs in code after {} attempts, last errors: {}",
+                bug_count,
+                errors.trim()
+            ),
+            Self::Aborted(reason) => write!(f, "aborted: {}", reason),
+            Self::UndeclaredWrite { agent, field } => {
+                write!(f, "{} changed {} without declaring it", agent, field)
+            }
+            Self::ConflictingWrites { field, agents } => {
+                write!(f, "{} all changed {} in parallel", agents.join(", "), field)
+            }
+            Self::ContextOverflow {
+                operation,
+                tokens,
+                budget,
+            } => write!(
+                f,
+                "prompt for {} needs {} tokens but only {} fit the context window",
+                operation, tokens, budget
+            ),
+        }
+    }
+}
+
+impl Error for AgentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::LlmRequest(e) | Self::Http(e) => Some(e),
+            Self::Decode { source, .. } => Some(source),
+            Self::Io { source, .. } => Some(source),
+            Self::Sandbox(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SandboxError> for AgentError {
+    fn from(e: SandboxError) -> Self {
+        Self::Sandbox(e)
+    }
+}
+
+impl From<reqwest::Error> for AgentError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+// what the manager should do when an agent fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailureAction {
+    Retry,
+    Skip,
+    Abort,
+}
+
+impl FailureAction {
+    // for agents the project is complete without, only the user stopping the run ends it
+    pub fn skip_unless_aborted(error: &AgentError) -> Self {
+        match error {
+            AgentError::Aborted(_) => Self::Abort,
+            _ => Self::Skip,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_error_chain_includes_causes() {
+        let source = io::Error::new(io::ErrorKind::NotFound, "no such file");
+        let error = AgentError::Sandbox(SandboxError::Io(source));
+
+        let chain = error.chain();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[0], "sandboxed execution failed");
+        assert_eq!(chain[2], "no such file");
+        assert!(!error.is_transient());
+        assert_eq!(
+            FailureAction::skip_unless_aborted(&error),
+            FailureAction::Skip
+        );
+
+        let bugs = AgentError::TooManyBugs {
+            bug_count: 3,
+            errors: "error[E0425]: cannot find value `db`\n".to_string(),
+        };
+        assert_eq!(
+            bugs.to_string(),
+            "too many bugs in code after 3 attempts, last errors: error[E0425]: cannot find value `db`"
+        );
+        let aborted = AgentError::Aborted("stopped by user".to_string());
+        assert_eq!(
+            FailureAction::skip_unless_aborted(&aborted),
+            FailureAction::Abort
+        );
+    }
+}
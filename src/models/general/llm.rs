@@ -1,9 +1,115 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    // the result of a tool the assistant asked for
+    Tool,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let role: &str = match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Assistant => "assistant",
+            Self::Tool => "tool",
+        };
+        f.write_str(role)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    // json encoded arguments, as written by the llm
+    pub arguments: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
 pub struct Message {
-    pub role: String,
+    pub role: Role,
     pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // tools the assistant asked for, answered by tool messages with the same ids
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    // the ai function the message belongs to, kept in agent memory only
+    #[serde(skip)]
+    pub operation: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            name: None,
+            tool_calls: vec![],
+            tool_call_id: None,
+            operation: None,
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    pub fn tool_result(tool_call_id: &str, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.to_string()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_operation(mut self, operation: &str) -> Self {
+        self.operation = Some(operation.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    // json schema of the arguments
+    pub parameters: serde_json::Value,
+}
+
+// a tool the llm may call instead of answering
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -11,19 +117,125 @@ pub struct ChatCompletion {
     pub model: String,
     pub messages: Vec<Message>,
     pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    // number of choices to generate, one when left out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u8>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    // "none" keeps the tools readable in the history but stops new calls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<String>,
+    // send the completion as server sent events while it is written
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+}
+
+// why the llm stopped writing a choice
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Stop,
+    // cut off at the token limit
+    Length,
+    ToolCalls,
+    ContentFilter,
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct APIMessage {
-    pub content: String,
+    pub role: Role,
+    // null when the assistant only calls tools
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct APIChoice {
     pub message: APIMessage,
+    pub finish_reason: Option<FinishReason>,
+}
+
+impl APIChoice {
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason == Some(FinishReason::Length)
+    }
+
+    // the choice as a message that can be sent back in a follow up call
+    pub fn into_message(self) -> Message {
+        Message {
+            tool_calls: self.message.tool_calls,
+            ..Message::new(self.message.role, self.message.content.unwrap_or_default())
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct APIResponse {
     pub choices: Vec<APIChoice>,
 }
+
+// the text added to a choice by one streamed event
+#[derive(Debug, Deserialize)]
+pub struct StreamDelta {
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamChoice {
+    #[serde(default)]
+    pub index: usize,
+    pub delta: StreamDelta,
+    pub finish_reason: Option<FinishReason>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamChunk {
+    pub choices: Vec<StreamChoice>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_decodes_tool_calls_and_finish_reasons() {
+        let response: APIResponse = serde_json::from_str(
+            r#"{"choices": [
+                {"index": 0, "finish_reason": "tool_calls", "message": {"role": "assistant", "content": null,
+                    "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "read_file", "arguments": "{\"path\": \"src/main.rs\"}"}}]}},
+                {"index": 1, "finish_reason": "length", "message": {"role": "assistant", "content": "fn main() {"}}
+            ]}"#,
+        )
+        .expect("response should decode");
+
+        let mut choices = response.choices.into_iter();
+        let called: APIChoice = choices.next().unwrap();
+        assert_eq!(called.finish_reason, Some(FinishReason::ToolCalls));
+        let message: Message = called.into_message();
+        assert_eq!(message.tool_calls[0].function.name, "read_file");
+
+        let cut: APIChoice = choices.next().unwrap();
+        assert!(cut.is_truncated());
+
+        let encoded = serde_json::to_value(vec![
+            message,
+            Message::tool_result("call_1", "fn main() {}"),
+            Message::user("hello").with_name("Backend_Developer"),
+        ])
+        .unwrap();
+        assert_eq!(encoded[0]["content"], "");
+        assert_eq!(encoded[1]["role"], "tool");
+        assert_eq!(encoded[1]["tool_call_id"], "call_1");
+        assert_eq!(encoded[2]["name"], "Backend_Developer");
+        assert!(encoded[2].get("tool_calls").is_none());
+    }
+}